@@ -0,0 +1,177 @@
+//! Apache Arrow conversion of query results.
+//!
+//! Columns keep their Postgres types where Arrow has a match (integers,
+//! floats, bools, numerics, dates and timestamps). Everything else is written
+//! as UTF-8 using the shared JSON decoder's rendering, so a column looks the
+//! same here as it does in the result grid.
+
+use crate::decode::{column_to_json, decode_numeric, raw_column, resolve_base_type};
+use arrow::array::{
+    Array, ArrayRef, BooleanArray, Date32Array, Decimal128Array, Float32Array, Float64Array,
+    Int16Array, Int32Array, Int64Array, StringArray, TimestampMicrosecondArray, UInt32Array,
+};
+use arrow::datatypes::{Field, Schema};
+use arrow::error::ArrowError;
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+use std::sync::Arc;
+use tokio_postgres::types::{FromSql, Type};
+use tokio_postgres::{Column, Row};
+
+/// Offset from the Unix epoch to the Postgres epoch (2000-01-01).
+const PG_EPOCH_MICROS: i64 = 946_684_800_000_000;
+const PG_EPOCH_DAYS: i32 = 10_957;
+
+/// Most digits a `Decimal128` can hold.
+const MAX_DECIMAL_PRECISION: u8 = 38;
+
+/// Types converted natively here that the JSON decoder doesn't handle, so the
+/// query must not cast them to text.
+pub(crate) fn has_arrow_type(ty: &Type) -> bool {
+    matches!(resolve_base_type(ty).name(), "date" | "timestamp" | "timestamptz")
+}
+
+/// Decode column `idx` of every row with `T`'s binary format.
+fn values<'a, T: FromSql<'a>>(rows: &'a [Row], idx: usize, wire_type: &Type) -> Vec<Option<T>> {
+    rows.iter()
+        .map(|row| raw_column(row, idx).1.and_then(|raw| T::from_sql(wire_type, raw).ok()))
+        .collect()
+}
+
+/// Timestamps and dates use `±infinity` sentinels that Arrow can't represent;
+/// those become nulls.
+fn finite<T: PartialEq + Copy>(value: Option<T>, min: T, max: T) -> Option<T> {
+    value.filter(|v| *v != min && *v != max)
+}
+
+/// A Postgres-epoch timestamp in Unix-epoch microseconds. `None` for
+/// timestamps near the top of Postgres' range, which don't fit in an `i64`
+/// once shifted.
+fn unix_micros(pg_micros: i64) -> Option<i64> {
+    pg_micros.checked_add(PG_EPOCH_MICROS)
+}
+
+/// `text` as an integer count of 10^-`scale` units. `None` for NaN and
+/// infinities, or when it needs more digits than a `Decimal128` holds.
+fn scaled_decimal(text: &str, scale: usize) -> Option<i128> {
+    let (whole, fraction) = text.split_once('.').unwrap_or((text, ""));
+    let digits = format!("{}{}{}", whole, fraction, "0".repeat(scale - fraction.len()));
+    if digits.trim_start_matches('-').trim_start_matches('0').len() > MAX_DECIMAL_PRECISION as usize {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+/// Numerics become `Decimal128` at the widest scale present, falling back to
+/// text when a value doesn't fit (more than 38 digits, NaN, infinity).
+fn numeric_array(rows: &[Row], idx: usize) -> Result<ArrayRef, ArrowError> {
+    let texts: Vec<Option<String>> = rows
+        .iter()
+        .map(|row| raw_column(row, idx).1.and_then(decode_numeric))
+        .collect();
+    let scale = texts
+        .iter()
+        .flatten()
+        .map(|text| text.split_once('.').map_or(0, |(_, fraction)| fraction.len()))
+        .max()
+        .unwrap_or(0);
+
+    let scaled: Option<Vec<Option<i128>>> = texts
+        .iter()
+        .map(|text| match text {
+            Some(text) => scaled_decimal(text, scale).map(Some),
+            None => Some(None),
+        })
+        .collect();
+
+    match scaled {
+        Some(scaled) if scale <= MAX_DECIMAL_PRECISION as usize => Ok(Arc::new(
+            Decimal128Array::from(scaled).with_precision_and_scale(MAX_DECIMAL_PRECISION, scale as i8)?,
+        )),
+        _ => Ok(Arc::new(texts.iter().map(Option::as_deref).collect::<StringArray>())),
+    }
+}
+
+fn column_array(rows: &[Row], idx: usize, ty: &Type) -> Result<ArrayRef, ArrowError> {
+    let array: ArrayRef = match resolve_base_type(ty).name() {
+        "int2" => Arc::new(Int16Array::from(values::<i16>(rows, idx, &Type::INT2))),
+        "int4" => Arc::new(Int32Array::from(values::<i32>(rows, idx, &Type::INT4))),
+        "int8" => Arc::new(Int64Array::from(values::<i64>(rows, idx, &Type::INT8))),
+        "float4" => Arc::new(Float32Array::from(values::<f32>(rows, idx, &Type::FLOAT4))),
+        "float8" => Arc::new(Float64Array::from(values::<f64>(rows, idx, &Type::FLOAT8))),
+        "bool" => Arc::new(BooleanArray::from(values::<bool>(rows, idx, &Type::BOOL))),
+        "oid" => Arc::new(UInt32Array::from(values::<u32>(rows, idx, &Type::OID))),
+        "numeric" => numeric_array(rows, idx)?,
+        // Binary dates and timestamps are plain integers from the Postgres epoch
+        "date" => Arc::new(Date32Array::from(
+            values::<i32>(rows, idx, &Type::INT4)
+                .into_iter()
+                .map(|days| finite(days, i32::MIN, i32::MAX).and_then(|days| days.checked_add(PG_EPOCH_DAYS)))
+                .collect::<Vec<_>>(),
+        )),
+        name @ ("timestamp" | "timestamptz") => {
+            let micros: Vec<Option<i64>> = values::<i64>(rows, idx, &Type::INT8)
+                .into_iter()
+                .map(|micros| finite(micros, i64::MIN, i64::MAX).and_then(unix_micros))
+                .collect();
+            let array = TimestampMicrosecondArray::from(micros);
+            if name == "timestamptz" {
+                Arc::new(array.with_timezone("UTC"))
+            } else {
+                Arc::new(array)
+            }
+        }
+        _ => Arc::new(
+            rows.iter()
+                .map(|row| match column_to_json(row, idx) {
+                    serde_json::Value::Null => None,
+                    serde_json::Value::String(text) => Some(text),
+                    other => Some(other.to_string()),
+                })
+                .collect::<StringArray>(),
+        ),
+    };
+    Ok(array)
+}
+
+/// Convert a result set into a single record batch with one nullable field
+/// per column.
+pub(crate) fn record_batch(columns: &[Column], rows: &[Row]) -> Result<RecordBatch, ArrowError> {
+    let mut fields = Vec::with_capacity(columns.len());
+    let mut arrays = Vec::with_capacity(columns.len());
+    for (idx, col) in columns.iter().enumerate() {
+        let array = column_array(rows, idx, col.type_())?;
+        fields.push(Field::new(col.name(), array.data_type().clone(), true));
+        arrays.push(array);
+    }
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)
+}
+
+/// Write `batch` as an Arrow IPC file. Blocking; run it off the async runtime.
+pub(crate) fn write_ipc_file(path: &str, batch: &RecordBatch) -> Result<(), ArrowError> {
+    let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let mut writer = FileWriter::try_new(file, &batch.schema())?;
+    writer.write(batch)?;
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scaled_decimal_pads_to_scale() {
+        assert_eq!(scaled_decimal("12.5", 3), Some(12500));
+        assert_eq!(scaled_decimal("-0.25", 2), Some(-25));
+        assert_eq!(scaled_decimal("7", 0), Some(7));
+        assert_eq!(scaled_decimal("NaN", 0), None);
+        assert_eq!(scaled_decimal(&"9".repeat(39), 0), None);
+    }
+
+    #[test]
+    fn test_unix_micros_overflow_is_none() {
+        assert_eq!(unix_micros(0), Some(PG_EPOCH_MICROS));
+        // 294276-12-31 23:59:59.999999, the largest timestamp Postgres accepts
+        assert_eq!(unix_micros(9_223_371_331_199_999_999), None);
+    }
+}
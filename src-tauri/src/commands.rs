@@ -1,17 +1,22 @@
-use tauri::{command, Window, Emitter, Error};
+use tauri::{command, Window, Emitter};
 use crate::db::{self, QueryHistory, QueryHistoryEntry};
 use crate::credentials;
+use crate::error::AppError;
+use crate::postgres::PooledClient;
 use serde::{Deserialize, Serialize};
 use chrono::Utc;
 use uuid::Uuid;
 use std::path::Path;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::fs::File;
-use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, BufWriter};
 use tokio_postgres::CopyInSink;
+use crate::decode::row_to_json;
 use bytes::Bytes;
 use futures_util::{SinkExt, StreamExt};
 use std::pin::Pin;
+use std::task::{Context, Poll};
+use sha2::{Digest, Sha256};
 
 #[derive(Serialize, Deserialize)]
 pub struct QueryResult {
@@ -22,11 +27,59 @@ pub struct QueryResult {
     pub message: Option<String>,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct SampledQueryResult {
+    #[serde(flatten)]
+    pub result: QueryResult,
+    /// True when only a `TABLESAMPLE` of the table was read, so the rows
+    /// (and any aggregates) are approximate
+    pub sampled: bool,
+}
+
+#[derive(Serialize)]
+pub struct ExecuteQueryResult {
+    #[serde(flatten)]
+    pub result: QueryResult,
+    /// Notices and warnings the server sent while the statement ran
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub notices: Vec<crate::postgres::Notice>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct ColumnInfo {
     pub name: String,
     #[serde(rename = "type")]
     pub type_: Option<String>,
+    /// Cell renderer the grid should use; see `decode::render_hint`
+    #[serde(rename = "renderHint", default)]
+    pub render_hint: String,
+}
+
+/// Target definition of one column for `generate_alter`.
+#[derive(Serialize, Deserialize)]
+pub struct DesiredColumn {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub data_type: String,
+    pub nullable: bool,
+}
+
+/// Extract `path` from a jsonb `column` of a SELECT result as its own text column.
+#[derive(Serialize, Deserialize)]
+pub struct JsonProjection {
+    pub column: String,
+    pub path: Vec<String>,
+    /// Result column name; defaults to `column.path.segments`
+    pub alias: Option<String>,
+}
+
+/// Portable form of a deduplicated history entry used by export/import.
+#[derive(Serialize, Deserialize)]
+pub struct HistoryExportEntry {
+    pub sql: String,
+    pub normalized_sql: String,
+    pub last_executed_at: i64,
+    pub execution_count: i64,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -53,1361 +106,7215 @@ pub struct DashboardMetrics {
     pub collected_at: i64,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct TerminatedConnections {
+    pub count: usize,
+    pub pids: Vec<i32>,
+}
+
+/// One server's dashboard in a batch: either `metrics` or `error` is set.
+#[derive(Serialize)]
+pub struct ServerDashboard {
+    #[serde(rename = "serverId")]
+    pub server_id: String,
+    pub metrics: Option<DashboardMetrics>,
+    pub error: Option<AppError>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct DashboardConnection {
     pub user: String,
     pub query: String,
 }
 
-fn system_time_to_epoch_millis(time: SystemTime) -> Option<i64> {
-    time.duration_since(UNIX_EPOCH)
-        .ok()
-        .and_then(|d| i64::try_from(d.as_millis()).ok())
+#[derive(Serialize, Deserialize)]
+pub struct ConstraintInfo {
+    pub name: String,
+    /// One of `PK`, `FK`, `UNIQUE`, `CHECK`, `EXCLUDE`, `TRIGGER`
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub definition: String,
+    /// Schema-qualified target of a foreign key
+    #[serde(rename = "referencedTable")]
+    pub referenced_table: Option<String>,
 }
 
-fn format_pg_error(error: &tokio_postgres::Error) -> String {
-    if let Some(db_err) = error.as_db_error() {
-        format!(
-            "{}: {}\nDetail: {}\nHint: {}\nWhere: {}",
-            db_err.code().code(),
-            db_err.message(),
-            db_err.detail().unwrap_or("(none)"),
-            db_err.hint().unwrap_or("(none)"),
-            db_err.where_().unwrap_or("(none)")
-        )
-    } else {
-        error.to_string()
-    }
+#[derive(Serialize, Deserialize)]
+pub struct TableEditability {
+    pub editable: bool,
+    /// Columns identifying a row: the primary key, or a unique non-null index
+    #[serde(rename = "primaryKey")]
+    pub primary_key: Vec<String>,
+    /// Why the table can't be edited, or which fallback key is in use
+    pub reason: Option<String>,
 }
 
-fn quote_ident(ident: &str) -> String {
-    format!("\"{}\"", ident.replace('"', "\"\""))
+#[derive(Serialize, Deserialize)]
+pub struct SchemaSize {
+    pub schema: String,
+    /// Sum of `pg_total_relation_size` (heap, indexes, TOAST) over its tables
+    #[serde(rename = "sizeBytes")]
+    pub size_bytes: i64,
+    #[serde(rename = "tableCount")]
+    pub table_count: i64,
 }
 
-async fn write_str(file: &mut File, value: &str) -> Result<(), String> {
-    file.write_all(value.as_bytes())
-        .await
-        .map_err(|e| format!("Failed writing export file: {}", e))
+#[derive(Serialize, Deserialize)]
+pub struct SequenceInfo {
+    pub name: String,
+    #[serde(rename = "dataType")]
+    pub data_type: String,
+    /// `None` until `nextval` is first called (or without USAGE/SELECT)
+    #[serde(rename = "lastValue")]
+    pub last_value: Option<i64>,
+    pub increment: i64,
+    #[serde(rename = "maxValue")]
+    pub max_value: i64,
+    /// `schema.table.column` for sequences owned by a serial/identity column
+    #[serde(rename = "owningColumn")]
+    pub owning_column: Option<String>,
+    /// How much of the range in the increment's direction is used up
+    #[serde(rename = "percentOfMax")]
+    pub percent_of_max: Option<f64>,
 }
 
-#[command]
-pub async fn get_cached_servers() -> Result<Vec<db::Server>, String> {
-    db::get_servers().map_err(|e| e.to_string())
+#[derive(Serialize, Deserialize, Clone)]
+pub struct IndexSummary {
+    pub name: String,
+    /// On-disk size in bytes
+    pub size: i64,
+    pub columns: Vec<String>,
 }
 
-#[command]
-pub async fn get_dashboard_metrics(server_id: String) -> Result<DashboardMetrics, String> {
-    let server = db::get_server_by_id(&server_id)
-        .map_err(|e| e.to_string())?
-        .ok_or("Server not found")?;
+/// An index that makes others on the same table redundant. `indexes[0]` is
+/// the one to keep; the rest are candidates to drop.
+#[derive(Serialize, Deserialize)]
+pub struct DuplicateIndexGroup {
+    pub table: String,
+    pub indexes: Vec<IndexSummary>,
+    pub recommendation: String,
+}
 
-    let password = credentials::retrieve_password(&server.credential_key)
-        .map_err(|e| format!("Failed to retrieve password: {}", e))?;
+#[derive(Serialize, Deserialize)]
+pub struct SeqScanDiagnosis {
+    pub table: String,
+    pub reason: String,
+}
 
-    let pool = crate::postgres::get_or_create_pool(
-        &server.id,
-        &server.host,
-        server.port as u16,
-        &server.username,
-        &password,
-        &server.database,
-    )
-    .await
-    .map_err(|e| e.to_string())?;
+#[derive(Serialize, Deserialize)]
+pub struct PreparedStatementInfo {
+    pub name: String,
+    pub statement: String,
+    /// Unix millis
+    #[serde(rename = "preparedAt")]
+    pub prepared_at: i64,
+    /// True for SQL-level `PREPARE`, false for protocol-level statements
+    /// such as the driver's statement cache
+    #[serde(rename = "fromSql")]
+    pub from_sql: bool,
+}
 
-    let client = pool.get().await.map_err(|e| e.to_string())?;
+#[derive(Serialize, Deserialize)]
+pub struct CursorInfo {
+    pub name: String,
+    pub statement: String,
+    #[serde(rename = "isHoldable")]
+    pub is_holdable: bool,
+    #[serde(rename = "isScrollable")]
+    pub is_scrollable: bool,
+}
 
-    let active_connections_row = client
-        .query_one(
-            "SELECT COUNT(*) FROM pg_stat_activity WHERE datname = current_database()",
-            &[],
-        )
-        .await
-        .map_err(|e| e.to_string())?;
-    let active_connections: i64 = active_connections_row.get(0);
+#[derive(Serialize, Deserialize)]
+pub struct SessionStatements {
+    /// Backend PID of the connection inspected
+    pub pid: i32,
+    #[serde(rename = "preparedStatements")]
+    pub prepared_statements: Vec<PreparedStatementInfo>,
+    pub cursors: Vec<CursorInfo>,
+}
 
-    let connections_rows = client
-        .query(
-            "SELECT usename, query
-             FROM pg_stat_activity
-             WHERE datname = current_database()
-               AND pid <> pg_backend_pid()",
-            &[],
-        )
-        .await
-        .map_err(|e| e.to_string())?;
+#[derive(Serialize, Deserialize)]
+pub struct TableRef {
+    pub schema: String,
+    pub table: String,
+}
 
-    let connections = connections_rows
-        .into_iter()
-        .map(|row| DashboardConnection {
-            user: row.get::<_, String>(0),
-            query: row.get::<_, String>(1),
-        })
-        .collect();
+#[derive(Serialize)]
+pub struct SqlValidation {
+    pub valid: bool,
+    /// Set when `valid` is false; a `sql` error with SQLSTATE and position
+    pub error: Option<AppError>,
+}
 
-    let total_transactions_row = client
-        .query_one(
-            "SELECT (xact_commit + xact_rollback) FROM pg_stat_database WHERE datname = current_database()",
-            &[],
-        )
-        .await
-        .map_err(|e| e.to_string())?;
-    let total_transactions: i64 = total_transactions_row.get(0);
+#[derive(Serialize, Deserialize)]
+pub struct TableExportSize {
+    pub table: String,
+    #[serde(rename = "ddlBytes")]
+    pub ddl_bytes: i64,
+    /// 0 when data isn't included
+    #[serde(rename = "dataBytes")]
+    pub data_bytes: i64,
+}
 
-    Ok(DashboardMetrics {
-        active_connections,
-        total_transactions,
-        connections,
-        database_name: server.database,
-        collected_at: Utc::now().timestamp_millis(),
-    })
+#[derive(Serialize, Deserialize)]
+pub struct ExportSizeEstimate {
+    #[serde(rename = "totalBytes")]
+    pub total_bytes: i64,
+    pub tables: Vec<TableExportSize>,
 }
 
-#[command]
-pub async fn connect_to_server(server_id: String) -> Result<String, String> {
-    let server = db::get_server_by_id(&server_id)
-        .map_err(|e| e.to_string())?
-        .ok_or("Server not found")?;
+#[derive(Serialize, Deserialize)]
+pub struct UnindexedForeignKey {
+    pub table: String,
+    pub constraint: String,
+    /// Referencing columns in constraint order
+    pub columns: Vec<String>,
+    /// `CONCURRENTLY`, so it can't run inside a transaction block
+    #[serde(rename = "suggestedIndex")]
+    pub suggested_index: String,
+}
 
-    let password = credentials::retrieve_password(&server.credential_key)
-        .map_err(|e| format!("Failed to retrieve password: {}", e))?;
+/// An object `get_table_dependencies` found, named so an export can render it.
+#[derive(Serialize, Deserialize)]
+pub struct TableDependency {
+    /// `type`, `sequence` or `table`
+    pub kind: String,
+    pub schema: String,
+    pub name: String,
+}
 
-    crate::postgres::get_or_create_pool(
-        &server.id,
-        &server.host,
-        server.port as u16,
-        &server.username,
-        &password,
-        &server.database,
-    )
-    .await
-    .map_err(|e| e.to_string())?;
+#[derive(Serialize, Deserialize)]
+pub struct FkReference {
+    /// `schema.table` of the table holding the foreign key
+    #[serde(rename = "referencingTable")]
+    pub referencing_table: String,
+    #[serde(rename = "referencingColumn")]
+    pub referencing_column: String,
+    #[serde(rename = "dependentRows")]
+    pub dependent_rows: i64,
+}
 
-    // Update last connected timestamp
-    db::update_server_last_connected(&server_id, Utc::now().timestamp())
-        .map_err(|e| e.to_string())?;
+#[derive(Serialize, Deserialize)]
+pub struct TableActivity {
+    pub schema: String,
+    pub table: String,
+    /// Rows inserted, updated and deleted since statistics were last reset
+    #[serde(rename = "writeActivity")]
+    pub write_activity: i64,
+    /// Most recent (auto)vacuum or (auto)analyze, in epoch milliseconds; a
+    /// proxy for when the table last changed noticeably
+    #[serde(rename = "lastActivity")]
+    pub last_activity: Option<i64>,
+}
 
-    Ok(server_id)
+#[derive(Serialize, Deserialize)]
+pub struct VacuumHealth {
+    pub schema: String,
+    pub table: String,
+    /// Unix millis; `None` if autovacuum has never processed the table
+    #[serde(rename = "lastAutovacuum")]
+    pub last_autovacuum: Option<i64>,
+    /// Unix millis; `None` if autoanalyze has never processed the table
+    #[serde(rename = "lastAutoanalyze")]
+    pub last_autoanalyze: Option<i64>,
+    #[serde(rename = "deadTuples")]
+    pub n_dead_tup: i64,
+    #[serde(rename = "liveTuples")]
+    pub n_live_tup: i64,
+    /// Dead tuples as a fraction of all tuples, 0 to 1
+    #[serde(rename = "deadRatio")]
+    pub dead_ratio: f64,
+    #[serde(rename = "needsVacuum")]
+    pub needs_vacuum: bool,
+    /// Rows have changed since the last (auto)analyze, which was either over
+    /// `STALE_STATS_DAYS` ago or never happened
+    #[serde(rename = "staleStats")]
+    pub stale_stats: bool,
+    /// Statement that addresses both flags, ready to run in the editor
+    #[serde(rename = "maintenanceSql")]
+    pub maintenance_sql: String,
 }
 
-#[command]
-pub async fn get_sql_file_metadata(file_path: String) -> Result<SqlFileMetadata, String> {
-    let path = Path::new(&file_path);
-    let name = path
-        .file_name()
-        .and_then(|s| s.to_str())
-        .unwrap_or("(unknown)")
-        .to_string();
+/// One path found in a json column's sampled values.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct JsonPathSummary {
+    /// JSONPath-style, e.g. `$.address.city` or `$.tags[*]`
+    pub path: String,
+    /// JSON types seen at this path: `object`, `array`, `string`, `number`,
+    /// `boolean` or `null`
+    pub types: Vec<String>,
+    /// Share of sampled rows containing this path at least once
+    #[serde(rename = "presentInPercent")]
+    pub present_in_percent: f64,
+}
 
-    let metadata = std::fs::metadata(&file_path)
-        .map_err(|e| format!("Failed to read file metadata: {}", e))?;
+#[derive(Serialize, Deserialize)]
+pub struct BloatEstimate {
+    pub schema: String,
+    pub table: String,
+    /// Set for index rows; `None` for the table itself
+    pub index: Option<String>,
+    #[serde(rename = "sizeBytes")]
+    pub size_bytes: i64,
+    #[serde(rename = "bloatBytes")]
+    pub bloat_bytes: i64,
+    #[serde(rename = "bloatPercent")]
+    pub bloat_percent: f64,
+}
 
-    let created_at = metadata
-        .created()
-        .ok()
-        .and_then(system_time_to_epoch_millis);
+#[derive(Serialize, Deserialize)]
+pub struct TablePrivilege {
+    pub grantee: String,
+    /// e.g. `SELECT`, `INSERT`, `UPDATE`
+    pub privilege: String,
+    /// Whether the grantee may grant this privilege on to others
+    pub grantable: bool,
+    /// Set for column-level grants; `None` for grants on the whole table
+    pub column: Option<String>,
+}
 
-    Ok(SqlFileMetadata {
-        path: file_path,
-        name,
-        size_bytes: metadata.len(),
-        created_at,
-    })
+#[derive(Serialize, Deserialize)]
+pub struct InsertableColumn {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub data_type: String,
+    pub nullable: bool,
+    /// Filled in by the server when left out: a default, identity or serial
+    #[serde(rename = "hasDefault")]
+    pub has_default: bool,
+    /// Identity or serial (`nextval` default) column
+    #[serde(rename = "isIdentity")]
+    pub is_identity: bool,
+    #[serde(rename = "isGenerated")]
+    pub is_generated: bool,
+    /// False for generated and `GENERATED ALWAYS` identity columns, which
+    /// reject explicit values
+    pub insertable: bool,
 }
 
-#[command]
-pub async fn execute_query(
-    window: Window,
-    server_id: String,
-    sql: String,
-    query_id: Option<String>,
-    schema_name: Option<String>,
-    database_name: Option<String>,
-) -> Result<QueryResult, String> {
-    let normalized = normalize_sql_head(&sql);
-    let is_create_table = normalized.starts_with("create table");
-    let is_drop_table = normalized.starts_with("drop table");
-    let is_drop_database = normalized.starts_with("drop database");
-    let is_drop_schema = normalized.starts_with("drop schema");
+/// Timings in milliseconds, warmup run excluded.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct BenchmarkResult {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub median: f64,
+    pub p95: f64,
+    /// Each measured run in execution order
+    pub durations: Vec<f64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WaitEventCount {
+    /// e.g. `Lock`, `IO`, `Client`, `LWLock`; `None` for sessions that aren't
+    /// waiting, i.e. running on CPU
+    #[serde(rename = "waitEventType")]
+    pub wait_event_type: Option<String>,
+    #[serde(rename = "waitEvent")]
+    pub wait_event: Option<String>,
+    pub count: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct HistogramBucket {
+    #[serde(rename = "rangeStart")]
+    pub range_start: f64,
+    /// Exclusive, except for the last bucket which holds the maximum
+    #[serde(rename = "rangeEnd")]
+    pub range_end: f64,
+    pub count: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct OperationProgress {
+    pub pid: i32,
+    /// e.g. `VACUUM`, `CREATE INDEX CONCURRENTLY`, `COPY FROM`
+    pub command: String,
+    /// `None` for COPY, which reports no phases
+    pub phase: Option<String>,
+    /// `None` while the total isn't known yet
+    #[serde(rename = "percentComplete")]
+    pub percent_complete: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RowCount {
+    pub count: i64,
+    /// False when `count` is the planner estimate used after a timeout
+    pub exact: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RowCountComparison {
+    pub table: String,
+    /// `None` when the table doesn't exist on server A
+    #[serde(rename = "countA")]
+    pub count_a: Option<i64>,
+    /// `None` when the table doesn't exist on server B
+    #[serde(rename = "countB")]
+    pub count_b: Option<i64>,
+    #[serde(rename = "match")]
+    pub matches: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TableSizeBreakdown {
+    /// Main heap, free space map and visibility map, excluding TOAST
+    #[serde(rename = "tableBytes")]
+    pub table_bytes: i64,
+    #[serde(rename = "indexesBytes")]
+    pub indexes_bytes: i64,
+    /// TOAST heap plus its index; 0 when the table has no TOAST relation
+    #[serde(rename = "toastBytes")]
+    pub toast_bytes: i64,
+    #[serde(rename = "totalBytes")]
+    pub total_bytes: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BulkDeleteResult {
+    pub deleted: usize,
+    /// `server_id: reason` for each server that wasn't fully removed
+    pub failed: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ActivePool {
+    #[serde(rename = "serverId")]
+    pub server_id: String,
+    pub database: String,
+    /// Session role the pool's connections start with, if not the login user
+    pub role: Option<String>,
+    /// True for pools on the server's read replica
+    pub replica: bool,
+    pub size: i64,
+    pub available: i64,
+    #[serde(rename = "maxSize")]
+    pub max_size: i64,
+    pub waiting: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DeletePreview {
+    pub columns: Vec<ColumnInfo>,
+    /// Up to `DELETE_PREVIEW_ROWS` of the matching rows
+    pub rows: Vec<serde_json::Value>,
+    /// Total number of rows the DELETE would remove
+    #[serde(rename = "matchingRows")]
+    pub matching_rows: i64,
+}
+
+fn system_time_to_epoch_millis(time: SystemTime) -> Option<i64> {
+    time.duration_since(UNIX_EPOCH)
+        .ok()
+        .and_then(|d| i64::try_from(d.as_millis()).ok())
+}
 
-    let server = db::get_server_by_id(&server_id)
-        .map_err(|e| e.to_string())?
-        .ok_or("Server not found")?;
+fn load_server(server_id: &str) -> Result<db::Server, AppError> {
+    db::get_server_by_id(server_id)?.ok_or_else(|| AppError::not_found("Server not found"))
+}
 
-    let password = credentials::retrieve_password(&server.credential_key)
-        .map_err(|e| format!("Failed to retrieve password: {}", e))?;
+fn server_password(server: &db::Server) -> Result<String, AppError> {
+    credentials::retrieve_password(&server.credential_key)
+        .map_err(|e| AppError::auth(format!("Failed to retrieve password: {}", e)))
+}
 
-    let target_database = database_name
+/// Use the requested database when given, otherwise the server's default.
+fn resolve_database(server: &db::Server, database_name: Option<String>) -> String {
+    database_name
         .filter(|name| !name.trim().is_empty())
-        .unwrap_or_else(|| server.database.clone());
+        .unwrap_or_else(|| server.database.clone())
+}
 
-    let exec_result = crate::postgres::execute_query(
-        &server.id,
-        &server.host,
-        server.port as u16,
-        &server.username,
-        &password,
-        &target_database,
-        &sql,
-        query_id.as_deref(),
-        schema_name.as_deref(),
-    )
+/// Check out a connection for a command. On servers using transaction
+/// pooling its single-statement queries each run in their own transaction;
+/// see `PooledClient`.
+async fn get_client(
+    server: &db::Server,
+    password: &str,
+    database: &str,
+) -> Result<PooledClient, AppError> {
+    get_client_for_role(server, password, database, None).await
+}
+
+/// Like `get_client`, but from the pool whose connections log in with `role`
+/// as their session role.
+async fn get_client_for_role(
+    server: &db::Server,
+    password: &str,
+    database: &str,
+    role: Option<&str>,
+) -> Result<PooledClient, AppError> {
+    let pool = crate::postgres::get_or_create_pool_for_role(server, password, database, role)
         .await
-        .map_err(|e| {
-            // Format database errors in a human-readable way
-            if let Some(db_error) = e.downcast_ref::<tokio_postgres::Error>() {
-                if let Some(db_err) = db_error.as_db_error() {
-                    return format!("{}: {}", db_err.code().code(), db_err.message());
-                }
-            }
-            format!("Error: {}", e)
-        })?;
+        .map_err(|e| AppError::from(e).context("Failed to connect to database"))?;
 
-    let (columns, json_rows, rows_affected) = match exec_result {
-        crate::postgres::QueryExecutionResult::Rows(rows) => {
-            let columns = if !rows.is_empty() {
-                rows[0]
-                    .columns()
-                    .iter()
-                    .map(|col: &tokio_postgres::Column| ColumnInfo {
-                        name: col.name().to_string(),
-                        type_: Some(format!("{:?}", col.type_())),
-                    })
-                    .collect()
-            } else {
-                vec![]
-            };
+    // A role that can't log in says nothing about the server's own state
+    let client = match role {
+        None => crate::postgres::checkout(server, &pool).await,
+        Some(_) => pool.get().await,
+    }
+    .map_err(|e| AppError::from(e).context("Failed to get database client"))?;
+    Ok(PooledClient::new(client, server, role))
+}
 
-            let json_rows: Vec<serde_json::Value> = rows
-                .iter()
-                .map(|row: &tokio_postgres::Row| {
-                    let mut map = serde_json::Map::new();
-                    for (idx, col) in row.columns().iter().enumerate() {
-                        let value: serde_json::Value = match col.type_().name() {
-                            "void" => serde_json::Value::Null,
-                            "int4" => row
-                                .try_get::<_, Option<i32>>(idx)
-                                .ok()
-                                .flatten()
-                                .map(|v: i32| v.into())
-                                .unwrap_or(serde_json::Value::Null),
-                            "int8" => row
-                                .try_get::<_, Option<i64>>(idx)
-                                .ok()
-                                .flatten()
-                                .map(|v: i64| v.into())
-                                .unwrap_or(serde_json::Value::Null),
-                            "float4" => row
-                                .try_get::<_, Option<f32>>(idx)
-                                .ok()
-                                .flatten()
-                                .map(|v: f32| v.into())
-                                .unwrap_or(serde_json::Value::Null),
-                            "float8" => row
-                                .try_get::<_, Option<f64>>(idx)
-                                .ok()
-                                .flatten()
-                                .map(|v: f64| v.into())
-                                .unwrap_or(serde_json::Value::Null),
-                            "bool" => row
-                                .try_get::<_, Option<bool>>(idx)
-                                .ok()
-                                .flatten()
-                                .map(|v: bool| v.into())
-                                .unwrap_or(serde_json::Value::Null),
-                            "text" | "varchar" => row
-                                .try_get::<_, Option<String>>(idx)
-                                .ok()
-                                .flatten()
-                                .map(|v: String| v.into())
-                                .unwrap_or(serde_json::Value::Null),
-                            _ => row
-                                .try_get::<_, Option<String>>(idx)
-                                .ok()
-                                .flatten()
-                                .map(|v: String| v.into())
-                                .unwrap_or(serde_json::Value::Null),
-                        };
-                        map.insert(col.name().to_string(), value);
-                    }
-                    serde_json::Value::Object(map)
-                })
-                .collect();
+/// Column descriptors for a result set, named from its first row. `types`
+/// are the statement's own column types; a column the text fallback read as
+/// `text` is still described (and hinted) by its real type.
+fn rows_to_columns(rows: &[tokio_postgres::Row], types: &[tokio_postgres::types::Type]) -> Vec<ColumnInfo> {
+    match rows.first() {
+        Some(row) => row
+            .columns()
+            .iter()
+            .enumerate()
+            .map(|(idx, col)| {
+                let ty = types.get(idx).unwrap_or(col.type_());
+                ColumnInfo {
+                    name: col.name().to_string(),
+                    type_: Some(format!("{:?}", ty)),
+                    render_hint: crate::decode::render_hint(ty).to_string(),
+                }
+            })
+            .collect(),
+        None => vec![],
+    }
+}
 
-            (columns, json_rows, Some(rows.len()))
+/// Every `Seq Scan` in an EXPLAIN (VERBOSE, FORMAT JSON) plan as
+/// (schema, table, filter).
+fn collect_seq_scans(plan: &serde_json::Value, out: &mut Vec<(String, String, Option<String>)>) {
+    if plan.get("Node Type").and_then(|v| v.as_str()) == Some("Seq Scan") {
+        let text = |key: &str| plan.get(key).and_then(|v| v.as_str()).map(str::to_string);
+        if let Some(table) = text("Relation Name") {
+            out.push((text("Schema").unwrap_or_default(), table, text("Filter")));
         }
-        crate::postgres::QueryExecutionResult::Affected(affected) => {
-            (vec![], vec![], Some(affected as usize))
+    }
+    if let Some(children) = plan.get("Plans").and_then(|v| v.as_array()) {
+        for child in children {
+            collect_seq_scans(child, out);
         }
-    };
+    }
+}
 
-    if is_drop_table || is_drop_database || is_drop_schema {
-        if let Err(e) = crate::schema::refresh_schema_for_server(&server, &password).await {
-            eprintln!("Failed to refresh schema after DROP TABLE/SCHEMA/DATABASE: {}", e);
-        } else {
-            let updated_schemas = db::get_schemas(&server_id).map_err(|e| e.to_string())?;
+/// Key columns or expressions of a `CREATE INDEX` definition, lowercased.
+fn index_key_columns(definition: &str) -> Vec<String> {
+    let lowered = definition.to_lowercase();
+    let Some(start) = lowered.find(" using ").and_then(|using| lowered[using..].find('(').map(|p| using + p)) else {
+        return vec![];
+    };
 
-            #[derive(Serialize, Clone)]
-            struct SchemaUpdate {
-                #[serde(rename = "serverId")]
-                server_id: String,
-                schemas: Vec<db::Schema>,
+    let mut columns = Vec::new();
+    let mut depth = 0;
+    let mut current = String::new();
+    for ch in lowered[start + 1..].chars() {
+        match ch {
+            '(' => {
+                depth += 1;
+                current.push(ch);
+            }
+            ')' if depth == 0 => break,
+            ')' => {
+                depth -= 1;
+                current.push(ch);
             }
+            ',' if depth == 0 => columns.push(std::mem::take(&mut current)),
+            _ => current.push(ch),
+        }
+    }
+    columns.push(current);
 
-            window
-                .emit(
-                    "schema_updated",
-                    SchemaUpdate {
-                        server_id: server_id.clone(),
-                        schemas: updated_schemas,
-                    },
-                )
-                .map_err(|e: Error| e.to_string())?;
+    columns
+        .into_iter()
+        .map(|column| column.trim().trim_matches('"').to_string())
+        .filter(|column| !column.is_empty())
+        .collect()
+}
+
+/// An index key expression as Postgres prints it, reduced for matching:
+/// lowercased, with casts, quotes and whitespace dropped and parentheses
+/// around a lone identifier removed, so `lower((email)::text)` becomes
+/// `lower(email)`.
+fn normalize_index_expression(expression: &str) -> String {
+    let chars: Vec<char> = expression.to_lowercase().chars().collect();
+    let mut normalized = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == ':' && chars.get(i + 1) == Some(&':') {
+            // Skip the type name, including multi-word ones like `character varying`
+            i += 2;
+            while i < chars.len() && (chars[i].is_alphanumeric() || "_. \"[]".contains(chars[i])) {
+                i += 1;
+            }
+            continue;
+        }
+        if !chars[i].is_whitespace() && chars[i] != '"' {
+            normalized.push(chars[i]);
         }
+        i += 1;
     }
 
-    // Save to history (legacy table)
-    let now = Utc::now().timestamp();
-    let history = QueryHistory {
-        id: Uuid::new_v4().to_string(),
-        server_id: server_id.clone(),
-        sql: sql.clone(),
-        executed_at: now,
-        success: 1,
-    };
-    if let Err(e) = db::add_query_history(&history) {
-        eprintln!("Failed to save query history: {}", e);
-    }
-
-    // Save to deduplicated history (for UI)
-    if let Err(e) = db::upsert_query_history_dedup(&server_id, &sql, now) {
-        eprintln!("Failed to save deduplicated query history: {}", e);
+    let is_ident_char = |c: char| c.is_alphanumeric() || c == '_' || c == '.';
+    loop {
+        let chars: Vec<char> = normalized.chars().collect();
+        let redundant = (0..chars.len()).find_map(|open| {
+            // A paren right after a name belongs to a function call
+            if chars[open] != '(' || (open > 0 && is_ident_char(chars[open - 1])) {
+                return None;
+            }
+            let close = open + 1 + chars[open + 1..].iter().position(|c| !is_ident_char(*c))?;
+            (chars[close] == ')' && close > open + 1).then_some((open, close))
+        });
+        let Some((open, close)) = redundant else {
+            return normalized;
+        };
+        normalized = chars[..open]
+            .iter()
+            .chain(&chars[open + 1..close])
+            .chain(&chars[close + 1..])
+            .collect();
     }
+}
 
-    let message = if is_create_table {
-        Some("Table created".to_string())
-    } else if is_drop_table {
-        Some("Table dropped".to_string())
-    } else if is_drop_database {
-        Some("Database dropped".to_string())
-    } else if is_drop_schema {
-        Some("Schema dropped".to_string())
-    } else {
-        None
-    };
-
-    Ok(QueryResult {
-        columns,
-        rows: json_rows,
-        rows_affected,
-        message,
-    })
+/// Whether index key `key` is `function(...)` with `column` as one of its
+/// arguments, e.g. `lower((email)::text)` for `lower` and `email`.
+fn expression_index_matches(key: &str, function: &str, column: &str) -> bool {
+    let key = normalize_index_expression(key);
+    key.strip_prefix(&function.to_lowercase())
+        .and_then(|rest| rest.strip_prefix('('))
+        .and_then(|rest| rest.strip_suffix(')'))
+        .is_some_and(|args| args.split(',').any(|arg| arg == column))
 }
 
-#[command]
-pub async fn execute_sql_file(server_id: String, file_path: String) -> Result<QueryResult, String> {
-    let server = db::get_server_by_id(&server_id)
-        .map_err(|e| e.to_string())?
-        .ok_or("Server not found")?;
+/// Identifiers in a plan filter, each with the function call directly
+/// wrapping it (`lower(email)` gives `("email", Some("lower"))`). Quoted
+/// literals are skipped and qualifiers like `t.` are dropped.
+fn filter_identifiers(filter: &str) -> Vec<(String, Option<String>)> {
+    let chars: Vec<char> = filter.chars().collect();
+    let mut found = Vec::new();
+    // Function name (if any) owning each open parenthesis
+    let mut calls: Vec<Option<String>> = Vec::new();
+    let mut pending_call: Option<String> = None;
+    // Set after a `::type` so words like `varying` in `character varying` are skipped
+    let mut in_cast_type = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let ch = chars[i];
+        if ch == '\'' {
+            i += 1;
+            while i < chars.len() {
+                if chars[i] == '\'' && chars.get(i + 1) == Some(&'\'') {
+                    i += 2;
+                } else if chars[i] == '\'' {
+                    break;
+                } else {
+                    i += 1;
+                }
+            }
+            i += 1;
+            continue;
+        }
+        if ch == '(' {
+            calls.push(pending_call.take());
+            i += 1;
+            continue;
+        }
+        if ch == ')' {
+            calls.pop();
+            i += 1;
+            continue;
+        }
 
-    let password = credentials::retrieve_password(&server.credential_key)
-        .map_err(|e| format!("Failed to retrieve password: {}", e))?;
+        let starts_ident = ch == '"' || ch.is_alphabetic() || ch == '_';
+        if !starts_ident {
+            pending_call = None;
+            if !ch.is_whitespace() {
+                in_cast_type = false;
+            }
+            i += 1;
+            continue;
+        }
 
-    let pool = crate::postgres::get_or_create_pool(
-        &server.id,
-        &server.host,
-        server.port as u16,
-        &server.username,
-        &password,
-        &server.database,
-    )
-    .await
-    .map_err(|e| format!("Failed to connect to database: {}", e))?;
+        let quoted = ch == '"';
+        let mut ident = String::new();
+        if quoted {
+            i += 1;
+            while i < chars.len() && chars[i] != '"' {
+                ident.push(chars[i]);
+                i += 1;
+            }
+            i += 1;
+        } else {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$') {
+                ident.push(chars[i]);
+                i += 1;
+            }
+        }
 
-    let client = pool
-        .get()
-        .await
-        .map_err(|e| format!("Failed to get database client: {}", e))?;
+        let lowered = ident.to_lowercase();
+        let after_cast = {
+            let consumed = ident.chars().count() + if quoted { 2 } else { 0 };
+            let before: String = chars[..i - consumed].iter().collect();
+            before.trim_end().ends_with("::")
+        };
+        let is_type_word = after_cast
+            || (in_cast_type
+                && matches!(lowered.as_str(), "varying" | "precision" | "with" | "without" | "time" | "zone"));
+        let is_keyword = !quoted
+            && matches!(
+                lowered.as_str(),
+                "and" | "or" | "not" | "is" | "null" | "true" | "false" | "any" | "all" | "array" | "distinct" | "from"
+            );
+        in_cast_type = is_type_word;
+
+        match chars.get(i) {
+            Some('.') => {}
+            Some('(') if !is_type_word => pending_call = Some(lowered),
+            _ if is_type_word || is_keyword => {}
+            _ => found.push((ident, calls.last().cloned().flatten())),
+        }
+    }
 
-    let path = Path::new(&file_path);
-    let file_name = path
-        .file_name()
-        .and_then(|s| s.to_str())
-        .unwrap_or("SQL file");
+    found
+}
 
-    let file = File::open(&file_path)
-        .await
-        .map_err(|e| format!("Failed to open SQL file: {}", e))?;
-    let mut reader = BufReader::new(file);
-    let mut buffer = vec![0u8; 64 * 1024];
+fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
 
-    let mut statement = String::new();
-    let mut statement_count: usize = 0;
+/// Postgres truncates identifiers longer than NAMEDATALEN - 1 bytes.
+const MAX_IDENTIFIER_BYTES: usize = 63;
 
-    let mut in_copy = false;
-    let mut copy_sink: Option<Pin<Box<CopyInSink<Bytes>>>> = None;
-    let mut copy_line_buffer = String::new();
+/// Check that a name can be used as a (quoted) Postgres identifier.
+fn validate_identifier(name: &str) -> Result<(), AppError> {
+    if name.trim().is_empty() {
+        return Err(AppError::invalid("Identifier must not be empty"));
+    }
+    if name.len() > MAX_IDENTIFIER_BYTES {
+        return Err(AppError::invalid(format!(
+            "Identifier \"{}\" is longer than {} bytes",
+            name, MAX_IDENTIFIER_BYTES
+        )));
+    }
+    if name.contains('\0') {
+        return Err(AppError::invalid("Identifier must not contain NUL characters"));
+    }
+    Ok(())
+}
 
-    let mut in_single_quote = false;
-    let mut in_double_quote = false;
-    let mut pending_single_quote_end = false;
-    let mut pending_double_quote_end = false;
-    let mut in_line_comment = false;
-    let mut in_block_comment = false;
-    let mut block_prev_char: Option<char> = None;
-    let mut dollar_tag: Option<String> = None;
-    let mut dollar_candidate: Option<String> = None;
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
 
-    loop {
-        let bytes_read = reader
-            .read(&mut buffer)
-            .await
-            .map_err(|e| format!("Failed to read SQL file: {}", e))?;
+/// Wrap a SELECT so each projection becomes an extra flat column computed with
+/// `#>>` on the server. Rewriting in SQL (rather than shipping the parsed
+/// document and pathing into it in the frontend) keeps payloads small for large
+/// jsonb values; the original columns are still returned unchanged.
+fn apply_json_projections(sql: &str, projections: &[JsonProjection]) -> String {
+    let inner = sql.trim().trim_end_matches(';');
+    let extra: Vec<String> = projections
+        .iter()
+        .map(|projection| {
+            let path = projection
+                .path
+                .iter()
+                .map(|segment| quote_literal(segment))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let alias = projection.alias.clone().unwrap_or_else(|| {
+                std::iter::once(projection.column.as_str())
+                    .chain(projection.path.iter().map(String::as_str))
+                    .collect::<Vec<_>>()
+                    .join(".")
+            });
+            format!(
+                "fastdb_q.{} #>> ARRAY[{}]::text[] AS {}",
+                quote_ident(&projection.column),
+                path,
+                quote_ident(&alias)
+            )
+        })
+        .collect();
 
-        if bytes_read == 0 {
-            break;
-        }
+    format!("SELECT fastdb_q.*, {} FROM ({}\n) AS fastdb_q", extra.join(", "), inner)
+}
 
-        let chunk = String::from_utf8_lossy(&buffer[..bytes_read]);
-        let mut iter = chunk.chars().peekable();
+/// Pretty-printed copy of `sql` for the history panel, when the
+/// `format_history_sql` setting is on. Off by default so users get their
+/// exact text back.
+fn format_history_sql(sql: &str) -> Option<String> {
+    let enabled = db::get_setting(db::SETTING_FORMAT_HISTORY_SQL)
+        .ok()
+        .flatten()
+        .map(|value| value == "true")
+        .unwrap_or(false);
+    if !enabled {
+        return None;
+    }
 
-        while let Some(c) = iter.next() {
-            let ch = c;
-            let mut reprocess = true;
+    Some(pretty_print_sql(sql, sqlformat::Indent::Spaces(2), false))
+}
 
-            while reprocess {
-                reprocess = false;
+/// Format `sql` with `sqlformat`. Dollar-quoted bodies (function bodies, `DO`
+/// blocks) are swapped for string-literal placeholders first so the
+/// formatter can't re-indent or re-case their contents; comments and quoted
+/// strings are left to the formatter, which keeps them verbatim.
+fn pretty_print_sql(sql: &str, indent: sqlformat::Indent, uppercase: bool) -> String {
+    let (masked, bodies) = mask_dollar_quotes(sql);
+    let options = sqlformat::FormatOptions {
+        indent,
+        uppercase,
+        ..Default::default()
+    };
+    let mut formatted = sqlformat::format(&masked, &sqlformat::QueryParams::None, options);
+    for (idx, body) in bodies.iter().enumerate() {
+        formatted = formatted.replacen(&dollar_placeholder(idx), body, 1);
+    }
+    formatted
+}
 
-                if in_copy {
-                    if ch == '\n' {
-                        let line = copy_line_buffer.trim_end_matches('\r');
-                        if line == "\\." {
-                            if let Some(mut sink) = copy_sink.take() {
-                                match sink.as_mut().finish().await {
-                                    Ok(_) => {}
-                                    Err(e) => {
-                                        let detail = format_pg_error(&e);
-                                        return Err(format!("Failed to finalize COPY: {}", detail));
-                                    }
-                                }
-                            }
-                            in_copy = false;
-                        } else if line.trim().is_empty() {
-                            copy_line_buffer.clear();
-                            continue;
-                        } else if let Some(sink) = copy_sink.as_mut() {
-                            sink.as_mut()
-                                .send(Bytes::from(line.to_owned()))
-                                .await
-                                .map_err(|e| format!("Failed writing COPY data: {}", e))?;
-                            sink.as_mut()
-                                .send(Bytes::from_static(b"\n"))
-                                .await
-                                .map_err(|e| format!("Failed writing COPY data: {}", e))?;
-                        }
-                        copy_line_buffer.clear();
-                    } else {
-                        copy_line_buffer.push(ch);
-                    }
-                    continue;
-                }
+/// Length of the `$tag$` opening `chars`, if any. Tags follow identifier
+/// rules, so `$1` is a parameter rather than a quote.
+fn dollar_tag_len(chars: &[char]) -> Option<usize> {
+    if chars.first() != Some(&'$') || chars.get(1).is_some_and(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let end = 1 + chars[1..].iter().take_while(|c| c.is_alphanumeric() || **c == '_').count();
+    (chars.get(end) == Some(&'$')).then_some(end + 1)
+}
 
-                if pending_single_quote_end {
-                    if ch == '\'' {
-                        statement.push(ch);
-                        pending_single_quote_end = false;
-                        continue;
-                    } else {
-                        in_single_quote = false;
-                        pending_single_quote_end = false;
-                        reprocess = true;
-                        continue;
-                    }
-                }
+fn dollar_placeholder(idx: usize) -> String {
+    format!("'__fastdb_dollar_quote_{}__'", idx)
+}
 
-                if pending_double_quote_end {
-                    if ch == '"' {
-                        statement.push(ch);
-                        pending_double_quote_end = false;
-                        continue;
-                    } else {
-                        in_double_quote = false;
-                        pending_double_quote_end = false;
-                        reprocess = true;
+/// Replace each `$tag$ ... $tag$` span outside comments and quoted strings
+/// with a placeholder, returning the masked text and the original spans.
+fn mask_dollar_quotes(sql: &str) -> (String, Vec<String>) {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut out = String::with_capacity(sql.len());
+    let mut bodies = Vec::new();
+    let mut i = 0;
+
+    // Copy chars[from..to] through unchanged
+    let copy = |out: &mut String, from: usize, to: usize| out.extend(&chars[from..to]);
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if c == '-' && next == Some('-') {
+            let end = chars[i..].iter().position(|&c| c == '\n').map_or(chars.len(), |p| i + p);
+            copy(&mut out, i, end);
+            i = end;
+        } else if c == '/' && next == Some('*') {
+            let end = (i + 2..chars.len().saturating_sub(1))
+                .find(|&j| chars[j] == '*' && chars[j + 1] == '/')
+                .map_or(chars.len(), |j| j + 2);
+            copy(&mut out, i, end);
+            i = end;
+        } else if c == '\'' || c == '"' {
+            let mut end = i + 1;
+            while end < chars.len() {
+                if chars[end] == c {
+                    // Doubled quote is an escaped quote
+                    if chars.get(end + 1) == Some(&c) {
+                        end += 2;
                         continue;
                     }
+                    end += 1;
+                    break;
                 }
+                end += 1;
+            }
+            copy(&mut out, i, end.min(chars.len()));
+            i = end;
+        } else if let Some(tag_len) = dollar_tag_len(&chars[i..]) {
+            let tag = &chars[i..i + tag_len];
+            let end = (i + tag_len..=chars.len() - tag_len)
+                .find(|&j| chars[j..j + tag_len] == *tag)
+                .map_or(chars.len(), |j| j + tag_len);
+            out.push_str(&dollar_placeholder(bodies.len()));
+            bodies.push(chars[i..end].iter().collect());
+            i = end;
+        } else {
+            out.push(c);
+            i += 1;
+        }
+    }
 
-                if in_line_comment {
-                    if ch == '\n' {
-                        in_line_comment = false;
-                    }
-                    continue;
-                }
+    (out, bodies)
+}
 
-                if in_block_comment {
-                    if block_prev_char == Some('*') && ch == '/' {
-                        in_block_comment = false;
-                        block_prev_char = None;
-                    } else {
-                        block_prev_char = Some(ch);
-                    }
-                    continue;
-                }
+/// OS account running FastDB, recorded with history on shared machines.
+/// `USERNAME` is set on Windows, `USER` on Unix-likes.
+fn current_os_user() -> Option<String> {
+    std::env::var("USERNAME")
+        .or_else(|_| std::env::var("USER"))
+        .ok()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
 
-                if let Some(tag) = &dollar_tag {
-                    statement.push(ch);
-                    if ch == '$' && statement.ends_with(tag) {
-                        dollar_tag = None;
-                    }
-                    continue;
-                }
+/// Quote a CSV field when it contains a delimiter, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
 
-                if let Some(tag) = dollar_candidate.as_mut() {
-                    statement.push(ch);
-                    if ch == '$' {
-                        let tag_value = dollar_candidate.take().unwrap_or_default();
-                        dollar_tag = Some(format!("${}$", tag_value));
-                    } else if ch.is_ascii_alphanumeric() || ch == '_' {
-                        tag.push(ch);
-                    } else {
-                        dollar_candidate = None;
-                    }
-                    continue;
+/// Minimal RFC 4180 reader: quoted fields may contain commas, doubled quotes
+/// and line breaks.
+fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if in_quotes {
+            if ch == '"' {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    in_quotes = false;
                 }
+            } else {
+                field.push(ch);
+            }
+            continue;
+        }
 
-                if in_single_quote {
-                    statement.push(ch);
-                    if ch == '\'' {
-                        pending_single_quote_end = true;
-                    }
-                    continue;
-                }
+        match ch {
+            '"' => in_quotes = true,
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            _ => field.push(ch),
+        }
+    }
 
-                if in_double_quote {
-                    statement.push(ch);
-                    if ch == '"' {
-                        pending_double_quote_end = true;
-                    }
-                    continue;
-                }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
 
-                if ch == '-' && iter.peek() == Some(&'-') {
-                    if let Some(_) = iter.next() {
-                        in_line_comment = true;
-                    }
-                    continue;
-                }
+    records
+}
 
-                if ch == '/' && iter.peek() == Some(&'*') {
-                    if let Some(_) = iter.next() {
-                        in_block_comment = true;
-                        block_prev_char = Some('*');
-                    }
-                    continue;
-                }
+async fn write_str<W: AsyncWrite + Unpin>(file: &mut W, value: &str) -> Result<(), AppError> {
+    file.write_all(value.as_bytes())
+        .await
+        .map_err(|e| AppError::from(e).context("Failed writing export file"))
+}
 
-                if ch == '$' {
-                    statement.push(ch);
-                    dollar_candidate = Some(String::new());
-                    continue;
-                }
+/// Export writer that hashes bytes as they stream through, so a SHA-256 of
+/// the file is available without reading it back. Hashing is skipped when
+/// the checksum wasn't requested.
+struct ChecksumWriter<W> {
+    inner: W,
+    hasher: Option<Sha256>,
+}
 
-                if ch == '\'' {
-                    statement.push(ch);
-                    in_single_quote = true;
-                    continue;
-                }
+impl<W: AsyncWrite + Unpin> ChecksumWriter<W> {
+    fn new(inner: W, enabled: bool) -> Self {
+        Self {
+            inner,
+            hasher: enabled.then(Sha256::new),
+        }
+    }
 
-                if ch == '"' {
-                    statement.push(ch);
-                    in_double_quote = true;
-                    continue;
-                }
+    /// Hex digest of everything written, also saved beside the export as
+    /// `<output_path>.sha256` in `sha256sum` format. Call after flushing.
+    async fn finish(self, output_path: &str) -> Result<Option<String>, AppError> {
+        let Some(hasher) = self.hasher else {
+            return Ok(None);
+        };
+        let digest = format!("{:x}", hasher.finalize());
+        let file_name = Path::new(output_path)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| output_path.to_string());
+        tokio::fs::write(
+            format!("{}.sha256", output_path),
+            format!("{}  {}\n", digest, file_name),
+        )
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to write checksum file"))?;
+        Ok(Some(digest))
+    }
+}
 
-                if ch == ';' {
-                    let trimmed = statement.trim();
-                    if !trimmed.is_empty() {
-                        let trimmed_lower = trimmed.to_lowercase();
-                        if trimmed_lower.starts_with("copy")
+impl<W: AsyncWrite + Unpin> AsyncWrite for ChecksumWriter<W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = &mut *self;
+        let poll = Pin::new(&mut this.inner).poll_write(cx, buf);
+        if let (Poll::Ready(Ok(written)), Some(hasher)) = (&poll, this.hasher.as_mut()) {
+            hasher.update(&buf[..*written]);
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Suffix for an export's result message naming its checksum, if any.
+fn checksum_note(checksum: Option<String>) -> String {
+    checksum
+        .map(|digest| format!(" (SHA-256 {})", digest))
+        .unwrap_or_default()
+}
+
+/// Pretty-print SQL for the editor's Format button. `indent` is the number of
+/// spaces per level (default 2) unless `use_tabs` is set; `uppercase` upper-cases
+/// keywords, otherwise their casing is kept as typed.
+#[command]
+pub async fn format_sql(
+    sql: String,
+    indent: Option<u8>,
+    use_tabs: Option<bool>,
+    uppercase: Option<bool>,
+) -> Result<String, AppError> {
+    let indent = if use_tabs.unwrap_or(false) {
+        sqlformat::Indent::Tabs
+    } else {
+        sqlformat::Indent::Spaces(indent.unwrap_or(2))
+    };
+    Ok(pretty_print_sql(&sql, indent, uppercase.unwrap_or(false)))
+}
+
+#[command]
+pub async fn get_setting(key: String) -> Result<Option<String>, AppError> {
+    db::get_setting(&key).map_err(AppError::from)
+}
+
+#[command]
+pub async fn set_setting(key: String, value: String) -> Result<(), AppError> {
+    db::set_setting(&key, &value).map_err(AppError::from)
+}
+
+/// Tune how long local database calls wait on a busy SQLite file.
+#[command]
+pub async fn set_busy_timeout(ms: u64) -> Result<(), AppError> {
+    if ms > 600_000 {
+        return Err(AppError::invalid("Busy timeout must be at most 600000 ms"));
+    }
+    db::set_busy_timeout(ms).map_err(AppError::from)
+}
+
+/// Set how long (in seconds) a query's cancel token is kept if the query
+/// never cleans it up itself.
+#[command]
+pub async fn set_cancel_token_ttl(seconds: u64) -> Result<(), AppError> {
+    if seconds < 60 {
+        return Err(AppError::invalid("Cancel token TTL must be at least 60 seconds"));
+    }
+    crate::postgres::set_cancel_token_ttl(std::time::Duration::from_secs(seconds));
+    Ok(())
+}
+
+#[command]
+pub async fn get_cached_servers() -> Result<Vec<db::Server>, AppError> {
+    db::get_servers().map_err(AppError::from)
+}
+
+/// Delete several servers at once: their rows and cached data in a single
+/// SQLite transaction, then their pools and stored passwords. Credential
+/// failures are reported per server without undoing the rest of the batch.
+#[command]
+pub async fn delete_servers(server_ids: Vec<String>) -> Result<BulkDeleteResult, AppError> {
+    let mut failed = Vec::new();
+    let mut servers = Vec::new();
+    for server_id in &server_ids {
+        match db::get_server_by_id(server_id)? {
+            Some(server) => servers.push(server),
+            None => failed.push(format!("{}: server not found", server_id)),
+        }
+    }
+
+    let ids: Vec<String> = servers.iter().map(|server| server.id.clone()).collect();
+    let deleted = db::delete_servers(&ids)?;
+
+    for server in &servers {
+        crate::postgres::drop_pools(&server.id).await;
+        if let Err(e) = credentials::delete_password(&server.credential_key) {
+            failed.push(format!("{}: {}", server.id, e));
+        }
+    }
+
+    Ok(BulkDeleteResult { deleted, failed })
+}
+
+/// Set the time zone every connection to the server uses, so `timestamptz`
+/// values are shown in it; `None` or empty goes back to the server default.
+/// The zone is checked by having Postgres apply it in a rolled-back
+/// transaction, so any name or offset Postgres accepts works. Existing pools
+/// are dropped so new connections pick it up.
+#[command]
+pub async fn set_server_timezone(server_id: String, timezone: Option<String>) -> Result<(), AppError> {
+    let timezone = timezone.map(|tz| tz.trim().to_string()).filter(|tz| !tz.is_empty());
+    let server = load_server(&server_id)?;
+
+    if let Some(timezone) = &timezone {
+        let password = server_password(&server)?;
+        let mut client = get_client(&server, &password, &server.database).await?;
+        let tx = client.transaction().await?;
+        tx.query_one("SELECT set_config('TimeZone', $1, true)", &[timezone])
+            .await
+            .map_err(|e| AppError::from(e).context(format!("Invalid time zone {}", timezone)))?;
+        tx.rollback().await?;
+    }
+
+    db::set_server_timezone(&server_id, timezone.as_deref())?;
+    crate::postgres::drop_pools(&server_id).await;
+    Ok(())
+}
+
+/// Set how long connecting and waiting for a pooled connection may take;
+/// `None` or zero goes back to the default. The timeout is fixed when a pool
+/// is built, so the server's pools are dropped and rebuilt on next use.
+#[command]
+pub async fn set_connect_timeout(server_id: String, timeout_ms: Option<i64>) -> Result<(), AppError> {
+    let timeout_ms = timeout_ms.filter(|ms| *ms > 0);
+    if !db::set_connect_timeout(&server_id, timeout_ms)? {
+        return Err(AppError::not_found("Server not found"));
+    }
+    crate::postgres::drop_pools(&server_id).await;
+    Ok(())
+}
+
+/// Pin or unpin a server at the top of the server list.
+#[command]
+pub async fn toggle_favorite_server(server_id: String) -> Result<bool, AppError> {
+    db::toggle_favorite_server(&server_id)?
+        .ok_or_else(|| AppError::not_found("Server not found"))
+}
+
+/// Servers queried at once by `get_all_dashboard_metrics`.
+const DASHBOARD_CONCURRENCY: usize = 8;
+
+/// Dashboards for many servers, fetched concurrently so a slow or unreachable
+/// server only delays its own tile. Each fetch is bounded by the server's
+/// connect timeout plus a few seconds for the metric queries.
+#[command]
+pub async fn get_all_dashboard_metrics(server_ids: Vec<String>) -> Result<Vec<ServerDashboard>, AppError> {
+    let dashboards = futures_util::stream::iter(server_ids)
+        .map(|server_id| async move {
+            let budget_ms = db::get_server_by_id(&server_id)
+                .ok()
+                .flatten()
+                .and_then(|server| server.connect_timeout_ms)
+                .filter(|ms| *ms > 0)
+                .unwrap_or(db::DEFAULT_CONNECT_TIMEOUT_MS)
+                + 5_000;
+
+            let result = tokio::time::timeout(
+                std::time::Duration::from_millis(budget_ms as u64),
+                get_dashboard_metrics(server_id.clone()),
+            )
+            .await
+            .unwrap_or_else(|_| Err(AppError::connection("Timed out fetching dashboard metrics")));
+
+            match result {
+                Ok(metrics) => ServerDashboard {
+                    server_id,
+                    metrics: Some(metrics),
+                    error: None,
+                },
+                Err(error) => ServerDashboard {
+                    server_id,
+                    metrics: None,
+                    error: Some(error),
+                },
+            }
+        })
+        .buffer_unordered(DASHBOARD_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+
+    Ok(dashboards)
+}
+
+#[command]
+pub async fn get_dashboard_metrics(server_id: String) -> Result<DashboardMetrics, AppError> {
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+
+    let client = get_client(&server, &password, &server.database).await?;
+
+    let active_connections_row = client
+        .query_one(
+            "SELECT COUNT(*) FROM pg_stat_activity WHERE datname = current_database()",
+            &[],
+        )
+        .await?;
+    let active_connections: i64 = active_connections_row.get(0);
+
+    let connections_rows = client
+        .query(
+            "SELECT usename, query
+             FROM pg_stat_activity
+             WHERE datname = current_database()
+               AND pid <> pg_backend_pid()",
+            &[],
+        )
+        .await?;
+
+    let connections = connections_rows
+        .into_iter()
+        .map(|row| DashboardConnection {
+            user: row.get::<_, String>(0),
+            query: row.get::<_, String>(1),
+        })
+        .collect();
+
+    let total_transactions_row = client
+        .query_one(
+            "SELECT (xact_commit + xact_rollback) FROM pg_stat_database WHERE datname = current_database()",
+            &[],
+        )
+        .await?;
+    let total_transactions: i64 = total_transactions_row.get(0);
+
+    Ok(DashboardMetrics {
+        active_connections,
+        total_transactions,
+        connections,
+        database_name: server.database,
+        collected_at: Utc::now().timestamp_millis(),
+    })
+}
+
+/// Terminate every backend on the server that has sat `idle` for longer
+/// than `idle_threshold_seconds`, for clearing out leaked connections when
+/// the server runs out of slots. Active and idle-in-transaction backends and
+/// this connection itself are never touched. `confirm` must be true.
+#[command]
+pub async fn terminate_idle_connections(
+    server_id: String,
+    idle_threshold_seconds: i64,
+    confirm: bool,
+) -> Result<TerminatedConnections, AppError> {
+    if !confirm {
+        return Err(AppError::invalid("Terminating idle connections requires confirmation"));
+    }
+    if idle_threshold_seconds < 0 {
+        return Err(AppError::invalid("Idle threshold must not be negative"));
+    }
+
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+
+    let client = get_client(&server, &password, &server.database).await?;
+    // Terminating in the select list, not the WHERE clause, so the planner
+    // can't call it before the idle filters have run
+    let rows = client
+        .query(
+            "SELECT pid, pg_terminate_backend(pid)
+             FROM pg_stat_activity
+             WHERE state = 'idle'
+               AND backend_type = 'client backend'
+               AND pid <> pg_backend_pid()
+               AND now() - state_change > make_interval(secs => $1::float8)
+             ORDER BY pid",
+            &[&(idle_threshold_seconds as f64)],
+        )
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to terminate idle connections"))?;
+
+    let pids: Vec<i32> = rows
+        .iter()
+        .filter(|row| row.get::<_, bool>(1))
+        .map(|row| row.get(0))
+        .collect();
+    Ok(TerminatedConnections {
+        count: pids.len(),
+        pids,
+    })
+}
+
+#[command]
+pub async fn connect_to_server(server_id: String) -> Result<String, AppError> {
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+
+    crate::postgres::get_or_create_pool(&server, &password, &server.database).await?;
+
+    // Update last connected timestamp
+    db::update_server_last_connected(&server_id, Utc::now().timestamp())?;
+
+    Ok(server_id)
+}
+
+#[command]
+pub async fn get_sql_file_metadata(file_path: String) -> Result<SqlFileMetadata, AppError> {
+    let path = Path::new(&file_path);
+    let name = path
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or("(unknown)")
+        .to_string();
+
+    let metadata = std::fs::metadata(&file_path)
+        .map_err(|e| AppError::from(e).context("Failed to read file metadata"))?;
+
+    let created_at = metadata
+        .created()
+        .ok()
+        .and_then(system_time_to_epoch_millis);
+
+    Ok(SqlFileMetadata {
+        path: file_path,
+        name,
+        size_bytes: metadata.len(),
+        created_at,
+    })
+}
+
+/// Keywords that may follow the table in a SELECT `add_tablesample` rewrites.
+const SAMPLE_FOLLOWING_CLAUSES: &[&str] = &[
+    "where", "group", "order", "limit", "offset", "having", "window", "fetch", "for",
+];
+
+/// Rewrite a plain single-table SELECT to read a `TABLESAMPLE SYSTEM` sample
+/// of its table. Deliberately conservative: joins, subqueries, set
+/// operations, comma-separated tables, table functions, comments and dollar
+/// quotes all give `None` so the query runs unchanged.
+fn add_tablesample(sql: &str, percent: f64) -> Option<String> {
+    let sql = sql.trim_end().trim_end_matches(';').trim_end();
+
+    // Same byte length as `sql`, ASCII-lowercased, with quoted text blanked so
+    // keywords inside literals and identifiers can't match
+    let mut masked = String::with_capacity(sql.len());
+    let mut quote: Option<char> = None;
+    for ch in sql.chars() {
+        match quote {
+            Some(q) if ch == q => {
+                quote = None;
+                masked.push(ch);
+            }
+            Some(_) => masked.push_str(&"x".repeat(ch.len_utf8())),
+            None => {
+                if ch == '\'' || ch == '"' {
+                    quote = Some(ch);
+                }
+                masked.push(ch.to_ascii_lowercase());
+            }
+        }
+    }
+    if quote.is_some() || [";", "--", "/*", "$"].iter().any(|token| masked.contains(token)) {
+        return None;
+    }
+
+    let is_word_char = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '"';
+    let words: Vec<(usize, &str)> = masked
+        .split(|c: char| !is_word_char(c))
+        .filter(|word| !word.is_empty())
+        .map(|word| (word.as_ptr() as usize - masked.as_ptr() as usize, word))
+        .collect();
+
+    let count = |keyword: &str| words.iter().filter(|(_, word)| *word == keyword).count();
+    if words.first().map(|(_, word)| *word) != Some("select")
+        || count("select") != 1
+        || count("from") != 1
+        || ["join", "union", "intersect", "except", "tablesample", "lateral"]
+            .iter()
+            .any(|keyword| count(keyword) > 0)
+    {
+        return None;
+    }
+
+    // Table reference: everything up to whitespace or punctuation, then an
+    // optional alias
+    let from = words.iter().position(|(_, word)| *word == "from")?;
+    let after_from = words[from].0 + "from".len();
+    let table_start = after_from + masked[after_from..].len() - masked[after_from..].trim_start().len();
+    let table_len = masked[table_start..]
+        .find(|c: char| c.is_whitespace() || ",()".contains(c))
+        .unwrap_or(masked.len() - table_start);
+    if table_len == 0 {
+        return None;
+    }
+    let mut insert_at = table_start + table_len;
+
+    // An alias only counts when nothing but whitespace separates it from the
+    // table; `FROM a, b` must not read `b` as an alias
+    let table_end = insert_at;
+    let adjacent = |start: usize| masked[table_end..start].trim().is_empty();
+    let mut following = words.iter().skip_while(move |(start, _)| *start < table_end).peekable();
+    match following.peek() {
+        Some((start, "as")) if adjacent(*start) => {
+            following.next();
+            insert_at = following.next().map(|(start, word)| start + word.len())?;
+        }
+        Some((start, word)) if adjacent(*start) && !SAMPLE_FOLLOWING_CLAUSES.contains(word) => {
+            insert_at = start + word.len();
+            following.next();
+        }
+        _ => {}
+    }
+
+    // Whatever follows must be a clause of this SELECT, not another table
+    let rest = masked[insert_at..].trim_start();
+    if !rest.is_empty()
+        && !following
+            .next()
+            .is_some_and(|(start, word)| rest.len() == masked.len() - start && SAMPLE_FOLLOWING_CLAUSES.contains(word))
+    {
+        return None;
+    }
+
+    Some(format!(
+        "{} TABLESAMPLE SYSTEM ({}){}",
+        &sql[..insert_at],
+        percent,
+        &sql[insert_at..]
+    ))
+}
+
+/// Run a SELECT against a `TABLESAMPLE SYSTEM (sample_percent)` sample of its
+/// table for a quick look at a huge table. Queries that aren't a plain
+/// single-table SELECT, or whose table can't be sampled (e.g. a view), run
+/// in full and come back with `sampled: false`.
+#[command]
+pub async fn execute_query_sampled(
+    server_id: String,
+    database_name: Option<String>,
+    sql: String,
+    sample_percent: f64,
+    query_id: Option<String>,
+) -> Result<SampledQueryResult, AppError> {
+    if !(sample_percent > 0.0 && sample_percent <= 100.0) {
+        return Err(AppError::invalid("Sample percent must be greater than 0 and at most 100"));
+    }
+
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+    let options = crate::postgres::ExecOptions {
+        query_id: query_id.as_deref(),
+        ..Default::default()
+    };
+
+    let mut sampled = false;
+    let mut result = None;
+    if let Some(sample_sql) = add_tablesample(&sql, sample_percent) {
+        match crate::postgres::execute_query(&server, &password, &target_database, &sample_sql, &options).await {
+            Ok(rows) => {
+                sampled = true;
+                result = Some(rows);
+            }
+            // 42809: TABLESAMPLE on a view or other non-table
+            Err(e) => {
+                let error = AppError::from(e);
+                if !matches!(&error, AppError::Sql { code, .. } if code == "42809") {
+                    return Err(error.without_position());
+                }
+            }
+        }
+    }
+    let result = match result {
+        Some(result) => result,
+        None => crate::postgres::execute_query(&server, &password, &target_database, &sql, &options)
+            .await
+            .map_err(AppError::from)?,
+    };
+
+    let result = match result {
+        crate::postgres::QueryExecutionResult::Rows { rows, types } => QueryResult {
+            columns: rows_to_columns(&rows, &types),
+            rows: rows.iter().map(row_to_json).collect(),
+            rows_affected: Some(rows.len()),
+            message: None,
+        },
+        crate::postgres::QueryExecutionResult::Affected(affected) => QueryResult {
+            columns: vec![],
+            rows: vec![],
+            rows_affected: Some(affected as usize),
+            message: None,
+        },
+    };
+
+    Ok(SampledQueryResult { result, sampled })
+}
+
+/// Run `sql` and return the first column of its first row, decoded like any
+/// result cell; `null` when there are no rows. For counts, `version()` and
+/// other one-value checks where a full `QueryResult` is just overhead.
+#[command]
+pub async fn execute_scalar(
+    server_id: String,
+    sql: String,
+    database_name: Option<String>,
+) -> Result<serde_json::Value, AppError> {
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let result = crate::postgres::execute_query(
+        &server,
+        &password,
+        &target_database,
+        &sql,
+        &crate::postgres::ExecOptions::default(),
+    )
+    .await
+    .map_err(AppError::from)?;
+
+    match result {
+        crate::postgres::QueryExecutionResult::Rows { rows, .. } => match rows.first() {
+            Some(row) if row.is_empty() => Err(AppError::invalid("Query returned no columns")),
+            Some(row) => Ok(crate::decode::column_to_json(row, 0)),
+            None => Ok(serde_json::Value::Null),
+        },
+        crate::postgres::QueryExecutionResult::Affected(_) => {
+            Err(AppError::invalid("Query returned no columns"))
+        }
+    }
+}
+
+/// Estimated total cost of an `EXPLAIN (FORMAT JSON)` plan, and the largest
+/// row estimate of any node in it. The top node alone isn't enough for rows:
+/// an UPDATE or DELETE without RETURNING reports 0 there however many rows
+/// its scan touches.
+fn plan_estimate(plan: &serde_json::Value) -> Option<(f64, f64)> {
+    fn max_rows(node: &serde_json::Value) -> f64 {
+        let own = node["Plan Rows"].as_f64().unwrap_or(0.0);
+        node["Plans"]
+            .as_array()
+            .map(|children| children.iter().map(max_rows).fold(own, f64::max))
+            .unwrap_or(own)
+    }
+
+    let root = &plan[0]["Plan"];
+    Some((root["Total Cost"].as_f64()?, max_rows(root)))
+}
+
+/// With the server's `confirm_expensive` on, EXPLAIN `sql` and refuse to run
+/// it when the estimate crosses a threshold. Statements EXPLAIN can't handle
+/// (e.g. several at once) are let through to fail or succeed on their own.
+async fn check_expensive_query(
+    server: &db::Server,
+    password: &str,
+    database: &str,
+    sql: &str,
+    options: &crate::postgres::ExecOptions<'_>,
+) -> Result<(), AppError> {
+    let explain_sql = format!("EXPLAIN (FORMAT JSON) {}", sql.trim().trim_end_matches(';'));
+    let plan = match crate::postgres::execute_query(server, password, database, &explain_sql, options).await {
+        Ok(crate::postgres::QueryExecutionResult::Rows { rows, .. }) => match rows.first() {
+            Some(row) => match crate::decode::column_to_json(row, 0) {
+                serde_json::Value::String(text) => serde_json::from_str(&text).unwrap_or_default(),
+                other => other,
+            },
+            None => return Ok(()),
+        },
+        _ => return Ok(()),
+    };
+    let Some((cost, rows)) = plan_estimate(&plan) else {
+        return Ok(());
+    };
+
+    let cost_threshold = server.expensive_cost_threshold.unwrap_or(db::DEFAULT_EXPENSIVE_COST);
+    let rows_threshold = server.expensive_rows_threshold.unwrap_or(db::DEFAULT_EXPENSIVE_ROWS) as f64;
+    if cost > cost_threshold || rows > rows_threshold {
+        return Err(AppError::ConfirmationRequired {
+            message: format!(
+                "Estimated cost {:.0} and up to {:.0} rows; run again with confirmation to execute",
+                cost, rows
+            ),
+            estimated_cost: cost,
+            estimated_rows: rows,
+        });
+    }
+    Ok(())
+}
+
+/// Turn the expensive-query confirmation on or off for a server, optionally
+/// with custom cost and row thresholds.
+#[command]
+pub async fn set_expensive_query_guard(
+    server_id: String,
+    enabled: bool,
+    cost_threshold: Option<f64>,
+    rows_threshold: Option<i64>,
+) -> Result<(), AppError> {
+    if cost_threshold.is_some_and(|cost| cost <= 0.0) || rows_threshold.is_some_and(|rows| rows <= 0) {
+        return Err(AppError::invalid("Thresholds must be positive"));
+    }
+    if !db::set_expensive_query_guard(&server_id, enabled, cost_threshold, rows_threshold)? {
+        return Err(AppError::not_found("Server not found"));
+    }
+    Ok(())
+}
+
+/// Servers with `confirm_expensive` set answer SELECT, WITH, UPDATE and
+/// DELETE statements the planner thinks are expensive with a
+/// `confirmationRequired` error carrying the estimate, until called again
+/// with `confirmed`. `client_min_messages` (`debug5` through `error`) is set
+/// for the statement's transaction only, and decides which of the server's
+/// messages come back in `notices`.
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub async fn execute_query(
+    window: Window,
+    server_id: String,
+    sql: String,
+    query_id: Option<String>,
+    schema_name: Option<String>,
+    database_name: Option<String>,
+    json_projections: Option<Vec<JsonProjection>>,
+    isolation_level: Option<String>,
+    force_primary: Option<bool>,
+    confirmed: Option<bool>,
+    client_min_messages: Option<String>,
+) -> Result<ExecuteQueryResult, AppError> {
+    let isolation_level = isolation_level
+        .as_deref()
+        .map(parse_isolation_level)
+        .transpose()?;
+    let client_min_messages = client_min_messages
+        .as_deref()
+        .map(parse_message_level)
+        .transpose()?;
+    let normalized = normalize_sql_head(&sql);
+    let is_select = normalized.starts_with("select") || normalized.starts_with("with");
+    let is_create_table = normalized.starts_with("create table");
+    let is_drop_table = normalized.starts_with("drop table");
+    let is_drop_database = normalized.starts_with("drop database");
+    let is_drop_schema = normalized.starts_with("drop schema");
+    let session_tag = session_command_tag(&normalized);
+
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+
+    let target_database = resolve_database(&server, database_name);
+
+    // Projections only make sense on row-returning statements; others run as typed
+    let exec_sql = match json_projections.as_deref() {
+        Some(projections) if is_select && !projections.is_empty() => {
+            apply_json_projections(&sql, projections)
+        }
+        _ => sql.clone(),
+    };
+
+    let options = crate::postgres::ExecOptions {
+        query_id: query_id.as_deref(),
+        schema_name: schema_name.as_deref(),
+        isolation_level,
+        force_primary: force_primary.unwrap_or(false),
+        client_min_messages,
+    };
+
+    let is_guarded = is_select || normalized.starts_with("update") || normalized.starts_with("delete");
+    if server.confirm_expensive != 0 && is_guarded && !confirmed.unwrap_or(false) {
+        let explain_options = crate::postgres::ExecOptions {
+            query_id: None,
+            ..options
+        };
+        check_expensive_query(&server, &password, &target_database, &exec_sql, &explain_options).await?;
+    }
+
+    crate::postgres::remember_attempt(
+        &server_id,
+        crate::postgres::LastAttempt {
+            sql: sql.clone(),
+            database: target_database.clone(),
+            schema_name: schema_name.clone(),
+        },
+    )
+    .await;
+
+    let (exec_result, notices) = crate::postgres::execute_query_with_notices(
+        &server,
+        &password,
+        &target_database,
+        &exec_sql,
+        &options,
+    )
+        .await
+        .map_err(|e| {
+            let error = AppError::from(e);
+            if exec_sql == sql { error } else { error.without_position() }
+        })?;
+
+    crate::postgres::forget_attempt(&server_id).await;
+
+    let (columns, json_rows, rows_affected) = match exec_result {
+        crate::postgres::QueryExecutionResult::Rows { rows, types } => {
+            let columns = rows_to_columns(&rows, &types);
+            let json_rows: Vec<serde_json::Value> = rows.iter().map(row_to_json).collect();
+
+            (columns, json_rows, Some(rows.len()))
+        }
+        // Session commands affect no rows; a count of 0 would just confuse
+        crate::postgres::QueryExecutionResult::Affected(_) if session_tag.is_some() => {
+            (vec![], vec![], None)
+        }
+        crate::postgres::QueryExecutionResult::Affected(affected) => {
+            (vec![], vec![], Some(affected as usize))
+        }
+    };
+
+    let ddl_tables = ddl_affected_tables(&sql);
+    if is_drop_database || is_drop_schema || ddl_tables.is_none() {
+        if let Err(e) = crate::schema::refresh_schema_for_server(&server, &password).await {
+            eprintln!("Failed to refresh schema after DDL: {}", e);
+        } else {
+            let updated_schemas = db::get_schemas(&server_id)?;
+
+            #[derive(Serialize, Clone)]
+            struct SchemaUpdate {
+                #[serde(rename = "serverId")]
+                server_id: String,
+                schemas: Vec<db::Schema>,
+            }
+
+            window
+                .emit(
+                    "schema_updated",
+                    SchemaUpdate {
+                        server_id: server_id.clone(),
+                        schemas: updated_schemas,
+                    },
+                )?;
+        }
+    } else if let Some(tables) = ddl_tables.filter(|tables| !tables.is_empty()) {
+        // Table DDL only touches the named tables, so patch those in place
+        let tables = qualify_ddl_tables(&server, &password, &target_database, schema_name.as_deref(), tables).await;
+        apply_table_ddl(&window, &server, &password, &target_database, &tables).await;
+    }
+
+    // Save to history (legacy table)
+    let now = Utc::now().timestamp();
+    let history = QueryHistory {
+        id: Uuid::new_v4().to_string(),
+        server_id: server_id.clone(),
+        sql: sql.clone(),
+        executed_at: now,
+        success: 1,
+        executed_by: current_os_user(),
+    };
+    if let Err(e) = db::add_query_history(&history) {
+        eprintln!("Failed to save query history: {}", e);
+    }
+
+    // Save to deduplicated history (for UI)
+    let display_sql = format_history_sql(&sql);
+    if let Err(e) = db::upsert_query_history_dedup(&server_id, &sql, display_sql.as_deref(), now) {
+        eprintln!("Failed to save deduplicated query history: {}", e);
+    }
+
+    let message = if is_create_table {
+        Some("Table created".to_string())
+    } else if is_drop_table {
+        Some("Table dropped".to_string())
+    } else if is_drop_database {
+        Some("Database dropped".to_string())
+    } else if is_drop_schema {
+        Some("Schema dropped".to_string())
+    } else {
+        session_tag
+    };
+
+    Ok(ExecuteQueryResult {
+        result: QueryResult {
+            columns,
+            rows: json_rows,
+            rows_affected,
+            message,
+        },
+        notices,
+    })
+}
+
+/// Postgres' limit on bind parameters in one statement.
+const MAX_BIND_PARAMS: usize = 65_535;
+
+/// `INSERT ... VALUES` with `row_count` rows of numbered placeholders. Columns
+/// with a `cast` take their value as text and convert it in SQL.
+fn insert_values_sql(target: &str, column_list: &str, row_count: usize, casts: &[Option<String>]) -> String {
+    let mut param = 0;
+    let rows: Vec<String> = (0..row_count)
+        .map(|_| {
+            let values: Vec<String> = casts
+                .iter()
+                .map(|cast| {
+                    param += 1;
+                    match cast {
+                        Some(ty) => format!("${}::text::{}", param, ty),
+                        None => format!("${}", param),
+                    }
+                })
+                .collect();
+            format!("({})", values.join(", "))
+        })
+        .collect();
+    format!("INSERT INTO {} ({}) VALUES {}", target, column_list, rows.join(", "))
+}
+
+/// Insert rows with every value bound as a parameter, in as many multi-row
+/// statements as Postgres' parameter limit requires. All batches run in one
+/// transaction, so any failure inserts nothing. Columns must exist in the
+/// cached schema. Values bind against the column types; types that can't be
+/// bound from JSON directly (dates, uuids, numerics, ...) are sent as text
+/// and cast to the column type.
+#[command]
+pub async fn insert_rows(
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: String,
+    table_name: String,
+    columns: Vec<String>,
+    rows: Vec<Vec<serde_json::Value>>,
+) -> Result<QueryResult, AppError> {
+    if columns.is_empty() {
+        return Err(AppError::invalid("At least one column is required"));
+    }
+    if let Some(idx) = rows.iter().position(|row| row.len() != columns.len()) {
+        return Err(AppError::invalid(format!(
+            "Row {} has {} values but {} columns were given",
+            idx + 1,
+            rows[idx].len(),
+            columns.len()
+        )));
+    }
+    if rows.is_empty() {
+        return Ok(QueryResult {
+            columns: vec![],
+            rows: vec![],
+            rows_affected: Some(0),
+            message: Some("0 rows inserted".to_string()),
+        });
+    }
+
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let schema_id = db::find_schema_id(&server.id, &target_database, &schema_name)?
+        .ok_or_else(|| AppError::not_found(format!("Schema {} is not cached; refresh the schema first", schema_name)))?;
+    let table = db::get_tables(&schema_id)?
+        .into_iter()
+        .find(|table| table.name == table_name)
+        .ok_or_else(|| AppError::not_found(format!("Table {} is not cached; refresh the schema first", table_name)))?;
+    let cached_columns = db::get_columns(&table.id)?;
+    for (idx, column) in columns.iter().enumerate() {
+        if !cached_columns.iter().any(|cached| &cached.name == column) {
+            return Err(AppError::invalid(format!("Column {} does not exist on {}", column, table_name)));
+        }
+        if columns[..idx].contains(column) {
+            return Err(AppError::invalid(format!("Column {} is listed twice", column)));
+        }
+    }
+
+    let target = format!("{}.{}", quote_ident(&schema_name), quote_ident(&table_name));
+    let column_list = columns.iter().map(|c| quote_ident(c)).collect::<Vec<_>>().join(", ");
+
+    let mut client = get_client(&server, &password, &target_database).await?;
+    let tx = client.transaction().await?;
+
+    // Learn each column's parameter type from a one-row statement
+    let plain = vec![None; columns.len()];
+    let probe = tx.prepare(&insert_values_sql(&target, &column_list, 1, &plain)).await?;
+    let casts: Vec<Option<String>> = probe
+        .params()
+        .iter()
+        .map(|ty| {
+            (!crate::params::can_bind(ty)).then(|| format!("{}.{}", quote_ident(ty.schema()), quote_ident(ty.name())))
+        })
+        .collect();
+
+    let rows_per_batch = MAX_BIND_PARAMS / columns.len();
+    let mut full_batch: Option<tokio_postgres::Statement> = None;
+    let mut inserted: u64 = 0;
+    for batch in rows.chunks(rows_per_batch) {
+        let statement = match &full_batch {
+            Some(statement) if batch.len() == rows_per_batch => statement.clone(),
+            _ => {
+                let statement = tx.prepare(&insert_values_sql(&target, &column_list, batch.len(), &casts)).await?;
+                if batch.len() == rows_per_batch {
+                    full_batch = Some(statement.clone());
+                }
+                statement
+            }
+        };
+
+        let values: Vec<serde_json::Value> = batch
+            .iter()
+            .flat_map(|row| row.iter().zip(&casts))
+            .map(|(value, cast)| match (value, cast) {
+                (serde_json::Value::String(_) | serde_json::Value::Null, _) | (_, None) => value.clone(),
+                (other, Some(_)) => serde_json::Value::String(other.to_string()),
+            })
+            .collect();
+        let bound = crate::params::bind_params(&values, statement.params()).map_err(AppError::invalid)?;
+        let refs: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> =
+            bound.iter().map(|param| param.as_ref() as &(dyn tokio_postgres::types::ToSql + Sync)).collect();
+        inserted += tx.execute(&statement, &refs).await?;
+    }
+    tx.commit().await?;
+
+    Ok(QueryResult {
+        columns: vec![],
+        rows: vec![],
+        rows_affected: Some(inserted as usize),
+        message: Some(format!("{} rows inserted", inserted)),
+    })
+}
+
+const MAX_TEST_DATA_ROWS: usize = 100_000;
+
+/// Development tooling, not for production: fill a table with `row_count`
+/// rows of random values (numbers, lorem text, uuids, booleans, dates and
+/// timestamps from 2020 on) through `insert_rows`. Columns with a default,
+/// identity or generated columns are left to the server. Nullable columns get
+/// an occasional NULL, and nullable columns of types without a generator are
+/// left out; a NOT NULL one is an error. Foreign keys aren't followed, so
+/// referencing columns will usually fail their constraint. The same `seed`
+/// produces the same data.
+#[command]
+pub async fn generate_test_data(
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: String,
+    table_name: String,
+    row_count: usize,
+    seed: Option<u64>,
+) -> Result<QueryResult, AppError> {
+    if !(1..=MAX_TEST_DATA_ROWS).contains(&row_count) {
+        return Err(AppError::invalid(format!(
+            "Row count must be between 1 and {}",
+            MAX_TEST_DATA_ROWS
+        )));
+    }
+
+    let table_columns = get_insertable_columns(
+        server_id.clone(),
+        database_name.clone(),
+        schema_name.clone(),
+        table_name.clone(),
+    )
+    .await?;
+
+    let mut rng = crate::test_data::TestDataRng::new(
+        seed.unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64)),
+    );
+    let mut columns = Vec::new();
+    let mut types = Vec::new();
+    for column in table_columns.iter().filter(|c| c.insertable && !c.has_default) {
+        if crate::test_data::test_value(&mut rng, &column.data_type).is_some() {
+            columns.push(column.name.clone());
+            types.push((column.data_type.as_str(), column.nullable));
+        } else if !column.nullable {
+            return Err(AppError::invalid(format!(
+                "Can't generate values for NOT NULL column {} of type {}",
+                column.name, column.data_type
+            )));
+        }
+    }
+    if columns.is_empty() {
+        return Err(AppError::invalid("Every column is filled in by the server; nothing to generate"));
+    }
+
+    let rows: Vec<Vec<serde_json::Value>> = (0..row_count)
+        .map(|_| {
+            types
+                .iter()
+                .map(|(data_type, nullable)| {
+                    if *nullable && rng.next_u64() % 10 == 0 {
+                        serde_json::Value::Null
+                    } else {
+                        crate::test_data::test_value(&mut rng, data_type).unwrap_or_default()
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut result = insert_rows(server_id, database_name, schema_name, table_name, columns, rows).await?;
+    result.message = result
+        .rows_affected
+        .map(|inserted| format!("{} rows of generated test data inserted", inserted));
+    Ok(result)
+}
+
+const MAX_BENCHMARK_RUNS: u32 = 1000;
+
+const DEFAULT_JSON_SAMPLE_SIZE: i64 = 1000;
+const MAX_JSON_SAMPLE_SIZE: i64 = 100_000;
+
+fn json_type_name(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "boolean",
+        serde_json::Value::Number(_) => "number",
+        serde_json::Value::String(_) => "string",
+        serde_json::Value::Array(_) => "array",
+        serde_json::Value::Object(_) => "object",
+    }
+}
+
+/// Record `value`'s type at `path`, then walk into objects by key and into
+/// arrays with a `[*]` step shared by every element.
+fn collect_json_paths(value: &serde_json::Value, path: String, out: &mut Vec<(String, &'static str)>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, child) in map {
+                let step = if !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                    format!("{}.{}", path, key)
+                } else {
+                    format!("{}.{}", path, serde_json::Value::String(key.clone()))
+                };
+                collect_json_paths(child, step, out);
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                collect_json_paths(item, format!("{}[*]", path), out);
+            }
+        }
+        _ => {}
+    }
+    out.push((path, json_type_name(value)));
+}
+
+/// Flatten sampled documents into one summary per path, sorted by path.
+fn infer_json_paths(values: &[serde_json::Value]) -> Vec<JsonPathSummary> {
+    let mut paths: std::collections::BTreeMap<String, (std::collections::BTreeSet<&'static str>, usize)> =
+        std::collections::BTreeMap::new();
+    for value in values {
+        let mut found = Vec::new();
+        collect_json_paths(value, "$".to_string(), &mut found);
+        let mut seen = std::collections::HashSet::new();
+        for (path, ty) in found {
+            let entry = paths.entry(path.clone()).or_default();
+            entry.0.insert(ty);
+            if seen.insert(path) {
+                entry.1 += 1;
+            }
+        }
+    }
+
+    paths
+        .into_iter()
+        .map(|(path, (types, rows))| JsonPathSummary {
+            path,
+            types: types.into_iter().map(str::to_string).collect(),
+            present_in_percent: rows as f64 * 100.0 / values.len() as f64,
+        })
+        .collect()
+}
+
+/// Summarize run durations (non-empty). Percentiles use the nearest rank.
+fn benchmark_stats(durations: Vec<f64>) -> BenchmarkResult {
+    let mut sorted = durations.clone();
+    sorted.sort_by(f64::total_cmp);
+    let rank = |pct: f64| sorted[((pct * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len()) - 1];
+    let median = if sorted.len() % 2 == 0 {
+        (sorted[sorted.len() / 2 - 1] + sorted[sorted.len() / 2]) / 2.0
+    } else {
+        sorted[sorted.len() / 2]
+    };
+
+    BenchmarkResult {
+        min: sorted[0],
+        max: sorted[sorted.len() - 1],
+        mean: durations.iter().sum::<f64>() / durations.len() as f64,
+        median,
+        p95: rank(0.95),
+        durations,
+    }
+}
+
+/// Describe the structure of a json or jsonb column from its first
+/// `sample_size` non-null values (1000 by default): every key path, the JSON
+/// types found there and how many of the sampled rows contain it. A plain
+/// `LIMIT` rather than `TABLESAMPLE`, so small tables are read in full and
+/// views work too.
+#[command]
+pub async fn infer_jsonb_schema(
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: String,
+    table_name: String,
+    column_name: String,
+    sample_size: Option<i64>,
+) -> Result<Vec<JsonPathSummary>, AppError> {
+    let sample_size = sample_size.unwrap_or(DEFAULT_JSON_SAMPLE_SIZE);
+    if !(1..=MAX_JSON_SAMPLE_SIZE).contains(&sample_size) {
+        return Err(AppError::invalid(format!(
+            "Sample size must be between 1 and {}",
+            MAX_JSON_SAMPLE_SIZE
+        )));
+    }
+
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let client = get_client(&server, &password, &target_database).await?;
+    let column = quote_ident(&column_name);
+    let sql = format!(
+        "SELECT {column}::jsonb FROM {}.{} WHERE {column} IS NOT NULL LIMIT $1",
+        quote_ident(&schema_name),
+        quote_ident(&table_name),
+    );
+    let rows = client
+        .query(&sql, &[&sample_size])
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to sample json column"))?;
+
+    let values: Vec<serde_json::Value> = rows.iter().map(|row| row.get(0)).collect();
+    Ok(infer_json_paths(&values))
+}
+
+/// Time `runs` executions of a read query (up to `MAX_BENCHMARK_RUNS`). The
+/// statement is prepared once and run one extra time first as a warmup that
+/// isn't counted. Durations are wall-clock round trips, so they include
+/// network latency and fetching the rows. Everything runs in a read-only
+/// transaction, and can be stopped with `cancel_query` using `query_id`.
+#[command]
+pub async fn benchmark_query(
+    server_id: String,
+    database_name: Option<String>,
+    sql: String,
+    runs: u32,
+    query_id: Option<String>,
+) -> Result<BenchmarkResult, AppError> {
+    let head = normalize_sql_head(&sql);
+    if !["select", "with", "explain"].iter().any(|kw| head.starts_with(kw)) {
+        return Err(AppError::invalid("Only SELECT, WITH and EXPLAIN queries can be benchmarked"));
+    }
+    if !(1..=MAX_BENCHMARK_RUNS).contains(&runs) {
+        return Err(AppError::invalid(format!("Runs must be between 1 and {}", MAX_BENCHMARK_RUNS)));
+    }
+
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let mut client = get_client(&server, &password, &target_database).await?;
+    if let Some(id) = query_id.as_deref() {
+        crate::postgres::register_cancel_token(id, client.cancel_token()).await;
+    }
+
+    let benchmark = async {
+        let tx = client.read_only_transaction().await?;
+        let statement = tx.prepare(sql.trim_end().trim_end_matches(';')).await?;
+        tx.query(&statement, &[]).await?;
+
+        let mut durations = Vec::with_capacity(runs as usize);
+        for _ in 0..runs {
+            let started = std::time::Instant::now();
+            tx.query(&statement, &[]).await?;
+            durations.push(started.elapsed().as_secs_f64() * 1000.0);
+        }
+        tx.rollback().await?;
+        Ok::<_, tokio_postgres::Error>(durations)
+    }
+    .await;
+
+    if let Some(id) = query_id.as_deref() {
+        crate::postgres::remove_cancel_token(id).await;
+    }
+
+    Ok(benchmark_stats(benchmark?))
+}
+
+/// Run a single statement with `$1`, `$2`, ... bound from `params` rather
+/// than spliced into the text. Values are converted to the types Postgres
+/// infers for each placeholder; arrays bind to Postgres arrays, so
+/// `WHERE id = ANY($1)` with `[1, 2, 3]` is a safe IN-list, and `null` binds as
+/// NULL. See `params` for the supported types.
+#[command]
+pub async fn execute_parameterized_query(
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: Option<String>,
+    sql: String,
+    params: Vec<serde_json::Value>,
+) -> Result<QueryResult, AppError> {
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let mut client = get_client(&server, &password, &target_database).await?;
+    let tx = client.transaction().await?;
+    if let Some(schema) = schema_name.as_deref() {
+        tx.batch_execute(&format!("SET LOCAL search_path TO {}", quote_ident(schema)))
+            .await?;
+    }
+
+    let sql = sql.trim_end().trim_end_matches(';');
+    let statement = tx.prepare(sql).await?;
+    let bound = crate::params::bind_params(&params, statement.params()).map_err(AppError::invalid)?;
+    let refs: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> =
+        bound.iter().map(|param| param.as_ref() as &(dyn tokio_postgres::types::ToSql + Sync)).collect();
+
+    let result = if statement.columns().is_empty() {
+        let affected = tx.execute(&statement, &refs).await?;
+        QueryResult {
+            columns: vec![],
+            rows: vec![],
+            rows_affected: Some(affected as usize),
+            message: Some(format!("{} rows affected", affected)),
+        }
+    } else {
+        let fallback =
+            crate::postgres::text_fallback_query_where(sql, statement.columns(), crate::decode::has_decoder);
+        let rows = match fallback {
+            Some(rewritten) => tx
+                .query(rewritten.as_str(), &refs)
+                .await
+                .map_err(|e| AppError::from(e).without_position())?,
+            None => tx.query(&statement, &refs).await?,
+        };
+        let types: Vec<_> = statement.columns().iter().map(|col| col.type_().clone()).collect();
+        QueryResult {
+            columns: rows_to_columns(&rows, &types),
+            rows: rows.iter().map(row_to_json).collect(),
+            rows_affected: Some(rows.len()),
+            message: None,
+        }
+    };
+    tx.commit().await?;
+
+    Ok(result)
+}
+
+/// Consecutive single-table `INSERT ... VALUES` statements from
+/// `execute_sql_file`, merged into one multi-row INSERT.
+struct InsertBatch {
+    /// Whitespace-normalized `INSERT INTO target (cols) VALUES`; only
+    /// statements with the same key join the batch
+    key: String,
+    prefix: String,
+    tuples: Vec<String>,
+    statements: usize,
+    bytes: usize,
+}
+
+const INSERT_BATCH_MAX_STATEMENTS: usize = 1000;
+const INSERT_BATCH_MAX_BYTES: usize = 1024 * 1024;
+
+/// Split `INSERT INTO target [(cols)] VALUES (...), (...)` into its prefix
+/// (through `VALUES`) and the tuple list. Anything else returns `None` and runs
+/// unbatched: `ON CONFLICT`, `RETURNING`, `INSERT ... SELECT`, `DEFAULT
+/// VALUES`, aliases, and values using dollar quotes or `E''` strings, which
+/// the tuple scanner doesn't track.
+fn split_simple_insert(sql: &str) -> Option<(&str, &str)> {
+    let lower = sql.to_ascii_lowercase();
+    let target = lower
+        .strip_prefix("insert")?
+        .trim_start()
+        .strip_prefix("into")?;
+
+    let mut depth = 0i32;
+    let mut in_double = false;
+    let mut values_at = None;
+    let bytes = lower.as_bytes();
+    for (idx, c) in lower.char_indices() {
+        match c {
+            '"' => in_double = !in_double,
+            _ if in_double => {}
+            '\'' => return None,
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            'v' if depth == 0
+                && lower[idx..].starts_with("values")
+                && (idx == 0 || !(bytes[idx - 1].is_ascii_alphanumeric() || bytes[idx - 1] == b'_'))
+                && !bytes
+                    .get(idx + 6)
+                    .is_some_and(|b| b.is_ascii_alphanumeric() || *b == b'_') =>
+            {
+                values_at = Some(idx);
+                break;
+            }
+            _ => {}
+        }
+    }
+    let values_at = values_at?;
+
+    // Between INTO and VALUES: one target name, then optionally a column list
+    let target_end = values_at - (lower.len() - target.len());
+    let target = target[..target_end].trim();
+    let name = match target.find('(') {
+        Some(paren) if target.ends_with(')') => target[..paren].trim(),
+        Some(_) => return None,
+        None => target,
+    };
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return None;
+    }
+
+    let tuples = sql[values_at + 6..].trim();
+    is_values_list(tuples).then_some((&sql[..values_at + 6], tuples))
+}
+
+/// True when `text` is only `(...)` tuples separated by commas.
+fn is_values_list(text: &str) -> bool {
+    let mut chars = text.chars().peekable();
+    let mut depth = 0;
+    let mut after_tuple = false;
+    let mut prev = ' ';
+    while let Some(c) = chars.next() {
+        if depth > 0 {
+            match c {
+                '\'' | '"' => {
+                    if c == '\'' && (prev == 'e' || prev == 'E') {
+                        return false;
+                    }
+                    // Skip the quoted text; doubled quotes are escapes
+                    loop {
+                        match chars.next() {
+                            Some(q) if q == c => {
+                                if chars.peek() == Some(&c) {
+                                    chars.next();
+                                } else {
+                                    break;
+                                }
+                            }
+                            Some(_) => {}
+                            None => return false,
+                        }
+                    }
+                }
+                '$' => return false,
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    after_tuple = depth == 0;
+                }
+                _ => {}
+            }
+        } else if c == '(' && !after_tuple {
+            depth = 1;
+        } else if c == ',' && after_tuple {
+            after_tuple = false;
+        } else if !c.is_whitespace() {
+            return false;
+        }
+        prev = c;
+    }
+    depth == 0 && after_tuple
+}
+
+/// Run and clear the pending INSERT batch, counting its original statements.
+async fn flush_insert_batch(
+    client: &tokio_postgres::Client,
+    batch: &mut Option<InsertBatch>,
+    statement_count: &mut usize,
+) -> Result<(), AppError> {
+    let Some(pending) = batch.take() else {
+        return Ok(());
+    };
+    let sql = format!("{} {}", pending.prefix, pending.tuples.join(",\n"));
+    if let Err(e) = client.batch_execute(&sql).await {
+        let preview: String = sql.chars().take(500).collect();
+        return Err(AppError::from(e).map_message(|message| {
+            format!(
+                "Failed executing batched INSERT statements {}-{}: {}\nStatement preview:\n{}",
+                *statement_count + 1,
+                *statement_count + pending.statements,
+                message,
+                preview
+            )
+        }));
+    }
+    *statement_count += pending.statements;
+    Ok(())
+}
+
+/// Default amount of COPY data gathered before it is sent to the server.
+const COPY_FLUSH_BYTES: usize = 256 * 1024;
+
+/// What `CopyBuffer::push` leaves the caller to do.
+#[derive(Debug, PartialEq)]
+enum CopyStep {
+    /// Keep feeding characters
+    More,
+    /// The chunk is full; send it
+    Flush,
+    /// The `\.` terminator was read; send what is left and finish the COPY
+    End,
+}
+
+/// Gathers the data lines of a `COPY ... FROM stdin` section, a character at
+/// a time, into chunks of about `flush_bytes`. Blank lines are dropped and
+/// `\r\n` endings are sent as `\n`.
+struct CopyBuffer {
+    line: String,
+    chunk: Vec<u8>,
+    flush_bytes: usize,
+}
+
+impl CopyBuffer {
+    fn new(flush_bytes: usize) -> Self {
+        Self {
+            line: String::new(),
+            chunk: Vec::with_capacity(flush_bytes),
+            flush_bytes,
+        }
+    }
+
+    fn push(&mut self, ch: char) -> CopyStep {
+        if ch != '\n' {
+            self.line.push(ch);
+            return CopyStep::More;
+        }
+        let line = self.line.trim_end_matches('\r');
+        let step = if line == "\\." {
+            CopyStep::End
+        } else if line.trim().is_empty() {
+            CopyStep::More
+        } else {
+            self.chunk.extend_from_slice(line.as_bytes());
+            self.chunk.push(b'\n');
+            if self.chunk.len() >= self.flush_bytes {
+                CopyStep::Flush
+            } else {
+                CopyStep::More
+            }
+        };
+        self.line.clear();
+        step
+    }
+
+    /// At end of file, whether the last line, lacking a newline, is the
+    /// terminator.
+    fn ends_at_eof(&mut self) -> bool {
+        let ends = self.line.trim_end_matches('\r') == "\\.";
+        self.line.clear();
+        ends
+    }
+}
+
+/// Send and clear the buffered COPY data lines.
+async fn flush_copy_chunk(
+    sink: &mut Option<Pin<Box<CopyInSink<Bytes>>>>,
+    chunk: &mut Vec<u8>,
+) -> Result<(), AppError> {
+    if chunk.is_empty() {
+        return Ok(());
+    }
+    let data = Bytes::from(std::mem::take(chunk));
+    if let Some(sink) = sink.as_mut() {
+        sink.as_mut()
+            .send(data)
+            .await
+            .map_err(|e| AppError::from(e).context("Failed writing COPY data"))?;
+    }
+    Ok(())
+}
+
+/// Whether a lowercased statement opens (`Some(true)`) or ends
+/// (`Some(false)`) an explicit transaction.
+fn transaction_control(statement_lower: &str) -> Option<bool> {
+    let mut words = statement_lower.split_whitespace();
+    match words.next()? {
+        "begin" | "start" => Some(true),
+        "commit" | "end" | "abort" => Some(false),
+        "rollback" => (words.next() != Some("to")).then_some(false),
+        _ => None,
+    }
+}
+
+/// Statements run from a SQL file.
+#[derive(Default)]
+struct SqlFileStats {
+    statements: usize,
+    /// INSERTs merged into multi-row batches, counted in `statements` too
+    batched_statements: usize,
+}
+
+impl SqlFileStats {
+    fn summary(&self) -> String {
+        let mut summary = format!(
+            "{} statement{}",
+            self.statements,
+            if self.statements == 1 { "" } else { "s" }
+        );
+        if self.batched_statements > 0 {
+            summary.push_str(&format!(", {} INSERTs batched", self.batched_statements));
+        }
+        summary
+    }
+}
+
+/// Execute a SQL file statement by statement, streaming `COPY ... FROM stdin`
+/// data sections in chunks of about `copy_buffer_bytes` (256 KB by default).
+/// With `batch_inserts`, runs of single-table `INSERT ... VALUES` statements
+/// (as in dumps made with `--inserts`) are merged into multi-row INSERTs,
+/// which is much faster; a failing row then fails its whole batch rather than
+/// just its own statement.
+#[command]
+pub async fn execute_sql_file(
+    server_id: String,
+    file_path: String,
+    batch_inserts: Option<bool>,
+    copy_buffer_bytes: Option<usize>,
+) -> Result<QueryResult, AppError> {
+    let copy_flush_bytes = copy_buffer_bytes.unwrap_or(COPY_FLUSH_BYTES).max(1);
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+
+    let client = get_client(&server, &password, &server.database).await?;
+
+    let path = Path::new(&file_path);
+    let file_name = path
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or("SQL file");
+
+    let stats = run_sql_file(&client, &file_path, batch_inserts.unwrap_or(false), copy_flush_bytes).await?;
+
+    Ok(QueryResult {
+        columns: vec![],
+        rows: vec![],
+        rows_affected: None,
+        message: Some(format!("Executed {} ({})", file_name, stats.summary())),
+    })
+}
+
+/// Run every file in `dir_path` whose name matches `pattern` (`*.sql` by
+/// default; `*` and `?` wildcards) in lexical order on one connection, as a
+/// migration runner would with `001_`, `002_` prefixes. Stops at the first
+/// failing file; files before it stay applied. Emits `sql_directory_progress`
+/// as each file starts and finishes.
+#[command]
+pub async fn execute_sql_directory(
+    window: Window,
+    server_id: String,
+    dir_path: String,
+    pattern: Option<String>,
+    batch_inserts: Option<bool>,
+) -> Result<QueryResult, AppError> {
+    #[derive(Serialize, Clone)]
+    struct DirectoryProgress {
+        file: String,
+        /// 0-based position of `file` in the run
+        index: usize,
+        total: usize,
+        done: bool,
+    }
+
+    let pattern = pattern.unwrap_or_else(|| "*.sql".to_string());
+    let batch_inserts = batch_inserts.unwrap_or(false);
+
+    let mut entries = tokio::fs::read_dir(&dir_path)
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read directory"))?;
+    let mut files = Vec::new();
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read directory"))?
+    {
+        let is_file = entry.file_type().await.map(|ty| ty.is_file()).unwrap_or(false);
+        if let Some(name) = entry.file_name().to_str() {
+            if is_file && wildcard_match(&pattern, name) {
+                files.push((name.to_string(), entry.path()));
+            }
+        }
+    }
+    files.sort();
+    if files.is_empty() {
+        return Err(AppError::not_found(format!("No files matching {} in {}", pattern, dir_path)));
+    }
+
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let client = get_client(&server, &password, &server.database).await?;
+
+    let total = files.len();
+    let emit_progress = |file: &str, index: usize, done: bool| {
+        let progress = DirectoryProgress {
+            file: file.to_string(),
+            index,
+            total,
+            done,
+        };
+        if let Err(e) = window.emit("sql_directory_progress", progress) {
+            eprintln!("Failed to emit sql_directory_progress: {}", e);
+        }
+    };
+
+    let mut stats = SqlFileStats::default();
+    for (index, (name, path)) in files.iter().enumerate() {
+        emit_progress(name, index, false);
+        let file_stats = run_sql_file(&client, &path.to_string_lossy(), batch_inserts, COPY_FLUSH_BYTES)
+            .await
+            .map_err(|e| {
+                e.context(format!(
+                    "Failed in {} ({} of {} files applied before it)",
+                    name, index, total
+                ))
+            })?;
+        stats.statements += file_stats.statements;
+        stats.batched_statements += file_stats.batched_statements;
+        emit_progress(name, index, true);
+    }
+
+    Ok(QueryResult {
+        columns: vec![],
+        rows: vec![],
+        rows_affected: None,
+        message: Some(format!(
+            "Executed {} file{} ({})",
+            total,
+            if total == 1 { "" } else { "s" },
+            stats.summary()
+        )),
+    })
+}
+
+/// Match a file name against a pattern where `*` is any run of characters
+/// and `?` any single one.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Last `*` seen and the name position it is currently standing in for
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, n));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Run one SQL file on `client`; see `execute_sql_file`.
+async fn run_sql_file(
+    client: &PooledClient,
+    file_path: &str,
+    batch_inserts: bool,
+    copy_flush_bytes: usize,
+) -> Result<SqlFileStats, AppError> {
+    let file = File::open(file_path)
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to open SQL file"))?;
+    let mut reader = BufReader::new(file);
+    let mut buffer = vec![0u8; 64 * 1024];
+
+    let mut statement = String::new();
+    let mut statement_count: usize = 0;
+    let mut insert_batch: Option<InsertBatch> = None;
+    let mut batched_statements: usize = 0;
+
+    // Whether the file has opened a transaction of its own
+    let mut file_transaction = false;
+
+    let mut in_copy = false;
+    let mut copy_sink: Option<Pin<Box<CopyInSink<Bytes>>>> = None;
+    let mut copy_buffer = CopyBuffer::new(copy_flush_bytes);
+
+    let mut in_single_quote = false;
+    let mut in_double_quote = false;
+    let mut pending_single_quote_end = false;
+    let mut pending_double_quote_end = false;
+    let mut in_line_comment = false;
+    let mut in_block_comment = false;
+    let mut block_prev_char: Option<char> = None;
+    let mut dollar_tag: Option<String> = None;
+    let mut dollar_candidate: Option<String> = None;
+
+    loop {
+        let bytes_read = reader
+            .read(&mut buffer)
+            .await
+            .map_err(|e| AppError::from(e).context("Failed to read SQL file"))?;
+
+        if bytes_read == 0 {
+            break;
+        }
+
+        let chunk = String::from_utf8_lossy(&buffer[..bytes_read]);
+        let mut iter = chunk.chars().peekable();
+
+        while let Some(c) = iter.next() {
+            let ch = c;
+            let mut reprocess = true;
+
+            while reprocess {
+                reprocess = false;
+
+                if in_copy {
+                    match copy_buffer.push(ch) {
+                        CopyStep::More => {}
+                        CopyStep::Flush => {
+                            flush_copy_chunk(&mut copy_sink, &mut copy_buffer.chunk).await?;
+                        }
+                        CopyStep::End => {
+                            flush_copy_chunk(&mut copy_sink, &mut copy_buffer.chunk).await?;
+                            if let Some(mut sink) = copy_sink.take() {
+                                sink.as_mut()
+                                    .finish()
+                                    .await
+                                    .map_err(|e| AppError::from(e).context("Failed to finalize COPY"))?;
+                            }
+                            client.unpin_backend().await?;
+                            in_copy = false;
+                        }
+                    }
+                    continue;
+                }
+
+                if pending_single_quote_end {
+                    if ch == '\'' {
+                        statement.push(ch);
+                        pending_single_quote_end = false;
+                        continue;
+                    } else {
+                        in_single_quote = false;
+                        pending_single_quote_end = false;
+                        reprocess = true;
+                        continue;
+                    }
+                }
+
+                if pending_double_quote_end {
+                    if ch == '"' {
+                        statement.push(ch);
+                        pending_double_quote_end = false;
+                        continue;
+                    } else {
+                        in_double_quote = false;
+                        pending_double_quote_end = false;
+                        reprocess = true;
+                        continue;
+                    }
+                }
+
+                if in_line_comment {
+                    if ch == '\n' {
+                        in_line_comment = false;
+                    }
+                    continue;
+                }
+
+                if in_block_comment {
+                    if block_prev_char == Some('*') && ch == '/' {
+                        in_block_comment = false;
+                        block_prev_char = None;
+                    } else {
+                        block_prev_char = Some(ch);
+                    }
+                    continue;
+                }
+
+                if let Some(tag) = &dollar_tag {
+                    statement.push(ch);
+                    if ch == '$' && statement.ends_with(tag) {
+                        dollar_tag = None;
+                    }
+                    continue;
+                }
+
+                if let Some(tag) = dollar_candidate.as_mut() {
+                    statement.push(ch);
+                    if ch == '$' {
+                        let tag_value = dollar_candidate.take().unwrap_or_default();
+                        dollar_tag = Some(format!("${}$", tag_value));
+                    } else if ch.is_ascii_alphanumeric() || ch == '_' {
+                        tag.push(ch);
+                    } else {
+                        dollar_candidate = None;
+                    }
+                    continue;
+                }
+
+                if in_single_quote {
+                    statement.push(ch);
+                    if ch == '\'' {
+                        pending_single_quote_end = true;
+                    }
+                    continue;
+                }
+
+                if in_double_quote {
+                    statement.push(ch);
+                    if ch == '"' {
+                        pending_double_quote_end = true;
+                    }
+                    continue;
+                }
+
+                if ch == '-' && iter.peek() == Some(&'-') {
+                    if let Some(_) = iter.next() {
+                        in_line_comment = true;
+                    }
+                    continue;
+                }
+
+                if ch == '/' && iter.peek() == Some(&'*') {
+                    if let Some(_) = iter.next() {
+                        in_block_comment = true;
+                        block_prev_char = Some('*');
+                    }
+                    continue;
+                }
+
+                if ch == '$' {
+                    statement.push(ch);
+                    dollar_candidate = Some(String::new());
+                    continue;
+                }
+
+                if ch == '\'' {
+                    statement.push(ch);
+                    in_single_quote = true;
+                    continue;
+                }
+
+                if ch == '"' {
+                    statement.push(ch);
+                    in_double_quote = true;
+                    continue;
+                }
+
+                if ch == ';' {
+                    let trimmed = statement.trim();
+                    if !trimmed.is_empty() {
+                        let trimmed_lower = trimmed.to_lowercase();
+                        let simple_insert = if batch_inserts {
+                            split_simple_insert(trimmed)
+                        } else {
+                            None
+                        };
+                        if let Some((prefix, tuples)) = simple_insert {
+                            let key = prefix.split_whitespace().collect::<Vec<_>>().join(" ");
+                            let joins = insert_batch.as_ref().is_some_and(|batch| {
+                                batch.key == key
+                                    && batch.statements < INSERT_BATCH_MAX_STATEMENTS
+                                    && batch.bytes + tuples.len() <= INSERT_BATCH_MAX_BYTES
+                            });
+                            if !joins {
+                                flush_insert_batch(client, &mut insert_batch, &mut statement_count).await?;
+                            }
+                            let batch = insert_batch.get_or_insert_with(|| InsertBatch {
+                                key,
+                                prefix: prefix.to_string(),
+                                tuples: Vec::new(),
+                                statements: 0,
+                                bytes: prefix.len(),
+                            });
+                            batch.tuples.push(tuples.to_string());
+                            batch.statements += 1;
+                            batch.bytes += tuples.len();
+                            batched_statements += 1;
+                            statement.clear();
+                            continue;
+                        }
+
+                        flush_insert_batch(client, &mut insert_batch, &mut statement_count).await?;
+                        if trimmed_lower.starts_with("copy")
                             && trimmed_lower.contains("from stdin")
                         {
+                            // COPY prepares and executes in separate round
+                            // trips; inside the file's own transaction the
+                            // backend is already held
+                            if !file_transaction {
+                                client.pin_backend().await?;
+                            }
                             let sink = client
                                 .copy_in(trimmed)
                                 .await
-                                .map_err(|e| format!("Failed to start COPY: {}", e))?;
+                                .map_err(|e| AppError::from(e).context("Failed to start COPY"))?;
                             copy_sink = Some(Box::pin(sink));
                             in_copy = true;
                         } else {
-                            if let Err(e) = client.batch_execute(trimmed).await {
-                                let preview: String = trimmed.chars().take(500).collect();
-                                return Err(format!(
-                                    "Failed executing SQL statement {}: {}\nStatement preview:\n{}",
-                                    statement_count + 1,
-                                    e,
-                                    preview
-                                ));
-                            }
-                            statement_count += 1;
+                            if let Err(e) = client.batch_execute(trimmed).await {
+                                let preview: String = trimmed.chars().take(500).collect();
+                                return Err(AppError::from(e).map_message(|message| {
+                                    format!(
+                                        "Failed executing SQL statement {}: {}\nStatement preview:\n{}",
+                                        statement_count + 1,
+                                        message,
+                                        preview
+                                    )
+                                }));
+                            }
+                            if let Some(open) = transaction_control(&trimmed_lower) {
+                                file_transaction = open;
+                            }
+                            statement_count += 1;
+                        }
+                    }
+                    statement.clear();
+                    continue;
+                }
+
+                statement.push(ch);
+            }
+        }
+    }
+
+    if in_copy && copy_buffer.ends_at_eof() {
+        flush_copy_chunk(&mut copy_sink, &mut copy_buffer.chunk).await?;
+        if let Some(mut sink) = copy_sink.take() {
+            sink.as_mut()
+                .finish()
+                .await
+                .map_err(|e| AppError::from(e).context("Failed to finalize COPY"))?;
+        }
+        client.unpin_backend().await?;
+        in_copy = false;
+    }
+
+    if in_copy {
+        return Err(AppError::invalid("COPY data did not terminate with \\."));
+    }
+
+    flush_insert_batch(client, &mut insert_batch, &mut statement_count).await?;
+
+    if !statement.trim().is_empty() {
+        let trimmed = statement.trim();
+        let trimmed_lower = trimmed.to_lowercase();
+        if trimmed_lower.starts_with("copy") && trimmed_lower.contains("from stdin") {
+            return Err(AppError::invalid("COPY statement missing data section"));
+        }
+
+        if let Err(e) = client.batch_execute(trimmed).await {
+            let preview: String = trimmed.chars().take(500).collect();
+            return Err(AppError::from(e).map_message(|message| {
+                format!(
+                    "Failed executing SQL statement {}: {}\nStatement preview:\n{}",
+                    statement_count + 1,
+                    message,
+                    preview
+                )
+            }));
+        }
+        statement_count += 1;
+    }
+
+    Ok(SqlFileStats {
+        statements: statement_count,
+        batched_statements,
+    })
+}
+
+/// Emit `export_progress` every this many COPY chunks.
+const EXPORT_PROGRESS_CHUNKS: u64 = 64;
+
+/// Stream `COPY ... TO STDOUT` into `file`, emitting `export_progress` with the
+/// bytes written so far. `totalBytes` is the on-disk relation size, which is
+/// only a rough guide to the COPY text size.
+async fn copy_out_with_progress<W: AsyncWrite + Unpin>(
+    window: &Window,
+    client: &PooledClient,
+    file: &mut W,
+    copy_query: &str,
+    schema_name: &str,
+    table_name: &str,
+) -> Result<u64, AppError> {
+    #[derive(Serialize, Clone)]
+    struct ExportProgress {
+        table: String,
+        #[serde(rename = "bytesWritten")]
+        bytes_written: u64,
+        #[serde(rename = "totalBytes")]
+        total_bytes: Option<i64>,
+        done: bool,
+    }
+
+    let total_bytes = client
+        .query_opt(
+            "SELECT pg_total_relation_size(c.oid)
+             FROM pg_class c
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             WHERE n.nspname = $1 AND c.relname = $2",
+            &[&schema_name, &table_name],
+        )
+        .await
+        .ok()
+        .flatten()
+        .map(|row| row.get::<_, i64>(0));
+
+    let table = format!("{}.{}", schema_name, table_name);
+    let emit_progress = |bytes_written: u64, done: bool| {
+        let progress = ExportProgress {
+            table: table.clone(),
+            bytes_written,
+            total_bytes,
+            done,
+        };
+        if let Err(e) = window.emit("export_progress", progress) {
+            eprintln!("Failed to emit export_progress: {}", e);
+        }
+    };
+
+    // COPY prepares and executes in separate round trips
+    client.pin_backend().await?;
+    let stream = client
+        .copy_out(copy_query)
+        .await
+        .map_err(|e| AppError::from(e).context(format!("Failed to export data for {}", table_name)))?;
+
+    let mut stream = Box::pin(stream);
+    let mut bytes_written: u64 = 0;
+    let mut chunks: u64 = 0;
+
+    while let Some(chunk) = stream.as_mut().next().await {
+        let bytes = chunk
+            .map_err(|e| AppError::from(e).context("Failed to read COPY data"))?;
+        file.write_all(&bytes)
+            .await
+            .map_err(|e| AppError::from(e).context("Failed to write COPY data"))?;
+
+        bytes_written += bytes.len() as u64;
+        chunks += 1;
+        if chunks % EXPORT_PROGRESS_CHUNKS == 0 {
+            emit_progress(bytes_written, false);
+        }
+    }
+
+    client.unpin_backend().await?;
+    emit_progress(bytes_written, true);
+    Ok(bytes_written)
+}
+
+/// Rough DDL output per table, column and index, for `estimate_export_size`.
+const DDL_BYTES_PER_TABLE: i64 = 256;
+const DDL_BYTES_PER_COLUMN: i64 = 48;
+const DDL_BYTES_PER_INDEX: i64 = 160;
+
+/// Estimated size of an `export_schema_sql` file, from the catalog alone.
+/// Data is estimated as each table's heap plus TOAST size
+/// (`pg_table_size`); indexes aren't exported as data, and COPY text usually
+/// lands within a factor of two of the on-disk size either way. Tables are
+/// listed largest first so the biggest can be left out.
+#[command]
+pub async fn estimate_export_size(
+    server_id: String,
+    schema_name: String,
+    include_data: bool,
+) -> Result<ExportSizeEstimate, AppError> {
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+
+    let client = get_client(&server, &password, &server.database).await?;
+    let rows = client
+        .query(
+            "SELECT c.relname::text,
+                    pg_table_size(c.oid),
+                    (SELECT count(*) FROM pg_attribute a
+                     WHERE a.attrelid = c.oid AND a.attnum > 0 AND NOT a.attisdropped),
+                    (SELECT count(*) FROM pg_index i WHERE i.indrelid = c.oid)
+             FROM pg_class c
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             WHERE n.nspname = $1 AND c.relkind IN ('r', 'p')",
+            &[&schema_name],
+        )
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read table sizes"))?;
+
+    let mut tables: Vec<TableExportSize> = rows
+        .iter()
+        .map(|row| {
+            let columns: i64 = row.get(2);
+            let indexes: i64 = row.get(3);
+            TableExportSize {
+                table: row.get(0),
+                ddl_bytes: DDL_BYTES_PER_TABLE + columns * DDL_BYTES_PER_COLUMN + indexes * DDL_BYTES_PER_INDEX,
+                data_bytes: if include_data { row.get(1) } else { 0 },
+            }
+        })
+        .collect();
+    tables.sort_by(|a, b| {
+        (b.ddl_bytes + b.data_bytes)
+            .cmp(&(a.ddl_bytes + a.data_bytes))
+            .then_with(|| a.table.cmp(&b.table))
+    });
+
+    Ok(ExportSizeEstimate {
+        total_bytes: tables.iter().map(|t| t.ddl_bytes + t.data_bytes).sum(),
+        tables,
+    })
+}
+
+#[command]
+pub async fn export_schema_sql(
+    window: Window,
+    server_id: String,
+    schema_name: String,
+    include_data: bool,
+    output_path: String,
+    anonymize_columns: Option<Vec<AnonymizeColumn>>,
+    emit_checksum: Option<bool>,
+) -> Result<QueryResult, AppError> {
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+
+    let client = get_client(&server, &password, &server.database).await?;
+
+    let anonymize = anonymize_columns.as_deref().unwrap_or_default();
+    if include_data && !anonymize.is_empty() {
+        let rows = client
+            .query(
+                "SELECT c.relname, a.attname
+                 FROM pg_attribute a
+                 JOIN pg_class c ON a.attrelid = c.oid
+                 JOIN pg_namespace n ON c.relnamespace = n.oid
+                 WHERE n.nspname = $1 AND c.relkind IN ('r', 'p') AND a.attnum > 0 AND NOT a.attisdropped",
+                &[&schema_name],
+            )
+            .await
+            .map_err(|e| AppError::from(e).context("Failed to read columns"))?;
+        let mut table_columns: std::collections::HashMap<String, Vec<String>> = std::collections::HashMap::new();
+        for row in &rows {
+            table_columns.entry(row.get(0)).or_default().push(quote_ident(row.get(1)));
+        }
+        check_anonymize_rules(
+            anonymize,
+            table_columns.iter().map(|(table, columns)| (table.as_str(), columns.as_slice())),
+        )?;
+    }
+
+    let file = File::create(&output_path)
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to create export file"))?;
+    let mut file = ChecksumWriter::new(file, emit_checksum.unwrap_or(false));
+
+    write_str(&mut file, "-- FastDB schema export\n").await?;
+    write_str(
+        &mut file,
+        &format!("-- Schema: {}\n\n", schema_name),
+    )
+    .await?;
+
+    let schema_q = quote_ident(&schema_name);
+    write_str(
+        &mut file,
+        &format!("CREATE SCHEMA IF NOT EXISTS {};\n\n", schema_q),
+    )
+    .await?;
+
+    let sequences = client
+        .query(
+            "SELECT sequence_name FROM information_schema.sequences WHERE sequence_schema = $1 ORDER BY sequence_name",
+            &[&schema_name],
+        )
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read sequences"))?;
+
+    for row in &sequences {
+        let seq_name: String = row.get(0);
+        let seq_q = quote_ident(&seq_name);
+        write_str(
+            &mut file,
+            &format!("CREATE SEQUENCE {}.{};\n", schema_q, seq_q),
+        )
+        .await?;
+    }
+
+    if !sequences.is_empty() {
+        write_str(&mut file, "\n").await?;
+    }
+
+    let tables = client
+        .query(
+            "SELECT table_name FROM information_schema.tables WHERE table_schema = $1 AND table_type = 'BASE TABLE' ORDER BY table_name",
+            &[&schema_name],
+        )
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read tables"))?;
+
+    for row in &tables {
+        let table_name: String = row.get(0);
+        let table_q = quote_ident(&table_name);
+
+        let columns = client
+            .query(
+                "SELECT a.attname, pg_catalog.format_type(a.atttypid, a.atttypmod), a.attnotnull, pg_get_expr(ad.adbin, ad.adrelid)
+                 FROM pg_attribute a
+                 JOIN pg_class c ON a.attrelid = c.oid
+                 JOIN pg_namespace n ON c.relnamespace = n.oid
+                 LEFT JOIN pg_attrdef ad ON a.attrelid = ad.adrelid AND a.attnum = ad.adnum
+                 WHERE n.nspname = $1 AND c.relname = $2 AND a.attnum > 0 AND NOT a.attisdropped
+                 ORDER BY a.attnum",
+                &[&schema_name, &table_name],
+            )
+            .await
+            .map_err(|e| AppError::from(e).context(format!("Failed to read columns for {}", table_name)))?;
+
+        let mut column_defs = Vec::new();
+        let mut column_names = Vec::new();
+
+        for col in columns {
+            let col_name: String = col.get(0);
+            let col_type: String = col.get(1);
+            let not_null: bool = col.get(2);
+            let default_expr: Option<String> = col.get(3);
+
+            let mut def = format!("{} {}", quote_ident(&col_name), col_type);
+            if let Some(expr) = default_expr {
+                def.push_str(&format!(" DEFAULT {}", expr));
+            }
+            if not_null {
+                def.push_str(" NOT NULL");
+            }
+
+            column_defs.push(def);
+            column_names.push(quote_ident(&col_name));
+        }
+
+        write_str(
+            &mut file,
+            &format!("CREATE TABLE {}.{} (\n    {}\n);\n", schema_q, table_q, column_defs.join(",\n    ")),
+        )
+        .await?;
+
+        let constraints = client
+            .query(
+                "SELECT con.conname, pg_get_constraintdef(con.oid)
+                 FROM pg_constraint con
+                 JOIN pg_class c ON con.conrelid = c.oid
+                 JOIN pg_namespace n ON c.relnamespace = n.oid
+                 WHERE n.nspname = $1 AND c.relname = $2
+                 ORDER BY con.conname",
+                &[&schema_name, &table_name],
+            )
+            .await
+            .map_err(|e| AppError::from(e).context(format!("Failed to read constraints for {}", table_name)))?;
+
+        for constraint in constraints {
+            let con_name: String = constraint.get(0);
+            let con_def: String = constraint.get(1);
+            write_str(
+                &mut file,
+                &format!(
+                    "ALTER TABLE {}.{} ADD CONSTRAINT {} {};\n",
+                    schema_q,
+                    table_q,
+                    quote_ident(&con_name),
+                    con_def
+                ),
+            )
+            .await?;
+        }
+
+        let constraint_indexes = client
+            .query(
+                "SELECT c2.relname
+                 FROM pg_constraint con
+                 JOIN pg_class c ON con.conrelid = c.oid
+                 JOIN pg_class c2 ON c2.oid = con.conindid
+                 JOIN pg_namespace n ON c.relnamespace = n.oid
+                 WHERE n.nspname = $1 AND c.relname = $2 AND con.conindid <> 0",
+                &[&schema_name, &table_name],
+            )
+            .await
+            .map_err(|e| AppError::from(e).context(format!("Failed to read indexes for {}", table_name)))?;
+
+        let mut constraint_index_names = std::collections::HashSet::new();
+        for idx in constraint_indexes {
+            let name: String = idx.get(0);
+            constraint_index_names.insert(name);
+        }
+
+        let indexes = client
+            .query(
+                "SELECT indexname, indexdef FROM pg_indexes WHERE schemaname = $1 AND tablename = $2 ORDER BY indexname",
+                &[&schema_name, &table_name],
+            )
+            .await
+            .map_err(|e| AppError::from(e).context(format!("Failed to read indexes for {}", table_name)))?;
+
+        for index in indexes {
+            let index_name: String = index.get(0);
+            let index_def: String = index.get(1);
+            if constraint_index_names.contains(&index_name) {
+                continue;
+            }
+            let statement = if index_def.ends_with(';') {
+                index_def
+            } else {
+                format!("{};", index_def)
+            };
+            write_str(&mut file, &format!("{}\n", statement)).await?;
+        }
+
+        write_str(&mut file, "\n").await?;
+
+        if include_data {
+            if !column_names.is_empty() {
+                write_str(
+                    &mut file,
+                    &format!(
+                        "COPY {}.{} ({}) FROM stdin;\n",
+                        schema_q,
+                        table_q,
+                        column_names.join(", ")
+                    ),
+                )
+                .await?;
+
+                let copy_query = data_copy_query(&schema_q, &table_q, &table_name, &column_names, anonymize);
+
+                copy_out_with_progress(
+                    &window,
+                    &client,
+                    &mut file,
+                    &copy_query,
+                    &schema_name,
+                    &table_name,
+                )
+                .await?;
+
+                write_str(&mut file, "\\.\n\n").await?;
+            }
+        }
+    }
+
+    let views = client
+        .query(
+            "SELECT table_name, view_definition FROM information_schema.views WHERE table_schema = $1 ORDER BY table_name",
+            &[&schema_name],
+        )
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read views"))?;
+
+    if !views.is_empty() {
+        write_str(&mut file, "-- Views\n").await?;
+    }
+
+    for view in views {
+        let view_name: String = view.get(0);
+        let view_def: String = view.get(1);
+        write_str(
+            &mut file,
+            &format!(
+                "CREATE OR REPLACE VIEW {}.{} AS\n{};\n\n",
+                schema_q,
+                quote_ident(&view_name),
+                view_def
+            ),
+        )
+        .await?;
+    }
+
+    file.flush()
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to finalize export file"))?;
+    let checksum = file.finish(&output_path).await?;
+
+    Ok(QueryResult {
+        columns: vec![],
+        rows: vec![],
+        rows_affected: None,
+        message: Some(format!("Schema exported to {}{}", output_path, checksum_note(checksum))),
+    })
+}
+
+/// DDL for one table, split so multi-table exports can order statements
+/// across tables.
+struct TableDdl {
+    schema_q: String,
+    table_q: String,
+    create: String,
+    /// Non-FK constraints, safe to add right after the table exists
+    constraints: Vec<String>,
+    /// FK constraints, which may reference tables created later in the script
+    foreign_keys: Vec<String>,
+    indexes: Vec<String>,
+    column_names: Vec<String>,
+}
+
+async fn read_table_ddl(
+    client: &PooledClient,
+    schema_name: &str,
+    table_name: &str,
+) -> Result<TableDdl, AppError> {
+    let schema_q = quote_ident(schema_name);
+    let table_q = quote_ident(table_name);
+
+    let columns = client
+        .query(
+            "SELECT a.attname, pg_catalog.format_type(a.atttypid, a.atttypmod), a.attnotnull, pg_get_expr(ad.adbin, ad.adrelid)
+             FROM pg_attribute a
+             JOIN pg_class c ON a.attrelid = c.oid
+             JOIN pg_namespace n ON c.relnamespace = n.oid
+             LEFT JOIN pg_attrdef ad ON a.attrelid = ad.adrelid AND a.attnum = ad.adnum
+             WHERE n.nspname = $1 AND c.relname = $2 AND a.attnum > 0 AND NOT a.attisdropped
+             ORDER BY a.attnum",
+            &[&schema_name, &table_name],
+        )
+        .await
+        .map_err(|e| AppError::from(e).context(format!("Failed to read columns for {}", table_name)))?;
+
+    if columns.is_empty() {
+        return Err(AppError::not_found(format!("Table {}.{} not found", schema_name, table_name)));
+    }
+
+    let mut column_defs = Vec::new();
+    let mut column_names = Vec::new();
+
+    for col in columns {
+        let col_name: String = col.get(0);
+        let col_type: String = col.get(1);
+        let not_null: bool = col.get(2);
+        let default_expr: Option<String> = col.get(3);
+
+        let mut def = format!("{} {}", quote_ident(&col_name), col_type);
+        if let Some(expr) = default_expr {
+            def.push_str(&format!(" DEFAULT {}", expr));
+        }
+        if not_null {
+            def.push_str(" NOT NULL");
+        }
+
+        column_defs.push(def);
+        column_names.push(quote_ident(&col_name));
+    }
+
+    let create = format!("CREATE TABLE {}.{} (\n    {}\n);\n", schema_q, table_q, column_defs.join(",\n    "));
+
+    let constraint_rows = client
+        .query(
+            "SELECT con.conname, pg_get_constraintdef(con.oid), con.contype = 'f'
+             FROM pg_constraint con
+             JOIN pg_class c ON con.conrelid = c.oid
+             JOIN pg_namespace n ON c.relnamespace = n.oid
+             WHERE n.nspname = $1 AND c.relname = $2
+             ORDER BY con.conname",
+            &[&schema_name, &table_name],
+        )
+        .await
+        .map_err(|e| AppError::from(e).context(format!("Failed to read constraints for {}", table_name)))?;
+
+    let mut constraints = Vec::new();
+    let mut foreign_keys = Vec::new();
+    for constraint in constraint_rows {
+        let con_name: String = constraint.get(0);
+        let con_def: String = constraint.get(1);
+        let is_foreign_key: bool = constraint.get(2);
+        let statement = format!(
+            "ALTER TABLE {}.{} ADD CONSTRAINT {} {};\n",
+            schema_q,
+            table_q,
+            quote_ident(&con_name),
+            con_def
+        );
+        if is_foreign_key {
+            foreign_keys.push(statement);
+        } else {
+            constraints.push(statement);
+        }
+    }
+
+    let constraint_indexes = client
+        .query(
+            "SELECT c2.relname
+             FROM pg_constraint con
+             JOIN pg_class c ON con.conrelid = c.oid
+             JOIN pg_class c2 ON c2.oid = con.conindid
+             JOIN pg_namespace n ON c.relnamespace = n.oid
+             WHERE n.nspname = $1 AND c.relname = $2 AND con.conindid <> 0",
+            &[&schema_name, &table_name],
+        )
+        .await
+        .map_err(|e| AppError::from(e).context(format!("Failed to read indexes for {}", table_name)))?;
+
+    let mut constraint_index_names = std::collections::HashSet::new();
+    for idx in constraint_indexes {
+        let name: String = idx.get(0);
+        constraint_index_names.insert(name);
+    }
+
+    let index_rows = client
+        .query(
+            "SELECT indexname, indexdef FROM pg_indexes WHERE schemaname = $1 AND tablename = $2 ORDER BY indexname",
+            &[&schema_name, &table_name],
+        )
+        .await
+        .map_err(|e| AppError::from(e).context(format!("Failed to read indexes for {}", table_name)))?;
+
+    let mut indexes = Vec::new();
+    for index in index_rows {
+        let index_name: String = index.get(0);
+        let index_def: String = index.get(1);
+        if constraint_index_names.contains(&index_name) {
+            continue;
+        }
+        let statement = if index_def.ends_with(';') {
+            index_def
+        } else {
+            format!("{};", index_def)
+        };
+        indexes.push(format!("{}\n", statement));
+    }
+
+    Ok(TableDdl {
+        schema_q,
+        table_q,
+        create,
+        constraints,
+        foreign_keys,
+        indexes,
+        column_names,
+    })
+}
+
+/// How an exported column's values are replaced.
+///
+/// - `null`: every value becomes NULL. Irreversible; fails to load into
+///   `NOT NULL` columns.
+/// - `hash`: hex SHA-256 of the value's text. Equal inputs give equal outputs,
+///   so joins and uniqueness survive, but short or guessable values (ids,
+///   emails from a known list) can be recovered by hashing candidates.
+/// - `fake`: `<column>_` plus 8 hex chars of the value's MD5, e.g.
+///   `email_1a79a4d6`. Deterministic like `hash` and just as guessable, but
+///   readable; collisions are possible on large tables.
+///
+/// `hash` and `fake` produce text, so use them on text-like columns. NULLs
+/// stay NULL under every strategy.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum AnonymizeStrategy {
+    Null,
+    Hash,
+    Fake,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AnonymizeColumn {
+    pub table: String,
+    pub column: String,
+    pub strategy: AnonymizeStrategy,
+}
+
+/// SQL expression replacing the quoted column `column_q` per `strategy`.
+fn anonymize_expr(column: &str, column_q: &str, strategy: AnonymizeStrategy) -> String {
+    match strategy {
+        AnonymizeStrategy::Null => "NULL".to_string(),
+        AnonymizeStrategy::Hash => format!(
+            "encode(sha256(convert_to({}::text, 'UTF8')), 'hex')",
+            column_q
+        ),
+        AnonymizeStrategy::Fake => format!(
+            "{} || substr(md5({}::text), 1, 8)",
+            quote_literal(&format!("{}_", column)),
+            column_q
+        ),
+    }
+}
+
+/// `COPY ... TO STDOUT` for a table's data. Tables with anonymized columns
+/// are copied through a `SELECT` that applies the transforms server-side, since
+/// the plain COPY stream is opaque bytes.
+fn data_copy_query(
+    schema_q: &str,
+    table_q: &str,
+    table_name: &str,
+    column_names: &[String],
+    anonymize: &[AnonymizeColumn],
+) -> String {
+    let rules: Vec<&AnonymizeColumn> = anonymize.iter().filter(|rule| rule.table == table_name).collect();
+    if rules.is_empty() {
+        return format!("COPY {}.{} ({}) TO STDOUT", schema_q, table_q, column_names.join(", "));
+    }
+
+    let select_list: Vec<String> = column_names
+        .iter()
+        .map(|column_q| {
+            match rules.iter().find(|rule| quote_ident(&rule.column) == *column_q) {
+                Some(rule) => format!("{} AS {}", anonymize_expr(&rule.column, column_q, rule.strategy), column_q),
+                None => column_q.clone(),
+            }
+        })
+        .collect();
+    format!(
+        "COPY (SELECT {} FROM {}.{}) TO STDOUT",
+        select_list.join(", "),
+        schema_q,
+        table_q
+    )
+}
+
+/// Fail unless every anonymize rule names an exported table and one of its
+/// columns. `exported` pairs each table with its quoted column names, as
+/// `data_copy_query` sees them. A misspelt rule would otherwise be ignored and
+/// the column's raw values exported.
+fn check_anonymize_rules<'a>(
+    rules: &[AnonymizeColumn],
+    exported: impl IntoIterator<Item = (&'a str, &'a [String])> + Clone,
+) -> Result<(), AppError> {
+    let unmatched: Vec<String> = rules
+        .iter()
+        .filter(|rule| {
+            let column_q = quote_ident(&rule.column);
+            !exported
+                .clone()
+                .into_iter()
+                .any(|(table, columns)| table == rule.table && columns.contains(&column_q))
+        })
+        .map(|rule| format!("{}.{}", rule.table, rule.column))
+        .collect();
+    if unmatched.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::invalid(format!(
+            "Anonymize rules match no exported column: {}",
+            unmatched.join(", ")
+        )))
+    }
+}
+
+/// Write a table's rows as a `COPY ... FROM stdin` block, applying any
+/// `anonymize` rules for the table.
+async fn write_table_data<W: AsyncWrite + Unpin>(
+    window: &Window,
+    client: &PooledClient,
+    file: &mut W,
+    schema_name: &str,
+    table_name: &str,
+    ddl: &TableDdl,
+    anonymize: &[AnonymizeColumn],
+) -> Result<(), AppError> {
+    if ddl.column_names.is_empty() {
+        return Ok(());
+    }
+
+    let column_list = ddl.column_names.join(", ");
+    write_str(
+        file,
+        &format!("COPY {}.{} ({}) FROM stdin;\n", ddl.schema_q, ddl.table_q, column_list),
+    )
+    .await?;
+
+    let copy_query = data_copy_query(&ddl.schema_q, &ddl.table_q, table_name, &ddl.column_names, anonymize);
+    copy_out_with_progress(window, client, file, &copy_query, schema_name, table_name).await?;
+
+    write_str(file, "\\.\n").await
+}
+
+#[allow(clippy::too_many_arguments)]
+#[command]
+pub async fn export_table_sql(
+    window: Window,
+    server_id: String,
+    schema_name: String,
+    table_name: String,
+    include_data: bool,
+    output_path: String,
+    anonymize_columns: Option<Vec<AnonymizeColumn>>,
+    emit_checksum: Option<bool>,
+) -> Result<QueryResult, AppError> {
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+
+    let client = get_client(&server, &password, &server.database).await?;
+
+    let ddl = read_table_ddl(&client, &schema_name, &table_name).await?;
+    let anonymize = anonymize_columns.as_deref().unwrap_or_default();
+    if include_data {
+        check_anonymize_rules(anonymize, [(table_name.as_str(), ddl.column_names.as_slice())])?;
+    }
+
+    let file = File::create(&output_path)
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to create export file"))?;
+    let mut file = ChecksumWriter::new(file, emit_checksum.unwrap_or(false));
+
+    write_str(&mut file, "-- FastDB table export\n").await?;
+    write_str(
+        &mut file,
+        &format!("-- Table: {}.{}\n\n", schema_name, table_name),
+    )
+    .await?;
+
+    write_str(&mut file, &ddl.create).await?;
+    for statement in ddl.constraints.iter().chain(&ddl.foreign_keys).chain(&ddl.indexes) {
+        write_str(&mut file, statement).await?;
+    }
+
+    write_str(&mut file, "\n").await?;
+
+    if include_data {
+        write_table_data(
+            &window,
+            &client,
+            &mut file,
+            &schema_name,
+            &table_name,
+            &ddl,
+            anonymize,
+        )
+        .await?;
+    }
+
+    file.flush()
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to finalize export file"))?;
+    let checksum = file.finish(&output_path).await?;
+
+    Ok(QueryResult {
+        columns: vec![],
+        rows: vec![],
+        rows_affected: None,
+        message: Some(format!("Table exported to {}{}", output_path, checksum_note(checksum))),
+    })
+}
+
+/// Export several tables of one schema into a single script. All CREATEs come
+/// first and foreign keys last, so tables may reference each other in any
+/// order. Tables whose definition can't be read are skipped and reported.
+#[allow(clippy::too_many_arguments)]
+#[command]
+pub async fn export_tables(
+    window: Window,
+    server_id: String,
+    schema_name: String,
+    table_names: Vec<String>,
+    include_data: bool,
+    output_path: String,
+    anonymize_columns: Option<Vec<AnonymizeColumn>>,
+    emit_checksum: Option<bool>,
+) -> Result<QueryResult, AppError> {
+    if table_names.is_empty() {
+        return Err(AppError::invalid("No tables selected for export"));
+    }
+
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+
+    let client = get_client(&server, &password, &server.database).await?;
+
+    let mut exported = Vec::new();
+    let mut failed = Vec::new();
+    for table_name in &table_names {
+        match read_table_ddl(&client, &schema_name, table_name).await {
+            Ok(ddl) => exported.push((table_name, ddl)),
+            Err(e) => failed.push(format!("{} ({})", table_name, e)),
+        }
+    }
+
+    if exported.is_empty() {
+        return Err(AppError::not_found(format!(
+            "None of the selected tables could be exported: {}",
+            failed.join(", ")
+        )));
+    }
+    let anonymize = anonymize_columns.as_deref().unwrap_or_default();
+    if include_data {
+        check_anonymize_rules(
+            anonymize,
+            exported.iter().map(|(name, ddl)| (name.as_str(), ddl.column_names.as_slice())),
+        )?;
+    }
+
+    let file = File::create(&output_path)
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to create export file"))?;
+    let mut file = ChecksumWriter::new(file, emit_checksum.unwrap_or(false));
+
+    write_str(&mut file, "-- FastDB tables export\n").await?;
+    write_str(&mut file, &format!("-- Schema: {}\n", schema_name)).await?;
+    write_str(
+        &mut file,
+        &format!(
+            "-- Tables: {}\n\n",
+            exported.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>().join(", ")
+        ),
+    )
+    .await?;
+
+    for (_, ddl) in &exported {
+        write_str(&mut file, &ddl.create).await?;
+        write_str(&mut file, "\n").await?;
+    }
+
+    for (table_name, ddl) in &exported {
+        write_str(&mut file, &format!("-- {}.{}\n", schema_name, table_name)).await?;
+        for statement in ddl.constraints.iter().chain(&ddl.indexes) {
+            write_str(&mut file, statement).await?;
+        }
+        if include_data {
+            write_table_data(
+                &window,
+                &client,
+                &mut file,
+                &schema_name,
+                table_name,
+                ddl,
+                anonymize,
+            )
+            .await?;
+        }
+        write_str(&mut file, "\n").await?;
+    }
+
+    // Added after the data so rows load regardless of dependency order
+    if exported.iter().any(|(_, ddl)| !ddl.foreign_keys.is_empty()) {
+        write_str(&mut file, "-- Foreign keys\n").await?;
+        for (_, ddl) in &exported {
+            for statement in &ddl.foreign_keys {
+                write_str(&mut file, statement).await?;
+            }
+        }
+    }
+
+    file.flush()
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to finalize export file"))?;
+    let checksum = file.finish(&output_path).await?;
+
+    let mut message = format!(
+        "Exported {} of {} tables to {}{}",
+        exported.len(),
+        table_names.len(),
+        output_path,
+        checksum_note(checksum)
+    );
+    if !failed.is_empty() {
+        message.push_str(&format!("; failed: {}", failed.join(", ")));
+    }
+
+    Ok(QueryResult {
+        columns: vec![],
+        rows: vec![],
+        rows_affected: Some(exported.len()),
+        message: Some(message),
+    })
+}
+
+/// Default rows fetched per cursor round trip when exporting NDJSON.
+const DEFAULT_FETCH_BATCH_SIZE: i64 = 1000;
+const MAX_FETCH_BATCH_SIZE: i64 = 100_000;
+
+/// Dump a table as newline-delimited JSON, one object per row. Rows are read
+/// through a server-side cursor so memory stays bounded, and values go through
+/// the typed decoder so numbers, bools and jsonb keep their JSON types.
+/// Columns the decoder can't handle are written as their text form, and
+/// numerics too precise for a JSON number are written as strings.
+/// `batch_size` rows are fetched per round trip (default 1000): lower suits
+/// wide rows, higher suits narrow ones.
+/// Emits `export_progress` per batch and can be stopped with `cancel_query`
+/// using `query_id`; a cancelled or failed export removes the partial file.
+/// `emit_checksum` also writes a `.sha256` file beside the export.
+#[allow(clippy::too_many_arguments)]
+#[command]
+pub async fn export_table_ndjson(
+    window: Window,
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: String,
+    table_name: String,
+    output_path: String,
+    query_id: Option<String>,
+    batch_size: Option<i64>,
+    emit_checksum: Option<bool>,
+) -> Result<QueryResult, AppError> {
+    #[derive(Serialize, Clone)]
+    struct NdjsonProgress {
+        table: String,
+        #[serde(rename = "rowsWritten")]
+        rows_written: u64,
+        #[serde(rename = "totalRows")]
+        total_rows: Option<i64>,
+        #[serde(rename = "batchSize")]
+        batch_size: i64,
+        done: bool,
+    }
+
+    validate_identifier(&schema_name)?;
+    validate_identifier(&table_name)?;
+    let batch_size = batch_size.unwrap_or(DEFAULT_FETCH_BATCH_SIZE);
+    if !(1..=MAX_FETCH_BATCH_SIZE).contains(&batch_size) {
+        return Err(AppError::invalid(format!(
+            "Batch size must be between 1 and {}",
+            MAX_FETCH_BATCH_SIZE
+        )));
+    }
+
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let mut client = get_client(&server, &password, &target_database).await?;
+    if let Some(id) = query_id.as_deref() {
+        crate::postgres::register_cancel_token(id, client.cancel_token()).await;
+    }
+
+    let table = format!("{}.{}", schema_name, table_name);
+    let emit_progress = |rows_written: u64, total_rows: Option<i64>, done: bool| {
+        let progress = NdjsonProgress {
+            table: table.clone(),
+            rows_written,
+            total_rows,
+            batch_size,
+            done,
+        };
+        if let Err(e) = window.emit("export_progress", progress) {
+            eprintln!("Failed to emit export_progress: {}", e);
+        }
+    };
+
+    let export = async {
+        // Planner estimate; only used to size the progress bar
+        let total_rows = client
+            .query_opt(
+                "SELECT GREATEST(c.reltuples, 0)::int8
+                 FROM pg_class c
+                 JOIN pg_namespace n ON n.oid = c.relnamespace
+                 WHERE n.nspname = $1 AND c.relname = $2",
+                &[&schema_name, &table_name],
+            )
+            .await
+            .ok()
+            .flatten()
+            .map(|row| row.get::<_, i64>(0));
+
+        let file = File::create(&output_path)
+            .await
+            .map_err(|e| AppError::from(e).context("Failed to create export file"))?;
+        let mut file = ChecksumWriter::new(BufWriter::new(file), emit_checksum.unwrap_or(false));
+
+        let tx = client.transaction().await?;
+        // Columns without a decoder (dates, arrays, extension types) are
+        // read as text rather than written as null
+        let select_sql = format!("SELECT * FROM {}.{}", quote_ident(&schema_name), quote_ident(&table_name));
+        let prepared = tx.prepare(&select_sql).await?;
+        let cursor_sql =
+            crate::postgres::text_fallback_query_where(&select_sql, prepared.columns(), crate::decode::has_decoder)
+                .unwrap_or(select_sql);
+        tx.batch_execute(&format!("DECLARE fastdb_ndjson_export NO SCROLL CURSOR FOR {}", cursor_sql))
+            .await
+            .map_err(|e| AppError::from(e).context(format!("Failed to export data for {}", table_name)))?;
+        let fetch = tx
+            .prepare(&format!("FETCH {} FROM fastdb_ndjson_export", batch_size))
+            .await?;
+
+        // Lets the frontend show the batch size before the first rows arrive
+        emit_progress(0, total_rows, false);
+        let mut rows_written: u64 = 0;
+        loop {
+            let rows = tx.query(&fetch, &[]).await?;
+            if rows.is_empty() {
+                break;
+            }
+            for row in &rows {
+                let mut line = row_to_json(row).to_string();
+                line.push('\n');
+                file.write_all(line.as_bytes())
+                    .await
+                    .map_err(|e| AppError::from(e).context("Failed to write export file"))?;
+            }
+            rows_written += rows.len() as u64;
+            emit_progress(rows_written, total_rows, false);
+        }
+        tx.commit().await?;
+
+        file.flush()
+            .await
+            .map_err(|e| AppError::from(e).context("Failed to finalize export file"))?;
+        let checksum = file.finish(&output_path).await?;
+        emit_progress(rows_written, total_rows, true);
+        Ok::<_, AppError>((rows_written, checksum))
+    }
+    .await;
+
+    if let Some(id) = query_id.as_deref() {
+        crate::postgres::remove_cancel_token(id).await;
+    }
+
+    let (rows_written, checksum) = match export {
+        Ok(result) => result,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&output_path).await;
+            return Err(e);
+        }
+    };
+
+    Ok(QueryResult {
+        columns: vec![],
+        rows: vec![],
+        rows_affected: Some(rows_written as usize),
+        message: Some(format!(
+            "Exported {} rows of {} to {}{}",
+            rows_written,
+            table,
+            output_path,
+            checksum_note(checksum)
+        )),
+    })
+}
+
+/// Run a query and write its result as an Apache Arrow IPC file for loading
+/// into dataframes. Columns keep their types where Arrow has a match (see
+/// `arrow_export`); others are written as text. The query runs in a read-only
+/// transaction and its result is held in memory, so this suits analysis
+/// extracts rather than whole-table dumps.
+#[command]
+pub async fn export_query_arrow(
+    server_id: String,
+    database_name: Option<String>,
+    sql: String,
+    output_path: String,
+) -> Result<QueryResult, AppError> {
+    let statement = sql.trim().trim_end_matches(';');
+    if statement.is_empty() {
+        return Err(AppError::invalid("Query is required"));
+    }
+
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let mut client = get_client(&server, &password, &target_database).await?;
+    let tx = client.read_only_transaction().await?;
+    let mut prepared = tx.prepare(statement).await?;
+    if prepared.columns().is_empty() {
+        return Err(AppError::invalid("Only queries that return rows can be exported"));
+    }
+    let arrow_types = |ty: &tokio_postgres::types::Type| {
+        crate::decode::has_decoder(ty) || crate::arrow_export::has_arrow_type(ty)
+    };
+    if let Some(rewritten) =
+        crate::postgres::text_fallback_query_where(statement, prepared.columns(), arrow_types)
+    {
+        prepared = tx
+            .prepare(&rewritten)
+            .await
+            .map_err(|e| AppError::from(e).without_position())?;
+    }
+    let rows = tx.query(&prepared, &[]).await?;
+    tx.rollback().await?;
+
+    let row_count = rows.len();
+    tokio::task::spawn_blocking(move || {
+        let batch = crate::arrow_export::record_batch(prepared.columns(), &rows)?;
+        crate::arrow_export::write_ipc_file(&output_path, &batch)?;
+        Ok::<_, arrow::error::ArrowError>(output_path)
+    })
+    .await
+    .map_err(|e| AppError::internal(e.to_string()))?
+    .map_err(|e| AppError::io(format!("Failed to write Arrow file: {}", e)))
+    .map(|output_path| QueryResult {
+        columns: vec![],
+        rows: vec![],
+        rows_affected: Some(row_count),
+        message: Some(format!("Exported {} rows to {}", row_count, output_path)),
+    })
+}
+
+/// A decoded value as a CSV field: NULL is empty, strings as-is, anything
+/// else (numbers, booleans, JSON) in its JSON text form.
+fn json_csv_field(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(text) => csv_field(text),
+        other => csv_field(&other.to_string()),
+    }
+}
+
+/// Stream a query's result as CSV through `csv_chunk` events, for when the
+/// result shouldn't be written to the backend's disk: the frontend
+/// assembles the chunks (header line first) into a download. Rows are read
+/// from a cursor in a read-only transaction, `batch_size` at a time, one
+/// chunk per batch. The last event has `done` set and the total row count.
+/// `stream_id` tags the events and doubles as the `cancel_query` id.
+#[command]
+pub async fn stream_query_csv(
+    window: Window,
+    server_id: String,
+    database_name: Option<String>,
+    sql: String,
+    batch_size: Option<i64>,
+    stream_id: String,
+) -> Result<QueryResult, AppError> {
+    #[derive(Serialize, Clone)]
+    struct CsvChunk {
+        #[serde(rename = "streamId")]
+        stream_id: String,
+        data: String,
+        /// Rows sent so far, including this chunk's
+        rows: u64,
+        done: bool,
+    }
+
+    let statement = sql.trim().trim_end_matches(';');
+    if statement.is_empty() {
+        return Err(AppError::invalid("Query is required"));
+    }
+    let batch_size = batch_size.unwrap_or(DEFAULT_FETCH_BATCH_SIZE);
+    if !(1..=MAX_FETCH_BATCH_SIZE).contains(&batch_size) {
+        return Err(AppError::invalid(format!(
+            "Batch size must be between 1 and {}",
+            MAX_FETCH_BATCH_SIZE
+        )));
+    }
+
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let mut client = get_client(&server, &password, &target_database).await?;
+    crate::postgres::register_cancel_token(&stream_id, client.cancel_token()).await;
+
+    let emit_chunk = |data: String, rows: u64, done: bool| {
+        let chunk = CsvChunk {
+            stream_id: stream_id.clone(),
+            data,
+            rows,
+            done,
+        };
+        if let Err(e) = window.emit("csv_chunk", chunk) {
+            eprintln!("Failed to emit csv_chunk: {}", e);
+        }
+    };
+
+    let stream = async {
+        let tx = client.read_only_transaction().await?;
+        let prepared = tx.prepare(statement).await?;
+        if prepared.columns().is_empty() {
+            return Err(AppError::invalid("Only queries that return rows can be streamed"));
+        }
+        let header: Vec<String> = prepared.columns().iter().map(|col| csv_field(col.name())).collect();
+        let cursor_sql =
+            crate::postgres::text_fallback_query_where(statement, prepared.columns(), crate::decode::has_decoder)
+                .unwrap_or_else(|| statement.to_string());
+
+        // The cursor wraps the statement, so a position would be off
+        tx.batch_execute(&format!("DECLARE fastdb_csv_stream NO SCROLL CURSOR FOR {}", cursor_sql))
+            .await
+            .map_err(|e| AppError::from(e).without_position())?;
+        let fetch = tx
+            .prepare(&format!("FETCH {} FROM fastdb_csv_stream", batch_size))
+            .await?;
+
+        emit_chunk(format!("{}\n", header.join(",")), 0, false);
+        let mut rows_sent: u64 = 0;
+        loop {
+            let rows = tx.query(&fetch, &[]).await?;
+            if rows.is_empty() {
+                break;
+            }
+            let mut data = String::new();
+            for row in &rows {
+                let fields: Vec<String> = (0..row.len())
+                    .map(|idx| json_csv_field(&crate::decode::column_to_json(row, idx)))
+                    .collect();
+                data.push_str(&fields.join(","));
+                data.push('\n');
+            }
+            rows_sent += rows.len() as u64;
+            emit_chunk(data, rows_sent, false);
+        }
+        tx.rollback().await?;
+        emit_chunk(String::new(), rows_sent, true);
+        Ok::<_, AppError>(rows_sent)
+    }
+    .await;
+
+    crate::postgres::remove_cancel_token(&stream_id).await;
+    let rows_sent = stream?;
+
+    Ok(QueryResult {
+        columns: vec![],
+        rows: vec![],
+        rows_affected: Some(rows_sent as usize),
+        message: Some(format!("Streamed {} rows as CSV", rows_sent)),
+    })
+}
+
+/// Tokens of a statement for DDL parsing: words lowercased, quoted
+/// identifiers unquoted (flagged so they never match a keyword), and any
+/// other character on its own. Comments are skipped and string literals
+/// become a single quoted token.
+fn ddl_tokens(sql: &str) -> Vec<(String, bool)> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_' || c == '$';
+    let mut tokens = Vec::new();
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '-' if chars.peek() == Some(&'-') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            '"' | '\'' => {
+                let mut text = String::new();
+                while let Some(next) = chars.next() {
+                    if next == c {
+                        if chars.peek() == Some(&c) {
+                            chars.next();
+                        } else {
+                            break;
                         }
                     }
-                    statement.clear();
-                    continue;
+                    text.push(next);
+                }
+                tokens.push((text, true));
+            }
+            c if is_word(c) => {
+                let mut word = c.to_lowercase().to_string();
+                while let Some(&next) = chars.peek() {
+                    if !is_word(next) {
+                        break;
+                    }
+                    word.extend(next.to_lowercase());
+                    chars.next();
+                }
+                tokens.push((word, false));
+            }
+            other => tokens.push((other.to_string(), false)),
+        }
+    }
+    tokens
+}
+
+/// Tables whose cached definition a CREATE, DROP or ALTER TABLE statement
+/// changes, as `(schema, table)` with `None` for an unqualified name. A
+/// rename or `SET SCHEMA` yields both the old and the new name. Temporary
+/// tables and anything else give an empty list. `None` means table DDL
+/// shares the SQL with other statements, which only a full refresh covers.
+fn ddl_affected_tables(sql: &str) -> Option<Vec<(Option<String>, String)>> {
+    let tokens = ddl_tokens(sql);
+    let mut statements: Vec<_> = tokens
+        .split(|(text, quoted)| !quoted && text == ";")
+        .filter(|statement| !statement.is_empty())
+        .map(statement_affected_tables)
+        .collect();
+    if statements.len() > 1 {
+        return statements.iter().all(Vec::is_empty).then(Vec::new);
+    }
+    Some(statements.pop().unwrap_or_default())
+}
+
+/// `ddl_affected_tables` for the tokens of a single statement.
+fn statement_affected_tables(tokens: &[(String, bool)]) -> Vec<(Option<String>, String)> {
+    let keyword = |idx: usize, word: &str| tokens.get(idx).is_some_and(|(text, quoted)| !quoted && text == word);
+    let ident = |idx: usize| {
+        tokens.get(idx).and_then(|(text, quoted)| {
+            let is_name = *quoted || text.chars().next().is_some_and(|c| c.is_alphanumeric() || c == '_');
+            is_name.then(|| text.clone())
+        })
+    };
+    // `name` or `schema.name` starting at `idx`, and the index after it
+    let name_at = |idx: usize| -> Option<(Option<String>, String, usize)> {
+        let first = ident(idx)?;
+        if keyword(idx + 1, ".") {
+            Some((Some(first), ident(idx + 2)?, idx + 3))
+        } else {
+            Some((None, first, idx + 1))
+        }
+    };
+
+    let mut affected = Vec::new();
+    if keyword(0, "create") {
+        let mut idx = 1;
+        if keyword(idx, "unlogged") {
+            idx += 1;
+        }
+        if !keyword(idx, "table") {
+            return affected;
+        }
+        idx += 1;
+        if keyword(idx, "if") && keyword(idx + 1, "not") && keyword(idx + 2, "exists") {
+            idx += 3;
+        }
+        if let Some((schema, table, _)) = name_at(idx) {
+            affected.push((schema, table));
+        }
+    } else if keyword(0, "drop") && keyword(1, "table") {
+        let mut idx = 2;
+        if keyword(idx, "if") && keyword(idx + 1, "exists") {
+            idx += 2;
+        }
+        while let Some((schema, table, next)) = name_at(idx) {
+            affected.push((schema, table));
+            if !keyword(next, ",") {
+                break;
+            }
+            idx = next + 1;
+        }
+    } else if keyword(0, "alter") && keyword(1, "table") {
+        let mut idx = 2;
+        if keyword(idx, "if") && keyword(idx + 1, "exists") {
+            idx += 2;
+        }
+        if keyword(idx, "only") {
+            idx += 1;
+        }
+        let Some((schema, table, next)) = name_at(idx) else {
+            return affected;
+        };
+        if keyword(next, "rename") && keyword(next + 1, "to") {
+            if let Some(new_name) = ident(next + 2) {
+                affected.push((schema.clone(), new_name));
+            }
+        } else if keyword(next, "set") && keyword(next + 1, "schema") {
+            if let Some(new_schema) = ident(next + 2) {
+                affected.push((Some(new_schema), table.clone()));
+            }
+        }
+        affected.insert(0, (schema, table));
+    }
+    affected
+}
+
+/// Schema-qualify the names from `ddl_affected_tables` the way the server
+/// resolved them. With the editor's schema set, the statement ran with only
+/// that schema on its search_path. Otherwise an unqualified name is the table
+/// it now resolves to, or for one dropped or renamed away, the first cached
+/// copy along the session's search_path; failing both it's a new table in
+/// `current_schema()`. Names that can't be resolved are left out.
+async fn qualify_ddl_tables(
+    server: &db::Server,
+    password: &str,
+    database_name: &str,
+    schema_name: Option<&str>,
+    tables: Vec<(Option<String>, String)>,
+) -> Vec<(String, String)> {
+    let unqualified: Vec<String> = tables
+        .iter()
+        .filter(|(schema, _)| schema.is_none())
+        .map(|(_, table)| table.clone())
+        .collect();
+    let lookup = async {
+        if unqualified.is_empty() || schema_name.is_some() {
+            return Ok((Vec::new(), Vec::new()));
+        }
+        let client = get_client(server, password, database_name).await?;
+        let row = client
+            .query_one(
+                "SELECT current_schemas(false), \
+                        array(SELECT n.nspname \
+                              FROM unnest($1::text[]) WITH ORDINALITY AS t(name, i) \
+                              LEFT JOIN pg_class c ON c.oid = to_regclass(quote_ident(t.name)) \
+                              LEFT JOIN pg_namespace n ON n.oid = c.relnamespace \
+                              ORDER BY t.i)",
+                &[&unqualified],
+            )
+            .await?;
+        Ok::<_, AppError>((row.get::<_, Vec<String>>(0), row.get::<_, Vec<Option<String>>>(1)))
+    };
+    let (search_path, found) = lookup.await.unwrap_or_else(|e| {
+        eprintln!("Failed to resolve tables changed by DDL: {}", e);
+        (Vec::new(), Vec::new())
+    });
+
+    let mut found = found.into_iter();
+    tables
+        .into_iter()
+        .filter_map(|(schema, table)| {
+            if let Some(schema) = schema.as_deref().or(schema_name) {
+                return Some((schema.to_string(), table));
+            }
+            let schema = found.next().flatten().or_else(|| {
+                search_path
+                    .iter()
+                    .find(|schema| !cached_table_names(&server.id, database_name, schema, &table).tables.is_empty())
+                    .or(search_path.first())
+                    .cloned()
+            })?;
+            Some((schema, table))
+        })
+        .collect()
+}
+
+/// Names the cached copy of one table contributes to autocomplete; empty
+/// when it isn't cached.
+fn cached_table_names(server_id: &str, database_name: &str, schema_name: &str, table_name: &str) -> db::AutocompleteItems {
+    let lookup = || -> Result<db::AutocompleteItems, rusqlite::Error> {
+        let mut items = db::AutocompleteItems::default();
+        let Some(schema_id) = db::find_schema_id(server_id, database_name, schema_name)? else {
+            return Ok(items);
+        };
+        if let Some(table) = db::get_tables(&schema_id)?.into_iter().find(|t| t.name == table_name) {
+            items.columns = db::get_columns(&table.id)?.into_iter().map(|c| c.name).collect();
+            items.indexes = db::get_indexes(&table.id)?.into_iter().map(|i| i.name).collect();
+            items.tables.push(table.name);
+        }
+        Ok(items)
+    };
+    lookup().unwrap_or_else(|e| {
+        eprintln!("Failed to read cached table {}.{}: {}", schema_name, table_name, e);
+        db::AutocompleteItems::default()
+    })
+}
+
+/// Bring the caches up to date after table DDL without a full rescan: each
+/// affected table is re-read (which drops it from the cache if it no longer
+/// exists), autocomplete names are patched to match, and
+/// `autocomplete_updated` is emitted. Failures are logged; the DDL itself
+/// already succeeded.
+async fn apply_table_ddl(
+    window: &Window,
+    server: &db::Server,
+    password: &str,
+    database_name: &str,
+    tables: &[(String, String)],
+) {
+    #[derive(Serialize, Clone)]
+    struct AutocompleteUpdate {
+        #[serde(rename = "serverId")]
+        server_id: String,
+        items: db::AutocompleteItems,
+    }
+
+    let mut added = db::AutocompleteItems::default();
+    let mut removed = db::AutocompleteItems::default();
+    for (schema, table) in tables {
+        let before = cached_table_names(&server.id, database_name, schema, table);
+        refresh_table_and_emit(window, server, password, database_name, schema, table).await;
+        let after = cached_table_names(&server.id, database_name, schema, table);
+
+        removed.tables.extend(before.tables);
+        removed.columns.extend(before.columns);
+        removed.indexes.extend(before.indexes);
+        added.tables.extend(after.tables);
+        added.columns.extend(after.columns);
+        added.indexes.extend(after.indexes);
+    }
+
+    if let Err(e) = db::update_autocomplete_items(&server.id, &added, &removed) {
+        eprintln!("Failed to update autocomplete items: {}", e);
+        return;
+    }
+    match db::get_autocomplete_items(&server.id) {
+        Ok(items) => {
+            let update = AutocompleteUpdate {
+                server_id: server.id.clone(),
+                items,
+            };
+            if let Err(e) = window.emit("autocomplete_updated", update) {
+                eprintln!("Failed to emit autocomplete_updated: {}", e);
+            }
+        }
+        Err(e) => eprintln!("Failed to read autocomplete items: {}", e),
+    }
+}
+
+/// Refresh a single cached table after DDL and let the tree know about it.
+/// Failures are logged rather than returned: the DDL itself already succeeded.
+async fn refresh_table_and_emit(
+    window: &Window,
+    server: &db::Server,
+    password: &str,
+    database_name: &str,
+    schema_name: &str,
+    table_name: &str,
+) {
+    #[derive(Serialize, Clone)]
+    struct TableUpdate {
+        #[serde(rename = "serverId")]
+        server_id: String,
+        #[serde(rename = "databaseName")]
+        database_name: String,
+        #[serde(rename = "schemaName")]
+        schema_name: String,
+        #[serde(rename = "tableName")]
+        table_name: String,
+        table: Option<db::Table>,
+    }
+
+    let table = match crate::schema::refresh_table_for_server(
+        server,
+        password,
+        database_name,
+        schema_name,
+        table_name,
+    )
+    .await
+    {
+        Ok(table) => table,
+        Err(e) => {
+            eprintln!("Failed to refresh table {}.{}: {}", schema_name, table_name, e);
+            return;
+        }
+    };
+
+    let update = TableUpdate {
+        server_id: server.id.clone(),
+        database_name: database_name.to_string(),
+        schema_name: schema_name.to_string(),
+        table_name: table_name.to_string(),
+        table,
+    };
+    if let Err(e) = window.emit("table_updated", update) {
+        eprintln!("Failed to emit table_updated: {}", e);
+    }
+}
+
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub async fn add_column(
+    window: Window,
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: String,
+    table_name: String,
+    column_name: String,
+    data_type: String,
+    nullable: bool,
+    default: Option<String>,
+) -> Result<QueryResult, AppError> {
+    if column_name.trim().is_empty() {
+        return Err(AppError::invalid("Column name is required"));
+    }
+    if data_type.trim().is_empty() {
+        return Err(AppError::invalid("Data type is required"));
+    }
+
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let mut sql = format!(
+        "ALTER TABLE {}.{} ADD COLUMN {} {}",
+        quote_ident(&schema_name),
+        quote_ident(&table_name),
+        quote_ident(&column_name),
+        data_type.trim()
+    );
+    if !nullable {
+        sql.push_str(" NOT NULL");
+    }
+    if let Some(expr) = default.as_deref().map(str::trim).filter(|expr| !expr.is_empty()) {
+        sql.push_str(&format!(" DEFAULT {}", expr));
+    }
+
+    let client = get_client(&server, &password, &target_database).await?;
+    client.batch_execute(&sql).await?;
+    drop(client);
+
+    refresh_table_and_emit(
+        &window,
+        &server,
+        &password,
+        &target_database,
+        &schema_name,
+        &table_name,
+    )
+    .await;
+
+    Ok(QueryResult {
+        columns: vec![],
+        rows: vec![],
+        rows_affected: None,
+        message: Some(format!("Column {} added to {}.{}", column_name, schema_name, table_name)),
+    })
+}
+
+/// Copy a table's definition (columns, defaults, constraints, indexes) to a
+/// new table in the same schema, optionally with its rows.
+#[command]
+pub async fn clone_table_structure(
+    window: Window,
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: String,
+    source_table: String,
+    new_table: String,
+    include_data: bool,
+) -> Result<QueryResult, AppError> {
+    validate_identifier(&source_table)?;
+    validate_identifier(&new_table)?;
+
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let schema_q = quote_ident(&schema_name);
+    let source_q = format!("{}.{}", schema_q, quote_ident(&source_table));
+    let new_q = format!("{}.{}", schema_q, quote_ident(&new_table));
+
+    let mut client = get_client(&server, &password, &target_database).await?;
+    let tx = client.transaction().await?;
+    tx.batch_execute(&format!("CREATE TABLE {} (LIKE {} INCLUDING ALL)", new_q, source_q))
+        .await
+        .map_err(|e| AppError::from(e).context(format!("Failed to create {}", new_table)))?;
+
+    let copied = if include_data {
+        tx.execute(&format!("INSERT INTO {} SELECT * FROM {}", new_q, source_q), &[])
+            .await
+            .map_err(|e| AppError::from(e).context(format!("Failed to copy rows into {}", new_table)))?
+    } else {
+        0
+    };
+    tx.commit().await?;
+    drop(client);
+
+    refresh_table_and_emit(
+        &window,
+        &server,
+        &password,
+        &target_database,
+        &schema_name,
+        &new_table,
+    )
+    .await;
+
+    Ok(QueryResult {
+        columns: vec![],
+        rows: vec![],
+        rows_affected: Some(copied as usize),
+        message: Some(if include_data {
+            format!("Created {}.{} with {} rows", schema_name, new_table, copied)
+        } else {
+            format!("Created {}.{}", schema_name, new_table)
+        }),
+    })
+}
+
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub async fn rename_object(
+    window: Window,
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: String,
+    object_type: String,
+    old_name: String,
+    new_name: String,
+    parent_table: Option<String>,
+) -> Result<QueryResult, AppError> {
+    validate_identifier(&new_name)?;
+
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+    let client = get_client(&server, &password, &target_database).await?;
+
+    let schema_q = quote_ident(&schema_name);
+    let parent_table = parent_table.filter(|name| !name.trim().is_empty());
+
+    // Work out the statement and which cached tables need refreshing afterwards
+    let (sql, tables_to_refresh) = match object_type.to_lowercase().as_str() {
+        "table" => (
+            format!(
+                "ALTER TABLE {}.{} RENAME TO {}",
+                schema_q,
+                quote_ident(&old_name),
+                quote_ident(&new_name)
+            ),
+            vec![old_name.clone(), new_name.clone()],
+        ),
+        "column" => {
+            let table = parent_table
+                .ok_or_else(|| AppError::invalid("Renaming a column requires its table"))?;
+            (
+                format!(
+                    "ALTER TABLE {}.{} RENAME COLUMN {} TO {}",
+                    schema_q,
+                    quote_ident(&table),
+                    quote_ident(&old_name),
+                    quote_ident(&new_name)
+                ),
+                vec![table],
+            )
+        }
+        "index" => {
+            let table = match parent_table {
+                Some(table) => Some(table),
+                None => client
+                    .query_opt(
+                        "SELECT tablename FROM pg_indexes WHERE schemaname = $1 AND indexname = $2",
+                        &[&schema_name, &old_name],
+                    )
+                    .await?
+                    .map(|row| row.get::<_, String>(0)),
+            };
+            (
+                format!(
+                    "ALTER INDEX {}.{} RENAME TO {}",
+                    schema_q,
+                    quote_ident(&old_name),
+                    quote_ident(&new_name)
+                ),
+                table.into_iter().collect(),
+            )
+        }
+        other => {
+            return Err(AppError::invalid(format!(
+                "Unsupported object type '{}': expected table, column or index",
+                other
+            )))
+        }
+    };
+
+    client.batch_execute(&sql).await?;
+    drop(client);
+
+    for table in &tables_to_refresh {
+        refresh_table_and_emit(&window, &server, &password, &target_database, &schema_name, table)
+            .await;
+    }
+
+    Ok(QueryResult {
+        columns: vec![],
+        rows: vec![],
+        rows_affected: None,
+        message: Some(format!("Renamed {} {} to {}", object_type.to_lowercase(), old_name, new_name)),
+    })
+}
+
+/// Canonical spelling of a type name so aliases compare equal. Modifiers such
+/// as `(255)` are dropped because the cache stores `information_schema`
+/// `data_type`, which omits them.
+fn canonical_type_name(data_type: &str) -> String {
+    let lowered = data_type.trim().to_lowercase();
+    let base = match lowered.find('(') {
+        Some(paren) => {
+            let suffix = lowered[paren..].find(')').map(|end| &lowered[paren + end + 1..]).unwrap_or("");
+            format!("{}{}", &lowered[..paren], suffix)
+        }
+        None => lowered,
+    };
+    let base = base.split_whitespace().collect::<Vec<_>>().join(" ");
+    match base.as_str() {
+        "int" | "int4" => "integer".to_string(),
+        "int2" => "smallint".to_string(),
+        "int8" => "bigint".to_string(),
+        "float4" => "real".to_string(),
+        "float8" | "float" => "double precision".to_string(),
+        "bool" => "boolean".to_string(),
+        "varchar" => "character varying".to_string(),
+        "char" | "bpchar" => "character".to_string(),
+        "decimal" => "numeric".to_string(),
+        "timestamp" => "timestamp without time zone".to_string(),
+        "timestamptz" => "timestamp with time zone".to_string(),
+        "time" => "time without time zone".to_string(),
+        "timetz" => "time with time zone".to_string(),
+        _ => base,
+    }
+}
+
+/// Whether a cached `information_schema` type already satisfies `desired`.
+fn type_matches(cached: &str, desired: &str) -> bool {
+    match cached {
+        // The cache doesn't record element or enum/domain names, so only the
+        // shape can be checked
+        "ARRAY" => desired.trim().ends_with("[]"),
+        "USER-DEFINED" => true,
+        _ => canonical_type_name(cached) == canonical_type_name(desired),
+    }
+}
+
+/// Build (but don't run) the ALTER TABLE statements that reconcile the cached
+/// columns of a table with `desired_columns`. Columns present in the cache but
+/// absent from the spec are dropped. Type changes are flagged as potentially
+/// lossy. Refresh the schema first if the table changed outside FastDB.
+#[command]
+pub async fn generate_alter(
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: String,
+    table_name: String,
+    desired_columns: Vec<DesiredColumn>,
+) -> Result<String, AppError> {
+    for column in &desired_columns {
+        validate_identifier(&column.name)?;
+        if column.data_type.trim().is_empty() {
+            return Err(AppError::invalid(format!("Data type is required for {}", column.name)));
+        }
+    }
+
+    let server = load_server(&server_id)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let schema_id = db::find_schema_id(&server.id, &target_database, &schema_name)?
+        .ok_or_else(|| AppError::not_found(format!("Schema {} is not cached; refresh the schema first", schema_name)))?;
+    let table = db::get_tables(&schema_id)?
+        .into_iter()
+        .find(|table| table.name == table_name)
+        .ok_or_else(|| AppError::not_found(format!("Table {} is not cached; refresh the schema first", table_name)))?;
+    let current = db::get_columns(&table.id)?;
+
+    let target = format!("{}.{}", quote_ident(&schema_name), quote_ident(&table_name));
+    let mut statements = Vec::new();
+
+    for desired in &desired_columns {
+        let column_q = quote_ident(&desired.name);
+        let data_type = desired.data_type.trim();
+
+        match current.iter().find(|column| column.name == desired.name) {
+            None => {
+                statements.push(format!(
+                    "ALTER TABLE {} ADD COLUMN {} {}{};",
+                    target,
+                    column_q,
+                    data_type,
+                    if desired.nullable { "" } else { " NOT NULL" }
+                ));
+            }
+            Some(column) => {
+                if !type_matches(&column.data_type, data_type) {
+                    statements.push(format!(
+                        "-- WARNING: changing {} from {} to {} may lose data\nALTER TABLE {} ALTER COLUMN {} TYPE {} USING {}::{};",
+                        desired.name, column.data_type, data_type, target, column_q, data_type, column_q, data_type
+                    ));
                 }
+                let is_nullable = column.nullable != 0;
+                if is_nullable && !desired.nullable {
+                    statements.push(format!("ALTER TABLE {} ALTER COLUMN {} SET NOT NULL;", target, column_q));
+                } else if !is_nullable && desired.nullable {
+                    statements.push(format!("ALTER TABLE {} ALTER COLUMN {} DROP NOT NULL;", target, column_q));
+                }
+            }
+        }
+    }
 
-                statement.push(ch);
+    for column in &current {
+        if !desired_columns.iter().any(|desired| desired.name == column.name) {
+            statements.push(format!(
+                "ALTER TABLE {} DROP COLUMN {};",
+                target,
+                quote_ident(&column.name)
+            ));
+        }
+    }
+
+    Ok(statements.join("\n"))
+}
+
+fn parse_isolation_level(level: &str) -> Result<tokio_postgres::IsolationLevel, AppError> {
+    match level.trim().to_lowercase().replace('_', " ").as_str() {
+        "read committed" => Ok(tokio_postgres::IsolationLevel::ReadCommitted),
+        "repeatable read" => Ok(tokio_postgres::IsolationLevel::RepeatableRead),
+        "serializable" => Ok(tokio_postgres::IsolationLevel::Serializable),
+        other => Err(AppError::invalid(format!(
+            "Unsupported isolation level '{}': expected read committed, repeatable read or serializable",
+            other
+        ))),
+    }
+}
+
+/// Validate a `client_min_messages` level, returning it in canonical form
+/// so it can be interpolated into `SET`.
+fn parse_message_level(level: &str) -> Result<&'static str, AppError> {
+    const LEVELS: [&str; 10] = [
+        "debug5", "debug4", "debug3", "debug2", "debug1", "debug", "log", "notice", "warning", "error",
+    ];
+    let level = level.trim().to_lowercase();
+    LEVELS.into_iter().find(|known| *known == level).ok_or_else(|| {
+        AppError::invalid(format!(
+            "Unsupported message level '{}': expected debug5-debug1, log, notice, warning or error",
+            level
+        ))
+    })
+}
+
+/// psql-style command tag for session statements (`SET`, `RESET`,
+/// `DISCARD ALL`, ...) given a normalized head; `None` for anything else.
+fn session_command_tag(head: &str) -> Option<String> {
+    let mut words = head.split_whitespace();
+    match words.next()? {
+        "set" => Some("SET".to_string()),
+        "reset" => Some("RESET".to_string()),
+        "discard" => Some(match words.next() {
+            Some(target) => format!("DISCARD {}", target.trim_end_matches(';').to_uppercase()),
+            None => "DISCARD".to_string(),
+        }),
+        _ => None,
+    }
+}
+
+fn normalize_sql_head(sql: &str) -> String {
+    let mut s = sql.trim_start().to_string();
+
+    loop {
+        let trimmed = s.trim_start();
+        if trimmed.starts_with("--") {
+            if let Some(pos) = trimmed.find('\n') {
+                s = trimmed[pos + 1..].to_string();
+                continue;
+            }
+            return "".to_string();
+        }
+        if trimmed.starts_with("/*") {
+            if let Some(end) = trimmed.find("*/") {
+                s = trimmed[end + 2..].to_string();
+                continue;
             }
+            return "".to_string();
         }
+        return trimmed.to_lowercase();
+    }
+}
+
+/// Re-run the last query that failed on this server on fresh connections.
+/// The server's pools are dropped first so the retry can't reuse a
+/// connection that was broken when the query failed.
+#[command]
+pub async fn retry_last_query(window: Window, server_id: String) -> Result<ExecuteQueryResult, AppError> {
+    let attempt = crate::postgres::last_attempt(&server_id)
+        .await
+        .ok_or_else(|| AppError::not_found("No failed query to retry"))?;
+
+    crate::postgres::drop_pools(&server_id).await;
+
+    execute_query(
+        window,
+        server_id,
+        attempt.sql,
+        None,
+        attempt.schema_name,
+        Some(attempt.database),
+        None,
+        None,
+        None,
+        // Only queries that got past the expensive-query check are remembered
+        Some(true),
+        None,
+    )
+    .await
+}
+
+#[command]
+pub async fn cancel_query(query_id: String) -> Result<(), AppError> {
+    crate::postgres::cancel_query(&query_id)
+        .await
+        .map_err(AppError::from)
+}
+
+/// List the connection pools FastDB is currently holding open
+#[command]
+pub async fn list_active_pools() -> Result<Vec<ActivePool>, AppError> {
+    let pools = crate::postgres::list_pools().await;
+    Ok(pools
+        .into_iter()
+        .map(|pool| ActivePool {
+            server_id: pool.server_id,
+            database: pool.database,
+            role: pool.role,
+            replica: pool.replica,
+            size: pool.status.size as i64,
+            available: pool.status.available as i64,
+            max_size: pool.status.max_size as i64,
+            waiting: pool.status.waiting as i64,
+        })
+        .collect())
+}
+
+/// Close all pools for a server to free their idle connections.
+/// The next command against the server transparently creates a new pool.
+#[command]
+pub async fn drop_pool(server_id: String) -> Result<usize, AppError> {
+    Ok(crate::postgres::drop_pools(&server_id).await)
+}
+
+#[command]
+pub async fn get_schema_tree(server_id: String) -> Result<Vec<db::Schema>, AppError> {
+    db::get_schemas(&server_id).map_err(AppError::from)
+}
+
+/// Cached schemas whose name contains `pattern`, at most `limit` (default 100).
+#[command]
+pub async fn search_schemas(
+    server_id: String,
+    pattern: String,
+    limit: Option<usize>,
+) -> Result<Vec<db::Schema>, AppError> {
+    db::search_schemas(&server_id, &pattern, limit.unwrap_or(100)).map_err(AppError::from)
+}
+
+#[command]
+pub async fn refresh_schema(
+    window: Window,
+    server_id: String,
+    force: Option<bool>,
+) -> Result<(), AppError> {
+    // Several DDL statements in a row each trigger a refresh; re-emit the
+    // cached schema instead of rescanning if one just finished.
+    let debounced = !force.unwrap_or(false)
+        && crate::schema::refreshed_within(&server_id, crate::schema::REFRESH_DEBOUNCE_WINDOW);
+
+    if !debounced {
+        let server = load_server(&server_id)?;
+        let password = server_password(&server)?;
+
+        crate::schema::refresh_schema_for_server(&server, &password)
+            .await?;
+    }
+
+    // Emit updated schema
+    let updated_schemas = db::get_schemas(&server_id)?;
+
+    #[derive(Serialize, Clone)]
+    struct SchemaUpdate {
+        #[serde(rename = "serverId")]
+        server_id: String,
+        schemas: Vec<db::Schema>,
+    }
+
+    window
+        .emit(
+            "schema_updated",
+            SchemaUpdate {
+                server_id: server_id.clone(),
+                schemas: updated_schemas,
+            },
+        )?;
+
+    Ok(())
+}
+
+#[command]
+pub async fn get_query_history(
+    server_id: String,
+    executed_by: Option<String>,
+) -> Result<Vec<db::QueryHistory>, AppError> {
+    db::get_query_history(&server_id, 100, executed_by.as_deref()).map_err(AppError::from)
+}
+
+#[command]
+pub async fn get_tables(schema_id: String) -> Result<Vec<db::Table>, AppError> {
+    db::get_tables(&schema_id).map_err(AppError::from)
+}
+
+#[command]
+pub async fn get_views(schema_id: String) -> Result<Vec<db::View>, AppError> {
+    db::get_views(&schema_id).map_err(AppError::from)
+}
+
+#[command]
+pub async fn get_columns(table_id: String) -> Result<Vec<db::Column>, AppError> {
+    db::get_columns(&table_id).map_err(AppError::from)
+}
+
+#[command]
+pub async fn get_indexes(table_id: String) -> Result<Vec<db::Index>, AppError> {
+    let cached = db::get_indexes(&table_id)?;
+    if !cached.is_empty() {
+        return Ok(cached);
+    }
+
+    let context = db::get_table_context(&table_id)?;
+    let Some((table_name, schema_name, server_id, database_name)) = context else {
+        return Ok(vec![]);
+    };
+
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+
+    let target_database = if database_name.is_empty() {
+        server.database.clone()
+    } else {
+        database_name
+    };
+
+    let client = get_client(&server, &password, &target_database).await?;
+    let rows = client
+        .query(
+            "SELECT indexname, indexdef FROM pg_indexes WHERE schemaname = $1 AND tablename = $2",
+            &[&schema_name, &table_name],
+        )
+        .await?;
+
+    let mut indexes: Vec<db::Index> = Vec::new();
+    for row in rows {
+        let name: String = row.get(0);
+        let definition: String = row.get(1);
+        indexes.push(db::Index {
+            id: Uuid::new_v4().to_string(),
+            table_id: table_id.clone(),
+            name,
+            definition,
+        });
     }
 
-    if in_copy {
-        if !copy_line_buffer.is_empty() {
-            let line = copy_line_buffer.trim_end_matches('\r');
-            if line == "\\." {
-                if let Some(mut sink) = copy_sink.take() {
-                    match sink.as_mut().finish().await {
-                        Ok(_) => {}
-                        Err(e) => {
-                            let detail = format_pg_error(&e);
-                            return Err(format!("Failed to finalize COPY: {}", detail));
-                        }
-                    }
-                }
-                in_copy = false;
-            }
-        }
+    db::replace_indexes_for_table(&table_id, &indexes)?;
+
+    Ok(indexes)
+}
+
+#[command]
+pub async fn get_primary_key_columns(
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: String,
+    table_name: String,
+) -> Result<Vec<String>, AppError> {
+    if schema_name.trim().is_empty() || table_name.trim().is_empty() {
+        return Ok(vec![]);
     }
 
-    if in_copy {
-        return Err("COPY data did not terminate with \\.".to_string());
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+
+    let target_database = resolve_database(&server, database_name);
+
+    let client = get_client(&server, &password, &target_database).await?;
+    let rows = client
+        .query(
+            "SELECT kcu.column_name\n             FROM information_schema.table_constraints tc\n             JOIN information_schema.key_column_usage kcu\n               ON tc.constraint_name = kcu.constraint_name\n              AND tc.table_schema = kcu.table_schema\n              AND tc.table_name = kcu.table_name\n             WHERE tc.constraint_type = 'PRIMARY KEY'\n               AND tc.table_schema = $1\n               AND tc.table_name = $2\n             ORDER BY kcu.ordinal_position",
+            &[&schema_name, &table_name],
+        )
+        .await?;
+
+    Ok(rows.iter().map(|row| row.get::<_, String>(0)).collect())
+}
+
+/// Check whether grid edits can target exactly one row. Uses the primary key
+/// when present, otherwise the first unique, non-partial index whose columns
+/// are all NOT NULL.
+#[command]
+pub async fn is_table_editable(
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: String,
+    table_name: String,
+) -> Result<TableEditability, AppError> {
+    let primary_key = get_primary_key_columns(
+        server_id.clone(),
+        database_name.clone(),
+        schema_name.clone(),
+        table_name.clone(),
+    )
+    .await?;
+
+    if !primary_key.is_empty() {
+        return Ok(TableEditability {
+            editable: true,
+            primary_key,
+            reason: None,
+        });
     }
 
-    if !statement.trim().is_empty() {
-        let trimmed = statement.trim();
-        let trimmed_lower = trimmed.to_lowercase();
-        if trimmed_lower.starts_with("copy") && trimmed_lower.contains("from stdin") {
-            return Err("COPY statement missing data section".to_string());
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let client = get_client(&server, &password, &target_database).await?;
+    let rows = client
+        .query(
+            "SELECT ic.relname,
+                    array_agg(a.attname::text ORDER BY k.ord) AS columns
+             FROM pg_index i
+             JOIN pg_class c ON c.oid = i.indrelid
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             JOIN pg_class ic ON ic.oid = i.indexrelid
+             CROSS JOIN LATERAL unnest(i.indkey) WITH ORDINALITY AS k(attnum, ord)
+             JOIN pg_attribute a ON a.attrelid = c.oid AND a.attnum = k.attnum
+             WHERE n.nspname = $1 AND c.relname = $2
+               AND i.indisunique AND i.indisvalid
+               AND i.indpred IS NULL
+               AND i.indexprs IS NULL
+             GROUP BY ic.relname
+             HAVING bool_and(a.attnotnull)
+             ORDER BY count(*), ic.relname
+             LIMIT 1",
+            &[&schema_name, &table_name],
+        )
+        .await
+        .map_err(|e| AppError::from(e).context(format!("Failed to read indexes for {}", table_name)))?;
+
+    Ok(match rows.first() {
+        Some(row) => {
+            let index_name: String = row.get(0);
+            TableEditability {
+                editable: true,
+                primary_key: row.get(1),
+                reason: Some(format!(
+                    "No primary key; using unique index {} to identify rows",
+                    index_name
+                )),
+            }
         }
+        None => TableEditability {
+            editable: false,
+            primary_key: vec![],
+            reason: Some(
+                "Table has no primary key or unique index on NOT NULL columns".to_string(),
+            ),
+        },
+    })
+}
 
-        if let Err(e) = client.batch_execute(trimmed).await {
-            let preview: String = trimmed.chars().take(500).collect();
-            return Err(format!(
-                "Failed executing SQL statement {}: {}\nStatement preview:\n{}",
-                statement_count + 1,
-                e,
-                preview
-            ));
+/// Preview a table's rows as another role so row-level security policies
+/// apply. The rows are read from a pool kept for that role, whose
+/// connections log in with it as their session role.
+#[command]
+pub async fn preview_table_as_role(
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: String,
+    table_name: String,
+    role: String,
+    limit: Option<i64>,
+) -> Result<QueryResult, AppError> {
+    validate_identifier(&schema_name)?;
+    validate_identifier(&table_name)?;
+    validate_identifier(&role)?;
+    let limit = limit.unwrap_or(100).clamp(1, 10_000);
+
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let role_error = |e: AppError| match e {
+        AppError::Sql { ref code, .. } if code == "22023" || code == "42704" => {
+            AppError::not_found(format!("Role '{}' does not exist", role))
         }
-        statement_count += 1;
-    }
+        other => other.context(format!("Failed to connect as role {}", role)),
+    };
+    // Connections in the role's own pool start as the role, so the shared
+    // pool's sessions are never switched. Behind a transaction pooler the
+    // transaction switches to it with SET LOCAL ROLE instead.
+    let mut client = get_client_for_role(&server, &password, &target_database, Some(&role))
+        .await
+        .map_err(role_error)?;
+    let tx = client.transaction().await.map_err(|e| role_error(e.into()))?;
+
+    let (rows, types) = crate::postgres::query_with_text_fallback(
+        &*tx,
+        &format!(
+            "SELECT * FROM {}.{} LIMIT $1",
+            quote_ident(&schema_name),
+            quote_ident(&table_name)
+        ),
+        &[&limit],
+    )
+    .await
+    .map_err(|e| AppError::from(e).context(format!("Failed to preview {} as {}", table_name, role)))?;
 
-    let message = Some(format!(
-        "Executed {} ({} statement{})",
-        file_name,
-        statement_count,
-        if statement_count == 1 { "" } else { "s" }
-    ));
+    // Read-only; nothing to commit
+    tx.rollback().await?;
 
     Ok(QueryResult {
-        columns: vec![],
-        rows: vec![],
-        rows_affected: None,
-        message,
+        columns: rows_to_columns(&rows, &types),
+        rows: rows.iter().map(row_to_json).collect(),
+        rows_affected: Some(rows.len()),
+        message: Some(format!("{} rows visible to role {}", rows.len(), role)),
     })
 }
 
+/// Rows shown by `preview_delete`; the count covers all of them regardless.
+const DELETE_PREVIEW_ROWS: i64 = 100;
+
+/// Show which rows `DELETE FROM schema.table WHERE <where_clause>` would
+/// remove, and how many, without deleting anything. Both queries run in a
+/// read-only transaction that is rolled back, so a clause with side effects
+/// (e.g. calling a volatile function) can't change data either.
 #[command]
-pub async fn export_schema_sql(
+pub async fn preview_delete(
     server_id: String,
+    database_name: Option<String>,
     schema_name: String,
-    include_data: bool,
-    output_path: String,
-) -> Result<QueryResult, String> {
-    let server = db::get_server_by_id(&server_id)
-        .map_err(|e| e.to_string())?
-        .ok_or("Server not found")?;
-
-    let password = credentials::retrieve_password(&server.credential_key)
-        .map_err(|e| format!("Failed to retrieve password: {}", e))?;
-
-    let pool = crate::postgres::get_or_create_pool(
-        &server.id,
-        &server.host,
-        server.port as u16,
-        &server.username,
-        &password,
-        &server.database,
+    table_name: String,
+    where_clause: String,
+) -> Result<DeletePreview, AppError> {
+    validate_identifier(&schema_name)?;
+    validate_identifier(&table_name)?;
+    let where_clause = where_clause.trim().trim_end_matches(';').trim();
+    if where_clause.is_empty() {
+        return Err(AppError::invalid(
+            "A WHERE clause is required; without one the DELETE removes every row",
+        ));
+    }
+
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let mut client = get_client(&server, &password, &target_database).await?;
+    let tx = client.read_only_transaction().await?;
+
+    let target = format!("{}.{}", quote_ident(&schema_name), quote_ident(&table_name));
+    let (rows, types) = crate::postgres::query_with_text_fallback(
+        &*tx,
+        &format!("SELECT * FROM {} WHERE ({}\n) LIMIT $1", target, where_clause),
+        &[&DELETE_PREVIEW_ROWS],
     )
     .await
-    .map_err(|e| format!("Failed to connect to database: {}", e))?;
-
-    let client = pool
-        .get()
+    .map_err(|e| AppError::from(e).context("Failed to preview matching rows"))?;
+    let matching_rows: i64 = tx
+        .query_one(&format!("SELECT count(*) FROM {} WHERE ({}\n)", target, where_clause), &[])
         .await
-        .map_err(|e| format!("Failed to get database client: {}", e))?;
+        .map_err(|e| AppError::from(e).context("Failed to count matching rows"))?
+        .get(0);
 
-    let mut file = File::create(&output_path)
-        .await
-        .map_err(|e| format!("Failed to create export file: {}", e))?;
+    tx.rollback().await?;
 
-    write_str(&mut file, "-- FastDB schema export\n").await?;
-    write_str(
-        &mut file,
-        &format!("-- Schema: {}\n\n", schema_name),
-    )
-    .await?;
+    Ok(DeletePreview {
+        columns: rows_to_columns(&rows, &types),
+        rows: rows.iter().map(row_to_json).collect(),
+        matching_rows,
+    })
+}
 
-    let schema_q = quote_ident(&schema_name);
-    write_str(
-        &mut file,
-        &format!("CREATE SCHEMA IF NOT EXISTS {};\n\n", schema_q),
-    )
-    .await?;
+/// Prepared statements and open cursors on one pooled connection, for
+/// diagnosing "prepared statement already exists" and similar pooling issues.
+/// Both views are per session, so this shows only the connection that happened
+/// to be checked out, identified by `pid`.
+#[command]
+pub async fn get_prepared_statements(
+    server_id: String,
+    database_name: Option<String>,
+) -> Result<SessionStatements, AppError> {
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let client = get_client(&server, &password, &target_database).await?;
+    let pid: i32 = client
+        .query_one("SELECT pg_backend_pid()", &[])
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read backend pid"))?
+        .get(0);
 
-    let sequences = client
+    let prepared_statements = client
         .query(
-            "SELECT sequence_name FROM information_schema.sequences WHERE sequence_schema = $1 ORDER BY sequence_name",
-            &[&schema_name],
+            "SELECT name, statement, (extract(epoch FROM prepare_time) * 1000)::int8, from_sql
+             FROM pg_prepared_statements
+             ORDER BY prepare_time",
+            &[],
         )
         .await
-        .map_err(|e| format!("Failed to read sequences: {}", e))?;
+        .map_err(|e| AppError::from(e).context("Failed to read prepared statements"))?
+        .iter()
+        .map(|row| PreparedStatementInfo {
+            name: row.get(0),
+            statement: row.get(1),
+            prepared_at: row.get(2),
+            from_sql: row.get(3),
+        })
+        .collect();
 
-    for row in &sequences {
-        let seq_name: String = row.get(0);
-        let seq_q = quote_ident(&seq_name);
-        write_str(
-            &mut file,
-            &format!("CREATE SEQUENCE {}.{};\n", schema_q, seq_q),
+    let cursors = client
+        .query(
+            "SELECT name, statement, is_holdable, is_scrollable
+             FROM pg_cursors
+             ORDER BY creation_time",
+            &[],
         )
-        .await?;
-    }
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read cursors"))?
+        .iter()
+        .map(|row| CursorInfo {
+            name: row.get(0),
+            statement: row.get(1),
+            is_holdable: row.get(2),
+            is_scrollable: row.get(3),
+        })
+        .collect();
 
-    if !sequences.is_empty() {
-        write_str(&mut file, "\n").await?;
-    }
+    Ok(SessionStatements {
+        pid,
+        prepared_statements,
+        cursors,
+    })
+}
 
-    let tables = client
-        .query(
-            "SELECT table_name FROM information_schema.tables WHERE table_schema = $1 AND table_type = 'BASE TABLE' ORDER BY table_name",
-            &[&schema_name],
+/// Effective session settings of a pooled connection. Each connection is an
+/// independent session, so this reflects the defaults a fresh session gets
+/// (server, database and role level settings), not changes made by a query.
+#[command]
+pub async fn get_session_settings(
+    server_id: String,
+    database_name: Option<String>,
+) -> Result<std::collections::HashMap<String, String>, AppError> {
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let client = get_client(&server, &password, &target_database).await?;
+    let row = client
+        .query_one(
+            "SELECT current_setting('search_path'),
+                    current_setting('statement_timeout'),
+                    current_setting('TimeZone'),
+                    current_setting('application_name'),
+                    current_user::text,
+                    session_user::text,
+                    current_database()::text",
+            &[],
         )
         .await
-        .map_err(|e| format!("Failed to read tables: {}", e))?;
+        .map_err(|e| AppError::from(e).context("Failed to read session settings"))?;
+
+    let names = [
+        "search_path",
+        "statement_timeout",
+        "timezone",
+        "application_name",
+        "role",
+        "user",
+        "database",
+    ];
+    Ok(names
+        .iter()
+        .enumerate()
+        .map(|(idx, name)| (name.to_string(), row.get::<_, String>(idx)))
+        .collect())
+}
 
-    for row in &tables {
-        let table_name: String = row.get(0);
-        let table_q = quote_ident(&table_name);
+/// Exact `count(*)` of a table, cancellable through `cancel_query` with
+/// `query_id`. With `timeout_ms`, a count that runs longer falls back to the
+/// planner's `reltuples` estimate instead of failing.
+#[command]
+pub async fn count_table_rows(
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: String,
+    table_name: String,
+    query_id: Option<String>,
+    timeout_ms: Option<i64>,
+) -> Result<RowCount, AppError> {
+    validate_identifier(&schema_name)?;
+    validate_identifier(&table_name)?;
+
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let mut client = get_client(&server, &password, &target_database).await?;
+    if let Some(id) = query_id.as_deref() {
+        crate::postgres::register_cancel_token(id, client.cancel_token()).await;
+    }
 
-        let columns = client
+    let count_sql = format!(
+        "SELECT count(*) FROM {}.{}",
+        quote_ident(&schema_name),
+        quote_ident(&table_name)
+    );
+    let exact = async {
+        let tx = client.transaction().await?;
+        if let Some(ms) = timeout_ms.filter(|ms| *ms > 0) {
+            tx.batch_execute(&format!("SET LOCAL statement_timeout = {}", ms)).await?;
+        }
+        let count: i64 = tx.query_one(&count_sql, &[]).await?.get(0);
+        tx.commit().await?;
+        Ok::<i64, tokio_postgres::Error>(count)
+    }
+    .await;
+
+    if let Some(id) = query_id.as_deref() {
+        crate::postgres::remove_cancel_token(id).await;
+    }
+
+    match exact {
+        Ok(count) => Ok(RowCount { count, exact: true }),
+        // 57014 is also a user cancel; only the timeout falls back
+        Err(e)
+            if e.as_db_error()
+                .map(|db_err| db_err.code().code() == "57014" && db_err.message().contains("statement timeout"))
+                .unwrap_or(false) =>
+        {
+            let row = client
+                .query_one(
+                    "SELECT GREATEST(c.reltuples, 0)::int8
+                     FROM pg_class c
+                     JOIN pg_namespace n ON n.oid = c.relnamespace
+                     WHERE n.nspname = $1 AND c.relname = $2",
+                    &[&schema_name, &table_name],
+                )
+                .await
+                .map_err(|e| AppError::from(e).context("Failed to read row estimate"))?;
+            Ok(RowCount {
+                count: row.get(0),
+                exact: false,
+            })
+        }
+        Err(e) => Err(AppError::from(e).context(format!("Failed to count rows in {}", table_name))),
+    }
+}
+
+/// Row count of every table in `schema_name` on one server's default
+/// database: exact `count(*)`s, or planner estimates when `exact` is false.
+async fn schema_row_counts(
+    server_id: &str,
+    schema_name: &str,
+    exact: bool,
+) -> Result<std::collections::BTreeMap<String, i64>, AppError> {
+    let server = load_server(server_id)?;
+    let password = server_password(&server)?;
+
+    let counts = async {
+        let client = get_client(&server, &password, &server.database).await?;
+        let rows = client
             .query(
-                "SELECT a.attname, pg_catalog.format_type(a.atttypid, a.atttypmod), a.attnotnull, pg_get_expr(ad.adbin, ad.adrelid)
-                 FROM pg_attribute a
-                 JOIN pg_class c ON a.attrelid = c.oid
-                 JOIN pg_namespace n ON c.relnamespace = n.oid
-                 LEFT JOIN pg_attrdef ad ON a.attrelid = ad.adrelid AND a.attnum = ad.adnum
-                 WHERE n.nspname = $1 AND c.relname = $2 AND a.attnum > 0 AND NOT a.attisdropped
-                 ORDER BY a.attnum",
-                &[&schema_name, &table_name],
+                "SELECT c.relname::text, GREATEST(c.reltuples, 0)::int8
+                 FROM pg_class c
+                 JOIN pg_namespace n ON n.oid = c.relnamespace
+                 WHERE n.nspname = $1 AND c.relkind IN ('r', 'p') AND NOT c.relispartition",
+                &[&schema_name],
             )
             .await
-            .map_err(|e| format!("Failed to read columns for {}: {}", table_name, e))?;
+            .map_err(|e| AppError::from(e).context("Failed to list tables"))?;
+
+        let mut counts = std::collections::BTreeMap::new();
+        for row in &rows {
+            let table: String = row.get(0);
+            let count = if exact {
+                client
+                    .query_one(
+                        &format!("SELECT count(*) FROM {}.{}", quote_ident(schema_name), quote_ident(&table)),
+                        &[],
+                    )
+                    .await
+                    .map_err(|e| AppError::from(e).context(format!("Failed to count rows in {}", table)))?
+                    .get(0)
+            } else {
+                row.get(1)
+            };
+            counts.insert(table, count);
+        }
+        Ok(counts)
+    }
+    .await;
 
-        let mut column_defs = Vec::new();
-        let mut column_names = Vec::new();
+    counts.map_err(|e: AppError| e.context(&server.name))
+}
 
-        for col in columns {
-            let col_name: String = col.get(0);
-            let col_type: String = col.get(1);
-            let not_null: bool = col.get(2);
-            let default_expr: Option<String> = col.get(3);
+/// Compare per-table row counts of one schema on two servers, e.g. after a
+/// migration. Both servers are counted concurrently. Tables present on only
+/// one side are listed with the other count `None` and never match. With
+/// `exact` false (the default) planner estimates are compared, which is fast
+/// but only meaningful after both sides have been analyzed.
+#[command]
+pub async fn compare_row_counts(
+    server_id_a: String,
+    server_id_b: String,
+    schema_name: String,
+    exact: Option<bool>,
+) -> Result<Vec<RowCountComparison>, AppError> {
+    let exact = exact.unwrap_or(false);
+    let (counts_a, counts_b) = tokio::try_join!(
+        schema_row_counts(&server_id_a, &schema_name, exact),
+        schema_row_counts(&server_id_b, &schema_name, exact),
+    )?;
+
+    let tables: std::collections::BTreeSet<&String> = counts_a.keys().chain(counts_b.keys()).collect();
+    Ok(tables
+        .into_iter()
+        .map(|table| {
+            let count_a = counts_a.get(table).copied();
+            let count_b = counts_b.get(table).copied();
+            RowCountComparison {
+                table: table.clone(),
+                count_a,
+                count_b,
+                matches: count_a.is_some() && count_a == count_b,
+            }
+        })
+        .collect())
+}
 
-            let mut def = format!("{} {}", quote_ident(&col_name), col_type);
-            if let Some(expr) = default_expr {
-                def.push_str(&format!(" DEFAULT {}", expr));
+/// Labels of an enum type in their defined order, for dropdown editors.
+/// `type_name` may be schema-qualified. Served from the schema cache when it
+/// has the type, otherwise read live (which also honours the search path).
+#[command]
+pub async fn get_enum_values(
+    server_id: String,
+    database_name: Option<String>,
+    type_name: String,
+) -> Result<Vec<String>, AppError> {
+    let server = load_server(&server_id)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let (schema_name, bare_name) = match type_name.split_once('.') {
+        Some((schema, name)) => (Some(schema.trim_matches('"')), name.trim_matches('"')),
+        None => (None, type_name.trim_matches('"')),
+    };
+    if let Some(labels) = db::get_enum_values(&server_id, &target_database, schema_name, bare_name)? {
+        return Ok(labels);
+    }
+
+    let password = server_password(&server)?;
+    let client = get_client(&server, &password, &target_database).await?;
+    let rows = client
+        .query(
+            "SELECT enumlabel::text FROM pg_enum WHERE enumtypid = $1::text::regtype ORDER BY enumsortorder",
+            &[&type_name],
+        )
+        .await
+        .map_err(|e| {
+            if e.as_db_error().map(|db_err| db_err.code().code() == "42704").unwrap_or(false) {
+                AppError::not_found(format!("Type '{}' does not exist", type_name))
+            } else {
+                AppError::from(e).context(format!("Failed to read labels of {}", type_name))
             }
-            if not_null {
-                def.push_str(" NOT NULL");
+        })?;
+    if rows.is_empty() {
+        return Err(AppError::invalid(format!("'{}' is not an enum type", type_name)));
+    }
+
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+/// Sequences in a schema with their current position, so ones close to
+/// exhausting their type (e.g. int4 serials) stand out.
+#[command]
+pub async fn get_sequences(
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: String,
+) -> Result<Vec<SequenceInfo>, AppError> {
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let client = get_client(&server, &password, &target_database).await?;
+    let rows = client
+        .query(
+            "SELECT s.sequencename::text, s.data_type::text, s.last_value, s.increment_by,
+                    s.min_value, s.max_value,
+                    (SELECT tn.nspname || '.' || t.relname || '.' || a.attname
+                     FROM pg_depend d
+                     JOIN pg_class t ON t.oid = d.refobjid
+                     JOIN pg_namespace tn ON tn.oid = t.relnamespace
+                     JOIN pg_attribute a ON a.attrelid = d.refobjid AND a.attnum = d.refobjsubid
+                     WHERE d.classid = 'pg_class'::regclass
+                       AND d.objid = seq.oid
+                       AND d.refclassid = 'pg_class'::regclass
+                       AND d.deptype IN ('a', 'i')
+                     LIMIT 1)
+             FROM pg_sequences s
+             JOIN pg_namespace sn ON sn.nspname = s.schemaname
+             JOIN pg_class seq ON seq.relnamespace = sn.oid AND seq.relname = s.sequencename
+             WHERE s.schemaname = $1
+             ORDER BY s.sequencename",
+            &[&schema_name],
+        )
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read sequences"))?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let last_value: Option<i64> = row.get(2);
+            let increment: i64 = row.get(3);
+            let min_value: i64 = row.get(4);
+            let max_value: i64 = row.get(5);
+
+            // Descending sequences count down from max towards min
+            let percent_of_max = last_value.map(|last| {
+                let range = max_value as f64 - min_value as f64;
+                let used = if increment >= 0 {
+                    last as f64 - min_value as f64
+                } else {
+                    max_value as f64 - last as f64
+                };
+                if range > 0.0 { used / range * 100.0 } else { 100.0 }
+            });
+
+            SequenceInfo {
+                name: row.get(0),
+                data_type: row.get(1),
+                last_value,
+                increment,
+                max_value,
+                owning_column: row.get(6),
+                percent_of_max,
             }
+        })
+        .collect())
+}
 
-            column_defs.push(def);
-            column_names.push(quote_ident(&col_name));
-        }
+/// Foreign keys with no index whose leading key columns are exactly the
+/// foreign key's columns (in any order). Without one, every delete or key
+/// update on the referenced table scans the referencing table, holding locks
+/// while it does. Partial and invalid indexes don't count.
+#[command]
+pub async fn find_unindexed_fks(
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: String,
+) -> Result<Vec<UnindexedForeignKey>, AppError> {
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let client = get_client(&server, &password, &target_database).await?;
+    let rows = client
+        .query(
+            "SELECT c.relname::text, con.conname::text,
+                    ARRAY(SELECT a.attname::text
+                          FROM unnest(con.conkey) WITH ORDINALITY AS k(attnum, ord)
+                          JOIN pg_attribute a ON a.attrelid = con.conrelid AND a.attnum = k.attnum
+                          ORDER BY k.ord)
+             FROM pg_constraint con
+             JOIN pg_class c ON c.oid = con.conrelid
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             WHERE con.contype = 'f'
+               AND n.nspname = $1
+               AND NOT EXISTS (
+                   SELECT 1
+                   FROM pg_index i
+                   WHERE i.indrelid = con.conrelid
+                     AND i.indpred IS NULL
+                     AND i.indisvalid
+                     AND (i.indkey::int2[])[0:cardinality(con.conkey) - 1] @> con.conkey
+                     AND (i.indkey::int2[])[0:cardinality(con.conkey) - 1] <@ con.conkey
+               )
+             ORDER BY 1, 2",
+            &[&schema_name],
+        )
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read foreign keys"))?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let table: String = row.get(0);
+            let columns: Vec<String> = row.get(2);
+            let suggested_index = format!(
+                "CREATE INDEX CONCURRENTLY ON {}.{} ({});",
+                quote_ident(&schema_name),
+                quote_ident(&table),
+                columns.iter().map(|c| quote_ident(c)).collect::<Vec<_>>().join(", ")
+            );
+            UnindexedForeignKey {
+                table,
+                constraint: row.get(1),
+                columns,
+                suggested_index,
+            }
+        })
+        .collect())
+}
+
+/// Find indexes whose key columns duplicate, or are a leading prefix of,
+/// another index on the same table. Partial indexes are skipped since their
+/// predicates make them non-interchangeable, and a unique index is only
+/// considered redundant against another unique index on the same columns.
+#[command]
+pub async fn find_duplicate_indexes(
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: String,
+) -> Result<Vec<DuplicateIndexGroup>, AppError> {
+    struct Candidate {
+        table: String,
+        summary: IndexSummary,
+        unique: bool,
+        primary: bool,
+        method: String,
+    }
 
-        write_str(
-            &mut file,
-            &format!("CREATE TABLE {}.{} (\n    {}\n);\n", schema_q, table_q, column_defs.join(",\n    ")),
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let client = get_client(&server, &password, &target_database).await?;
+    let rows = client
+        .query(
+            "SELECT t.relname::text, ic.relname::text, pg_relation_size(i.indexrelid),
+                    ARRAY(SELECT pg_get_indexdef(i.indexrelid, k, true)
+                          FROM generate_series(1, i.indnkeyatts) AS k
+                          ORDER BY k),
+                    i.indisunique, i.indisprimary, am.amname::text
+             FROM pg_index i
+             JOIN pg_class t ON t.oid = i.indrelid
+             JOIN pg_namespace n ON n.oid = t.relnamespace
+             JOIN pg_class ic ON ic.oid = i.indexrelid
+             JOIN pg_am am ON am.oid = ic.relam
+             WHERE n.nspname = $1 AND i.indpred IS NULL AND i.indisvalid
+             ORDER BY t.relname, ic.relname",
+            &[&schema_name],
         )
-        .await?;
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read indexes"))?;
+
+    let candidates: Vec<Candidate> = rows
+        .iter()
+        .map(|row| Candidate {
+            table: row.get(0),
+            summary: IndexSummary {
+                name: row.get(1),
+                size: row.get(2),
+                columns: row.get(3),
+            },
+            unique: row.get(4),
+            primary: row.get(5),
+            method: row.get(6),
+        })
+        .collect();
 
-        let constraints = client
-            .query(
-                "SELECT con.conname, pg_get_constraintdef(con.oid)
-                 FROM pg_constraint con
-                 JOIN pg_class c ON con.conrelid = c.oid
-                 JOIN pg_namespace n ON c.relnamespace = n.oid
-                 WHERE n.nspname = $1 AND c.relname = $2
-                 ORDER BY con.conname",
-                &[&schema_name, &table_name],
-            )
-            .await
-            .map_err(|e| format!("Failed to read constraints for {}: {}", table_name, e))?;
+    // Ranks which of two identical indexes to keep: PK, then unique, then name
+    let keep_rank = |c: &Candidate| (!c.primary, !c.unique, c.summary.name.clone());
 
-        for constraint in constraints {
-            let con_name: String = constraint.get(0);
-            let con_def: String = constraint.get(1);
-            write_str(
-                &mut file,
-                &format!(
-                    "ALTER TABLE {}.{} ADD CONSTRAINT {} {};\n",
-                    schema_q,
-                    table_q,
-                    quote_ident(&con_name),
-                    con_def
-                ),
-            )
-            .await?;
+    let mut groups: Vec<DuplicateIndexGroup> = Vec::new();
+    for redundant in &candidates {
+        if redundant.primary {
+            continue;
         }
 
-        let constraint_indexes = client
-            .query(
-                "SELECT c2.relname
-                 FROM pg_constraint con
-                 JOIN pg_class c ON con.conrelid = c.oid
-                 JOIN pg_class c2 ON c2.oid = con.conindid
-                 JOIN pg_namespace n ON c.relnamespace = n.oid
-                 WHERE n.nspname = $1 AND c.relname = $2 AND con.conindid <> 0",
-                &[&schema_name, &table_name],
-            )
-            .await
-            .map_err(|e| format!("Failed to read indexes for {}: {}", table_name, e))?;
+        let keeper = candidates.iter().find(|keeper| {
+            if std::ptr::eq(*keeper, redundant)
+                || keeper.table != redundant.table
+                || keeper.method != redundant.method
+            {
+                return false;
+            }
+            let same = keeper.summary.columns == redundant.summary.columns;
+            let prefix = redundant.method == "btree"
+                && keeper.summary.columns.starts_with(&redundant.summary.columns);
+            if !(same || prefix) {
+                return false;
+            }
+            if redundant.unique && !(same && keeper.unique) {
+                return false;
+            }
+            !same || keep_rank(keeper) < keep_rank(redundant)
+        });
 
-        let mut constraint_index_names = std::collections::HashSet::new();
-        for idx in constraint_indexes {
-            let name: String = idx.get(0);
-            constraint_index_names.insert(name);
-        }
+        let Some(keeper) = keeper else {
+            continue;
+        };
 
-        let indexes = client
-            .query(
-                "SELECT indexname, indexdef FROM pg_indexes WHERE schemaname = $1 AND tablename = $2 ORDER BY indexname",
-                &[&schema_name, &table_name],
+        let reason = if keeper.summary.columns == redundant.summary.columns {
+            format!(
+                "Drop {}: it has the same columns ({}) as {}",
+                redundant.summary.name,
+                redundant.summary.columns.join(", "),
+                keeper.summary.name
             )
-            .await
-            .map_err(|e| format!("Failed to read indexes for {}: {}", table_name, e))?;
+        } else {
+            format!(
+                "Drop {}: its columns ({}) are a leading prefix of {} ({})",
+                redundant.summary.name,
+                redundant.summary.columns.join(", "),
+                keeper.summary.name,
+                keeper.summary.columns.join(", ")
+            )
+        };
 
-        for index in indexes {
-            let index_name: String = index.get(0);
-            let index_def: String = index.get(1);
-            if constraint_index_names.contains(&index_name) {
-                continue;
+        match groups
+            .iter_mut()
+            .find(|group| group.table == keeper.table && group.indexes[0].name == keeper.summary.name)
+        {
+            Some(group) => {
+                group.indexes.push(redundant.summary.clone());
+                group.recommendation.push_str(&format!("\n{}", reason));
             }
-            let statement = if index_def.ends_with(';') {
-                index_def
-            } else {
-                format!("{};", index_def)
-            };
-            write_str(&mut file, &format!("{}\n", statement)).await?;
+            None => groups.push(DuplicateIndexGroup {
+                table: keeper.table.clone(),
+                indexes: vec![keeper.summary.clone(), redundant.summary.clone()],
+                recommendation: reason,
+            }),
         }
+    }
 
-        write_str(&mut file, "\n").await?;
-
-        if include_data {
-            if !column_names.is_empty() {
-                write_str(
-                    &mut file,
-                    &format!(
-                        "COPY {}.{} ({}) FROM stdin;\n",
-                        schema_q,
-                        table_q,
-                        column_names.join(", ")
-                    ),
-                )
-                .await?;
+    Ok(groups)
+}
 
-                let copy_query = format!(
-                    "COPY {}.{} ({}) TO STDOUT",
-                    schema_q,
-                    table_q,
-                    column_names.join(", ")
-                );
+/// Base tables with no primary key, in one schema or (without `schema_name`)
+/// every user schema. Such tables can't be edited in the grid and, without
+/// a replica identity, can't publish updates or deletes via logical replication.
+#[command]
+pub async fn find_tables_without_pk(
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: Option<String>,
+) -> Result<Vec<TableRef>, AppError> {
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
 
-                let stream = client
-                    .copy_out(&copy_query)
-                    .await
-                    .map_err(|e| format!("Failed to export data for {}: {}", table_name, e))?;
+    let client = get_client(&server, &password, &target_database).await?;
+    let rows = client
+        .query(
+            "SELECT n.nspname::text, c.relname::text
+             FROM pg_class c
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             WHERE c.relkind IN ('r', 'p')
+               AND NOT c.relispartition
+               AND ($1::text IS NULL OR n.nspname = $1)
+               AND n.nspname NOT IN ('pg_catalog', 'information_schema')
+               AND n.nspname !~ '^pg_'
+               AND NOT EXISTS (
+                   SELECT 1 FROM pg_constraint con
+                   WHERE con.conrelid = c.oid AND con.contype = 'p'
+               )
+             ORDER BY n.nspname, c.relname",
+            &[&schema_name],
+        )
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to look for tables without primary keys"))?;
 
-                let mut stream = Box::pin(stream);
+    Ok(rows
+        .iter()
+        .map(|row| TableRef {
+            schema: row.get(0),
+            table: row.get(1),
+        })
+        .collect())
+}
 
-                while let Some(chunk) = stream.as_mut().next().await {
-                    let bytes = chunk
-                        .map_err(|e| format!("Failed to read COPY data: {}", e))?;
-                    file.write_all(&bytes)
-                        .await
-                        .map_err(|e| format!("Failed to write COPY data: {}", e))?;
-                }
+/// Rows in other tables that reference the row of `schema_name.table_name`
+/// whose `pk_column` is `pk_value`, counted per single-column foreign key on
+/// that column, so the UI can warn before a delete or key change. The value
+/// is compared as text cast to each referencing column's type, which keeps
+/// the lookup indexable for any key type.
+#[command]
+pub async fn check_fk_references(
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: String,
+    table_name: String,
+    pk_column: String,
+    pk_value: serde_json::Value,
+) -> Result<Vec<FkReference>, AppError> {
+    let pk_text = match pk_value {
+        serde_json::Value::Null => return Ok(vec![]),
+        serde_json::Value::String(text) => text,
+        other => other.to_string(),
+    };
 
-                write_str(&mut file, "\\.\n\n").await?;
-            }
-        }
-    }
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
 
-    let views = client
+    let client = get_client(&server, &password, &target_database).await?;
+    let foreign_keys = client
         .query(
-            "SELECT table_name, view_definition FROM information_schema.views WHERE table_schema = $1 ORDER BY table_name",
-            &[&schema_name],
+            "SELECT n.nspname::text, c.relname::text, a.attname::text,
+                    format_type(a.atttypid, a.atttypmod)
+             FROM pg_constraint con
+             JOIN pg_class c ON c.oid = con.conrelid
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             JOIN pg_attribute a ON a.attrelid = con.conrelid AND a.attnum = con.conkey[1]
+             JOIN pg_attribute ra ON ra.attrelid = con.confrelid AND ra.attnum = con.confkey[1]
+             WHERE con.contype = 'f'
+               AND con.confrelid = (quote_ident($1) || '.' || quote_ident($2))::regclass
+               AND cardinality(con.conkey) = 1
+               AND ra.attname = $3
+             ORDER BY 1, 2, 3",
+            &[&schema_name, &table_name, &pk_column],
         )
         .await
-        .map_err(|e| format!("Failed to read views: {}", e))?;
+        .map_err(|e| AppError::from(e).context("Failed to read foreign keys"))?;
+
+    let mut references = Vec::new();
+    for fk in &foreign_keys {
+        let (schema, table, column, type_name): (String, String, String, String) =
+            (fk.get(0), fk.get(1), fk.get(2), fk.get(3));
+        let dependent_rows: i64 = client
+            .query_one(
+                &format!(
+                    "SELECT count(*) FROM {}.{} WHERE {} = $1::text::{}",
+                    quote_ident(&schema),
+                    quote_ident(&table),
+                    quote_ident(&column),
+                    type_name
+                ),
+                &[&pk_text],
+            )
+            .await
+            .map_err(|e| AppError::from(e).context(format!("Failed to count rows in {}.{}", schema, table)))?
+            .get(0);
 
-    if !views.is_empty() {
-        write_str(&mut file, "-- Views\n").await?;
+        references.push(FkReference {
+            referencing_table: format!("{}.{}", schema, table),
+            referencing_column: column,
+            dependent_rows,
+        });
     }
 
-    for view in views {
-        let view_name: String = view.get(0);
-        let view_def: String = view.get(1);
-        write_str(
-            &mut file,
-            &format!(
-                "CREATE OR REPLACE VIEW {}.{} AS\n{};\n\n",
-                schema_q,
-                quote_ident(&view_name),
-                view_def
-            ),
+    Ok(references)
+}
+
+/// Tables referenced through foreign keys, followed transitively, with how
+/// many hops away each is (the longest path when there are several).
+const FK_CLOSURE_SQL: &str = "
+    WITH RECURSIVE refs(oid, depth, path) AS (
+        SELECT $1::text::regclass::oid, 0, ARRAY[$1::text::regclass::oid]
+        UNION ALL
+        SELECT c.confrelid, r.depth + 1, r.path || c.confrelid
+        FROM refs r
+        JOIN pg_constraint c ON c.conrelid = r.oid AND c.contype = 'f'
+        WHERE c.confrelid <> ALL (r.path)
+    )
+    SELECT r.oid, n.nspname::text, cl.relname::text, max(r.depth) AS depth
+    FROM refs r
+    JOIN pg_class cl ON cl.oid = r.oid
+    JOIN pg_namespace n ON n.oid = cl.relnamespace
+    GROUP BY r.oid, n.nspname, cl.relname
+    ORDER BY depth DESC, 2, 3";
+
+/// Everything a table needs to be recreated elsewhere, dependencies first:
+/// user-defined types its columns use (enums, composites, domains), the
+/// sequences it owns, then the tables it references via foreign keys
+/// (transitively, most distant first), and finally the table itself. The
+/// same types and sequences are included for every referenced table.
+/// Foreign key cycles are cut at the first repeat, so the order is only
+/// restorable as-is when the constraints are added after the data.
+#[command]
+pub async fn get_table_dependencies(
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: String,
+    table_name: String,
+) -> Result<Vec<TableDependency>, AppError> {
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let client = get_client(&server, &password, &target_database).await?;
+    let qualified = format!("{}.{}", quote_ident(&schema_name), quote_ident(&table_name));
+    let table_rows = client
+        .query(FK_CLOSURE_SQL, &[&qualified])
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read foreign key dependencies"))?;
+    let oids: Vec<u32> = table_rows.iter().map(|row| row.get(0)).collect();
+
+    let type_rows = client
+        .query(
+            "SELECT DISTINCT n.nspname::text, t.typname::text
+             FROM pg_attribute a
+             JOIN pg_type t ON t.oid = (
+                 SELECT CASE WHEN at.typcategory = 'A' THEN at.typelem ELSE at.oid END
+                 FROM pg_type at WHERE at.oid = a.atttypid
+             )
+             JOIN pg_namespace n ON n.oid = t.typnamespace
+             WHERE a.attrelid = ANY($1)
+               AND a.attnum > 0
+               AND NOT a.attisdropped
+               AND t.typtype IN ('e', 'c', 'd', 'r')
+               AND n.nspname NOT IN ('pg_catalog', 'information_schema')
+             ORDER BY 1, 2",
+            &[&oids],
         )
-        .await?;
-    }
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read column types"))?;
 
-    file.flush()
+    let sequence_rows = client
+        .query(
+            "SELECT DISTINCT n.nspname::text, s.relname::text
+             FROM pg_depend d
+             JOIN pg_class s ON s.oid = d.objid AND s.relkind = 'S'
+             JOIN pg_namespace n ON n.oid = s.relnamespace
+             WHERE d.classid = 'pg_class'::regclass
+               AND d.refclassid = 'pg_class'::regclass
+               AND d.refobjid = ANY($1)
+               AND d.deptype IN ('a', 'i')
+             ORDER BY 1, 2",
+            &[&oids],
+        )
         .await
-        .map_err(|e| format!("Failed to finalize export file: {}", e))?;
+        .map_err(|e| AppError::from(e).context("Failed to read owned sequences"))?;
 
-    Ok(QueryResult {
-        columns: vec![],
-        rows: vec![],
-        rows_affected: None,
-        message: Some(format!("Schema exported to {}", output_path)),
-    })
+    let object = |kind: &str, row: &tokio_postgres::Row, offset: usize| TableDependency {
+        kind: kind.to_string(),
+        schema: row.get(offset),
+        name: row.get(offset + 1),
+    };
+    Ok(type_rows
+        .iter()
+        .map(|row| object("type", row, 0))
+        .chain(sequence_rows.iter().map(|row| object("sequence", row, 0)))
+        .chain(table_rows.iter().map(|row| object("table", row, 1)))
+        .collect())
 }
 
+/// Dead-tuple fraction above which a table is flagged for VACUUM.
+const VACUUM_DEAD_RATIO: f64 = 0.2;
+/// Tables with fewer dead tuples than this aren't flagged whatever the ratio.
+const VACUUM_MIN_DEAD_TUPLES: i64 = 1000;
+const STALE_STATS_DAYS: i64 = 7;
+
+/// Autovacuum and autoanalyze status of user tables from
+/// `pg_stat_user_tables`, worst dead-tuple ratio first, flagging tables that
+/// need a VACUUM or fresh statistics. Manual VACUUM and ANALYZE runs count
+/// towards freshness as well as automatic ones.
 #[command]
-pub async fn export_table_sql(
+pub async fn get_vacuum_health(
     server_id: String,
-    schema_name: String,
-    table_name: String,
-    include_data: bool,
-    output_path: String,
-) -> Result<QueryResult, String> {
-    let server = db::get_server_by_id(&server_id)
-        .map_err(|e| e.to_string())?
-        .ok_or("Server not found")?;
-
-    let password = credentials::retrieve_password(&server.credential_key)
-        .map_err(|e| format!("Failed to retrieve password: {}", e))?;
-
-    let pool = crate::postgres::get_or_create_pool(
-        &server.id,
-        &server.host,
-        server.port as u16,
-        &server.username,
-        &password,
-        &server.database,
-    )
-    .await
-    .map_err(|e| format!("Failed to connect to database: {}", e))?;
+    database_name: Option<String>,
+    schema_name: Option<String>,
+) -> Result<Vec<VacuumHealth>, AppError> {
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
 
-    let client = pool
-        .get()
+    let client = get_client(&server, &password, &target_database).await?;
+    let rows = client
+        .query(
+            "SELECT schemaname::text, relname::text,
+                    (extract(epoch FROM last_autovacuum) * 1000)::int8,
+                    (extract(epoch FROM last_autoanalyze) * 1000)::int8,
+                    n_dead_tup, n_live_tup, n_mod_since_analyze,
+                    (extract(epoch FROM greatest(last_analyze, last_autoanalyze)) * 1000)::int8
+             FROM pg_stat_user_tables
+             WHERE $1::text IS NULL OR schemaname = $1",
+            &[&schema_name],
+        )
         .await
-        .map_err(|e| format!("Failed to get database client: {}", e))?;
+        .map_err(|e| AppError::from(e).context("Failed to read table statistics"))?;
+
+    let stale_before = Utc::now().timestamp_millis() - STALE_STATS_DAYS * 86_400_000;
+    let mut health: Vec<VacuumHealth> = rows
+        .iter()
+        .map(|row| {
+            let schema: String = row.get(0);
+            let table: String = row.get(1);
+            let n_dead_tup: i64 = row.get(4);
+            let n_live_tup: i64 = row.get(5);
+            let modified_since_analyze: i64 = row.get(6);
+            let last_analyzed: Option<i64> = row.get(7);
+
+            let total = n_dead_tup + n_live_tup;
+            let dead_ratio = if total > 0 { n_dead_tup as f64 / total as f64 } else { 0.0 };
+            let needs_vacuum = dead_ratio > VACUUM_DEAD_RATIO && n_dead_tup >= VACUUM_MIN_DEAD_TUPLES;
+            let stale_stats = modified_since_analyze > 0
+                && last_analyzed.is_none_or(|at| at < stale_before);
+            let maintenance_sql = format!(
+                "VACUUM (ANALYZE) {}.{};",
+                quote_ident(&schema),
+                quote_ident(&table)
+            );
+
+            VacuumHealth {
+                schema,
+                table,
+                last_autovacuum: row.get(2),
+                last_autoanalyze: row.get(3),
+                n_dead_tup,
+                n_live_tup,
+                dead_ratio,
+                needs_vacuum,
+                stale_stats,
+                maintenance_sql,
+            }
+        })
+        .collect();
 
-    let mut file = File::create(&output_path)
+    health.sort_by(|a, b| b.dead_ratio.total_cmp(&a.dead_ratio));
+    Ok(health)
+}
+
+/// User tables with the most writes first, from the cumulative counters in
+/// `pg_stat_user_tables`; ties (e.g. all zero after a stats reset) are broken
+/// by the latest vacuum or analyze. Shows which tables are in use.
+#[command]
+pub async fn get_recently_modified_tables(
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: Option<String>,
+    limit: Option<i64>,
+) -> Result<Vec<TableActivity>, AppError> {
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+    let limit = limit.unwrap_or(50).max(1);
+
+    let client = get_client(&server, &password, &target_database).await?;
+    let rows = client
+        .query(
+            "SELECT schemaname::text, relname::text,
+                    n_tup_ins + n_tup_upd + n_tup_del AS writes,
+                    (extract(epoch FROM greatest(last_vacuum, last_autovacuum,
+                                                 last_analyze, last_autoanalyze)) * 1000)::int8 AS last_activity
+             FROM pg_stat_user_tables
+             WHERE $1::text IS NULL OR schemaname = $1
+             ORDER BY writes DESC, last_activity DESC NULLS LAST, 1, 2
+             LIMIT $2",
+            &[&schema_name, &limit],
+        )
         .await
-        .map_err(|e| format!("Failed to create export file: {}", e))?;
+        .map_err(|e| AppError::from(e).context("Failed to read table statistics"))?;
+
+    Ok(rows
+        .iter()
+        .map(|row| TableActivity {
+            schema: row.get(0),
+            table: row.get(1),
+            write_activity: row.get(2),
+            last_activity: row.get(3),
+        })
+        .collect())
+}
 
-    let schema_q = quote_ident(&schema_name);
-    let table_q = quote_ident(&table_name);
+/// Tables listed under "Largest tables" in a server report.
+const REPORT_LARGEST_TABLES: i64 = 20;
+
+/// Sizes in the units `pg_size_pretty` uses.
+fn format_bytes(bytes: i64) -> String {
+    const UNITS: [&str; 5] = ["bytes", "kB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value.abs() >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} bytes", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
 
-    write_str(&mut file, "-- FastDB table export\n").await?;
-    write_str(
-        &mut file,
-        &format!("-- Table: {}.{}\n\n", schema_name, table_name),
-    )
-    .await?;
+/// Text for a Markdown table cell.
+fn md_cell(value: &str) -> String {
+    value.replace('|', "\\|").replace('\n', " ")
+}
 
-    let columns = client
+fn format_epoch_ms(ms: Option<i64>) -> String {
+    ms.and_then(chrono::DateTime::from_timestamp_millis)
+        .map(|at| at.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_else(|| "never".to_string())
+}
+
+/// Parts of a server report with no command of their own.
+struct ReportCatalog {
+    version: String,
+    /// Name and installed version
+    extensions: Vec<(String, String)>,
+    /// Schema, table, total size, index size
+    largest_tables: Vec<(String, String, i64, i64)>,
+    /// Schema, table, index, size
+    unused_indexes: Vec<(String, String, String, i64)>,
+}
+
+async fn report_catalog(server: &db::Server, password: &str, database: &str) -> Result<ReportCatalog, AppError> {
+    let client = get_client(server, password, database).await?;
+
+    let version: String = client
+        .query_one("SELECT version()", &[])
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read server version"))?
+        .get(0);
+    let extensions = client
+        .query("SELECT extname::text, extversion FROM pg_extension ORDER BY 1", &[])
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read extensions"))?
+        .iter()
+        .map(|row| (row.get(0), row.get(1)))
+        .collect();
+    let largest_tables = client
         .query(
-            "SELECT a.attname, pg_catalog.format_type(a.atttypid, a.atttypmod), a.attnotnull, pg_get_expr(ad.adbin, ad.adrelid)
-             FROM pg_attribute a
-             JOIN pg_class c ON a.attrelid = c.oid
-             JOIN pg_namespace n ON c.relnamespace = n.oid
-             LEFT JOIN pg_attrdef ad ON a.attrelid = ad.adrelid AND a.attnum = ad.adnum
-             WHERE n.nspname = $1 AND c.relname = $2 AND a.attnum > 0 AND NOT a.attisdropped
-             ORDER BY a.attnum",
-            &[&schema_name, &table_name],
+            "SELECT n.nspname::text, c.relname::text,
+                    pg_total_relation_size(c.oid), pg_indexes_size(c.oid)
+             FROM pg_class c
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             WHERE c.relkind = 'r'
+               AND n.nspname NOT IN ('pg_catalog', 'information_schema')
+               AND n.nspname NOT LIKE 'pg_toast%'
+             ORDER BY 3 DESC, 1, 2
+             LIMIT $1",
+            &[&REPORT_LARGEST_TABLES],
+        )
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read table sizes"))?
+        .iter()
+        .map(|row| (row.get(0), row.get(1), row.get(2), row.get(3)))
+        .collect();
+    // Unique and primary key indexes enforce constraints even when never scanned
+    let unused_indexes = client
+        .query(
+            "SELECT s.schemaname::text, s.relname::text, s.indexrelname::text,
+                    pg_relation_size(s.indexrelid)
+             FROM pg_stat_user_indexes s
+             JOIN pg_index i ON i.indexrelid = s.indexrelid
+             WHERE s.idx_scan = 0 AND NOT i.indisunique AND NOT i.indisprimary
+             ORDER BY 4 DESC, 1, 2, 3",
+            &[],
         )
         .await
-        .map_err(|e| format!("Failed to read columns for {}: {}", table_name, e))?;
+        .map_err(|e| AppError::from(e).context("Failed to read index usage"))?
+        .iter()
+        .map(|row| (row.get(0), row.get(1), row.get(2), row.get(3)))
+        .collect();
 
-    let mut column_defs = Vec::new();
-    let mut column_names = Vec::new();
+    Ok(ReportCatalog {
+        version,
+        extensions,
+        largest_tables,
+        unused_indexes,
+    })
+}
 
-    for col in columns {
-        let col_name: String = col.get(0);
-        let col_type: String = col.get(1);
-        let not_null: bool = col.get(2);
-        let default_expr: Option<String> = col.get(3);
+/// Write a Markdown health report of one database for reviews and handoffs:
+/// server version and extensions, schema sizes, the largest tables, indexes
+/// never scanned since statistics were reset, tables without a primary key
+/// and tables needing a VACUUM or ANALYZE. The sections are gathered
+/// concurrently, then written out one at a time.
+#[command]
+pub async fn generate_server_report(
+    server_id: String,
+    database_name: Option<String>,
+    output_path: String,
+) -> Result<(), AppError> {
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let (catalog, schemas, without_pk, vacuum) = tokio::try_join!(
+        report_catalog(&server, &password, &target_database),
+        get_schema_sizes(server_id.clone(), Some(target_database.clone())),
+        find_tables_without_pk(server_id.clone(), Some(target_database.clone()), None),
+        get_vacuum_health(server_id.clone(), Some(target_database.clone()), None),
+    )?;
+
+    let file = File::create(&output_path)
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to create report file"))?;
+    let mut file = BufWriter::new(file);
 
-        let mut def = format!("{} {}", quote_ident(&col_name), col_type);
-        if let Some(expr) = default_expr {
-            def.push_str(&format!(" DEFAULT {}", expr));
-        }
-        if not_null {
-            def.push_str(" NOT NULL");
+    write_str(
+        &mut file,
+        &format!(
+            "# Database report: {}\n\n- Server: {} ({}:{})\n- Generated: {}\n\n",
+            target_database,
+            server.name,
+            server.host,
+            server.port,
+            Utc::now().format("%Y-%m-%d %H:%M UTC")
+        ),
+    )
+    .await?;
+
+    write_str(&mut file, &format!("## Server\n\n{}\n\n", catalog.version)).await?;
+    if catalog.extensions.is_empty() {
+        write_str(&mut file, "No extensions installed.\n\n").await?;
+    } else {
+        write_str(&mut file, "| Extension | Version |\n| --- | --- |\n").await?;
+        for (name, version) in &catalog.extensions {
+            write_str(&mut file, &format!("| {} | {} |\n", md_cell(name), md_cell(version))).await?;
         }
+        write_str(&mut file, "\n").await?;
+    }
 
-        column_defs.push(def);
-        column_names.push(quote_ident(&col_name));
+    write_str(&mut file, "## Schemas\n\n| Schema | Tables | Size |\n| --- | ---: | ---: |\n").await?;
+    for schema in &schemas {
+        write_str(
+            &mut file,
+            &format!("| {} | {} | {} |\n", md_cell(&schema.schema), schema.table_count, format_bytes(schema.size_bytes)),
+        )
+        .await?;
     }
 
     write_str(
         &mut file,
-        &format!("CREATE TABLE {}.{} (\n    {}\n);\n", schema_q, table_q, column_defs.join(",\n    ")),
+        "\n## Largest tables\n\n| Table | Total size | Index size |\n| --- | ---: | ---: |\n",
     )
     .await?;
+    for (schema, table, total, indexes) in &catalog.largest_tables {
+        write_str(
+            &mut file,
+            &format!(
+                "| {}.{} | {} | {} |\n",
+                md_cell(schema),
+                md_cell(table),
+                format_bytes(*total),
+                format_bytes(*indexes)
+            ),
+        )
+        .await?;
+    }
 
-    let constraints = client
-        .query(
-            "SELECT con.conname, pg_get_constraintdef(con.oid)
-             FROM pg_constraint con
-             JOIN pg_class c ON con.conrelid = c.oid
-             JOIN pg_namespace n ON c.relnamespace = n.oid
-             WHERE n.nspname = $1 AND c.relname = $2
-             ORDER BY con.conname",
-            &[&schema_name, &table_name],
+    write_str(&mut file, "\n## Unused indexes\n\n").await?;
+    if catalog.unused_indexes.is_empty() {
+        write_str(&mut file, "None.\n").await?;
+    } else {
+        write_str(
+            &mut file,
+            "Never scanned since statistics were last reset; unique and primary key indexes are excluded.\n\n\
+             | Index | Table | Size |\n| --- | --- | ---: |\n",
         )
-        .await
-        .map_err(|e| format!("Failed to read constraints for {}: {}", table_name, e))?;
+        .await?;
+        for (schema, table, index, size) in &catalog.unused_indexes {
+            write_str(
+                &mut file,
+                &format!(
+                    "| {} | {}.{} | {} |\n",
+                    md_cell(index),
+                    md_cell(schema),
+                    md_cell(table),
+                    format_bytes(*size)
+                ),
+            )
+            .await?;
+        }
+    }
 
-    for constraint in constraints {
-        let con_name: String = constraint.get(0);
-        let con_def: String = constraint.get(1);
+    write_str(&mut file, "\n## Tables without a primary key\n\n").await?;
+    if without_pk.is_empty() {
+        write_str(&mut file, "None.\n").await?;
+    }
+    for table in &without_pk {
+        write_str(&mut file, &format!("- {}.{}\n", md_cell(&table.schema), md_cell(&table.table))).await?;
+    }
+
+    write_str(&mut file, "\n## Vacuum health\n\n").await?;
+    let flagged: Vec<&VacuumHealth> = vacuum.iter().filter(|t| t.needs_vacuum || t.stale_stats).collect();
+    if flagged.is_empty() {
+        write_str(&mut file, "No tables need a VACUUM or fresh statistics.\n").await?;
+    } else {
         write_str(
             &mut file,
-            &format!(
-                "ALTER TABLE {}.{} ADD CONSTRAINT {} {};\n",
-                schema_q,
-                table_q,
-                quote_ident(&con_name),
-                con_def
-            ),
+            "| Table | Dead rows | Last autovacuum | Last autoanalyze | Needs |\n| --- | ---: | --- | --- | --- |\n",
         )
         .await?;
+        for table in flagged {
+            let needs = match (table.needs_vacuum, table.stale_stats) {
+                (true, true) => "VACUUM, ANALYZE",
+                (true, false) => "VACUUM",
+                _ => "ANALYZE",
+            };
+            write_str(
+                &mut file,
+                &format!(
+                    "| {}.{} | {} ({:.0}%) | {} | {} | {} |\n",
+                    md_cell(&table.schema),
+                    md_cell(&table.table),
+                    table.n_dead_tup,
+                    table.dead_ratio * 100.0,
+                    format_epoch_ms(table.last_autovacuum),
+                    format_epoch_ms(table.last_autoanalyze),
+                    needs
+                ),
+            )
+            .await?;
+        }
     }
 
-    let constraint_indexes = client
+    file.flush()
+        .await
+        .map_err(|e| AppError::from(e).context("Failed writing report file"))?;
+    Ok(())
+}
+
+/// Table bloat: the expected page count for the live tuples (average row
+/// width from `pg_stats`, tuple header and alignment, fillfactor) compared
+/// with the actual heap plus TOAST pages. Assumes 8-byte alignment.
+const TABLE_BLOAT_SQL: &str = "
+SELECT schemaname::text, tblname::text, NULL::text,
+       (bs * tblpages)::int8,
+       GREATEST((tblpages - est_tblpages_ff) * bs, 0)::int8,
+       CASE WHEN tblpages > 0
+            THEN GREATEST(100 * (tblpages - est_tblpages_ff) / tblpages, 0)
+            ELSE 0
+       END::float8
+FROM (
+    SELECT ceil(reltuples / ((bs - page_hdr) * fillfactor / (tpl_size * 100)))
+             + ceil(toasttuples / 4) AS est_tblpages_ff,
+           heappages + toastpages AS tblpages, bs, schemaname, tblname
+    FROM (
+        SELECT (4 + tpl_hdr_size + tpl_data_size + (2 * ma)
+                - CASE WHEN tpl_hdr_size % ma = 0 THEN ma ELSE tpl_hdr_size % ma END
+                - CASE WHEN ceil(tpl_data_size)::int % ma = 0 THEN ma ELSE ceil(tpl_data_size)::int % ma END
+               ) AS tpl_size,
+               heappages, toastpages, reltuples, toasttuples, bs, page_hdr, schemaname, tblname, fillfactor
+        FROM (
+            SELECT ns.nspname AS schemaname, tbl.relname AS tblname, tbl.reltuples,
+                   tbl.relpages AS heappages, coalesce(toast.relpages, 0) AS toastpages,
+                   coalesce(toast.reltuples, 0) AS toasttuples,
+                   coalesce(substring(array_to_string(tbl.reloptions, ' ') FROM 'fillfactor=([0-9]+)')::int, 100) AS fillfactor,
+                   current_setting('block_size')::numeric AS bs,
+                   8 AS ma,
+                   24 AS page_hdr,
+                   23 + CASE WHEN max(coalesce(s.null_frac, 0)) > 0 THEN (7 + count(s.attname)) / 8 ELSE 0 END AS tpl_hdr_size,
+                   sum((1 - coalesce(s.null_frac, 0)) * coalesce(s.avg_width, 0)) AS tpl_data_size,
+                   bool_or(att.atttypid = 'pg_catalog.name'::regtype) OR count(*) <> count(s.attname) AS is_na
+            FROM pg_attribute att
+            JOIN pg_class tbl ON tbl.oid = att.attrelid
+            JOIN pg_namespace ns ON ns.oid = tbl.relnamespace
+            LEFT JOIN pg_stats s ON s.schemaname = ns.nspname AND s.tablename = tbl.relname
+                 AND NOT s.inherited AND s.attname = att.attname
+            LEFT JOIN pg_class toast ON toast.oid = tbl.reltoastrelid
+            WHERE att.attnum > 0 AND NOT att.attisdropped
+              AND tbl.relkind IN ('r', 'm') AND tbl.reltuples >= 0
+              AND ns.nspname = $1
+            GROUP BY ns.nspname, tbl.relname, tbl.reltuples, tbl.relpages, tbl.reloptions,
+                     toast.relpages, toast.reltuples
+        ) AS attrs
+        WHERE NOT is_na
+    ) AS tuples
+) AS pages";
+
+/// B-tree index bloat, estimated the same way from the indexed columns'
+/// widths (expression columns use the index's own statistics).
+const INDEX_BLOAT_SQL: &str = "
+SELECT nspname::text, tblname::text, idxname::text,
+       (bs * relpages)::int8,
+       GREATEST(bs * (relpages - est_pages_ff), 0)::int8,
+       GREATEST(100 * (relpages - est_pages_ff) / relpages, 0)::float8
+FROM (
+    SELECT coalesce(1 + ceil(reltuples / floor((bs - pageopqdata - pagehdr) * fillfactor
+             / (100 * (4 + nulldatahdrwidth)))), 0) AS est_pages_ff,
+           bs, nspname, tblname, idxname, relpages
+    FROM (
+        SELECT bs, nspname, tblname, idxname, reltuples, relpages, fillfactor, pagehdr, pageopqdata,
+               (index_tuple_hdr_bm + maxalign
+                - CASE WHEN index_tuple_hdr_bm % maxalign = 0 THEN maxalign ELSE index_tuple_hdr_bm % maxalign END
+                + nulldatawidth + maxalign
+                - CASE WHEN nulldatawidth = 0 THEN 0
+                       WHEN nulldatawidth::int % maxalign = 0 THEN maxalign
+                       ELSE nulldatawidth::int % maxalign END
+               )::numeric AS nulldatahdrwidth
+        FROM (
+            SELECT n.nspname, ct.relname AS tblname, ci.relname AS idxname, ci.reltuples, ci.relpages,
+                   coalesce(substring(array_to_string(ci.reloptions, ' ') FROM 'fillfactor=([0-9]+)')::int, 90) AS fillfactor,
+                   current_setting('block_size')::numeric AS bs,
+                   8 AS maxalign, 24 AS pagehdr, 16 AS pageopqdata,
+                   CASE WHEN max(coalesce(s.null_frac, 0)) = 0 THEN 8 ELSE 8 + ((32 + 8 - 1) / 8) END AS index_tuple_hdr_bm,
+                   sum((1 - coalesce(s.null_frac, 0)) * coalesce(s.avg_width, 1024)) AS nulldatawidth,
+                   bool_or(a.atttypid = 'pg_catalog.name'::regtype) OR count(*) <> count(s.attname) AS is_na
+            FROM pg_index i
+            JOIN pg_class ci ON ci.oid = i.indexrelid
+            JOIN pg_class ct ON ct.oid = i.indrelid
+            JOIN pg_namespace n ON n.oid = ct.relnamespace
+            JOIN pg_am am ON am.oid = ci.relam AND am.amname = 'btree'
+            CROSS JOIN LATERAL generate_series(0, i.indnatts - 1) AS pos
+            JOIN pg_attribute a
+              ON (i.indkey[pos] <> 0 AND a.attrelid = ct.oid AND a.attnum = i.indkey[pos])
+              OR (i.indkey[pos] = 0 AND a.attrelid = ci.oid AND a.attnum = pos + 1)
+            LEFT JOIN pg_stats s ON s.schemaname = n.nspname
+                 AND s.tablename = CASE WHEN i.indkey[pos] = 0 THEN ci.relname ELSE ct.relname END
+                 AND s.attname = a.attname
+            WHERE n.nspname = $1 AND ci.relpages > 0 AND ci.reltuples >= 0
+            GROUP BY n.nspname, ct.relname, ci.relname, ci.reltuples, ci.relpages, ci.reloptions
+        ) AS attrs
+        WHERE NOT is_na
+    ) AS tuples
+) AS pages";
+
+/// Estimated wasted space in a schema's tables and B-tree indexes, largest
+/// first, to judge when a VACUUM FULL or REINDEX is worth it. The numbers
+/// come from planner statistics, so they are only as fresh as the last
+/// ANALYZE; relations without usable statistics are left out.
+#[command]
+pub async fn get_bloat_estimate(
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: String,
+) -> Result<Vec<BloatEstimate>, AppError> {
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let client = get_client(&server, &password, &target_database).await?;
+    let table_rows = client
+        .query(TABLE_BLOAT_SQL, &[&schema_name])
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to estimate table bloat"))?;
+    let index_rows = client
+        .query(INDEX_BLOAT_SQL, &[&schema_name])
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to estimate index bloat"))?;
+
+    let mut estimates: Vec<BloatEstimate> = table_rows
+        .iter()
+        .chain(&index_rows)
+        .map(|row| BloatEstimate {
+            schema: row.get(0),
+            table: row.get(1),
+            index: row.get(2),
+            size_bytes: row.get(3),
+            bloat_bytes: row.get(4),
+            bloat_percent: row.get(5),
+        })
+        .collect();
+
+    estimates.sort_by_key(|estimate| std::cmp::Reverse(estimate.bloat_bytes));
+    Ok(estimates)
+}
+
+/// Who holds which privileges on a table, table-level grants first, then
+/// column-level ones. Read from the `information_schema` grant views, so only
+/// grants visible to the connecting role are listed: those it made, received
+/// or holds through a role it belongs to.
+#[command]
+pub async fn get_table_privileges(
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: String,
+    table_name: String,
+) -> Result<Vec<TablePrivilege>, AppError> {
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let client = get_client(&server, &password, &target_database).await?;
+    let rows = client
         .query(
-            "SELECT c2.relname
-             FROM pg_constraint con
-             JOIN pg_class c ON con.conrelid = c.oid
-             JOIN pg_class c2 ON c2.oid = con.conindid
-             JOIN pg_namespace n ON c.relnamespace = n.oid
-             WHERE n.nspname = $1 AND c.relname = $2 AND con.conindid <> 0",
+            "SELECT grantee::text, privilege_type::text, is_grantable = 'YES', NULL::text
+             FROM information_schema.role_table_grants
+             WHERE table_schema = $1 AND table_name = $2
+             UNION ALL
+             SELECT grantee::text, privilege_type::text, is_grantable = 'YES', column_name::text
+             FROM information_schema.role_column_grants
+             WHERE table_schema = $1 AND table_name = $2
+             ORDER BY 4 NULLS FIRST, 1, 2",
             &[&schema_name, &table_name],
         )
         .await
-        .map_err(|e| format!("Failed to read indexes for {}: {}", table_name, e))?;
+        .map_err(|e| AppError::from(e).context("Failed to read table privileges"))?;
+
+    Ok(rows
+        .iter()
+        .map(|row| TablePrivilege {
+            grantee: row.get(0),
+            privilege: row.get(1),
+            grantable: row.get(2),
+            column: row.get(3),
+        })
+        .collect())
+}
 
-    let mut constraint_index_names = std::collections::HashSet::new();
-    for idx in constraint_indexes {
-        let name: String = idx.get(0);
-        constraint_index_names.insert(name);
-    }
+/// Columns of a table as the insert form needs them: which ones the server
+/// fills in when left blank, and which can't be given a value at all.
+#[command]
+pub async fn get_insertable_columns(
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: String,
+    table_name: String,
+) -> Result<Vec<InsertableColumn>, AppError> {
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
 
-    let indexes = client
+    let client = get_client(&server, &password, &target_database).await?;
+    let rows = client
         .query(
-            "SELECT indexname, indexdef FROM pg_indexes WHERE schemaname = $1 AND tablename = $2 ORDER BY indexname",
+            "SELECT c.column_name::text,
+                    format_type(a.atttypid, a.atttypmod),
+                    c.is_nullable = 'YES',
+                    c.column_default IS NOT NULL OR c.is_identity = 'YES' OR c.is_generated = 'ALWAYS',
+                    c.is_identity = 'YES' OR COALESCE(c.column_default, '') LIKE 'nextval(%',
+                    c.is_generated = 'ALWAYS',
+                    COALESCE(c.identity_generation = 'ALWAYS', false)
+             FROM information_schema.columns c
+             JOIN pg_attribute a
+               ON a.attrelid = format('%I.%I', c.table_schema, c.table_name)::regclass
+              AND a.attname = c.column_name
+             WHERE c.table_schema = $1 AND c.table_name = $2
+             ORDER BY c.ordinal_position",
             &[&schema_name, &table_name],
         )
         .await
-        .map_err(|e| format!("Failed to read indexes for {}: {}", table_name, e))?;
+        .map_err(|e| AppError::from(e).context("Failed to read table columns"))?;
 
-    for index in indexes {
-        let index_name: String = index.get(0);
-        let index_def: String = index.get(1);
-        if constraint_index_names.contains(&index_name) {
-            continue;
-        }
-        let statement = if index_def.ends_with(';') {
-            index_def
-        } else {
-            format!("{};", index_def)
-        };
-        write_str(&mut file, &format!("{}\n", statement)).await?;
+    if rows.is_empty() {
+        return Err(AppError::not_found(format!("Table {}.{} not found", schema_name, table_name)));
     }
 
-    write_str(&mut file, "\n").await?;
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let is_generated: bool = row.get(5);
+            let identity_always: bool = row.get(6);
+            InsertableColumn {
+                name: row.get(0),
+                data_type: row.get(1),
+                nullable: row.get(2),
+                has_default: row.get(3),
+                is_identity: row.get(4),
+                is_generated,
+                insertable: !is_generated && !identity_always,
+            }
+        })
+        .collect())
+}
 
-    if include_data && !column_names.is_empty() {
-        write_str(
-            &mut file,
-            &format!(
-                "COPY {}.{} ({}) FROM stdin;\n",
-                schema_q,
-                table_q,
-                column_names.join(", ")
-            ),
-        )
-        .await?;
+/// What the server's active client sessions are waiting on, as counts per
+/// wait event, most common first. Idle and idle-in-transaction sessions
+/// aren't running anything, so they and this connection are left out.
+#[command]
+pub async fn get_wait_events(server_id: String) -> Result<Vec<WaitEventCount>, AppError> {
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
 
-        let copy_query = format!(
-            "COPY {}.{} ({}) TO STDOUT",
-            schema_q,
-            table_q,
-            column_names.join(", ")
-        );
+    let client = get_client(&server, &password, &server.database).await?;
+    let rows = client
+        .query(
+            "SELECT wait_event_type::text, wait_event::text, count(*)
+             FROM pg_stat_activity
+             WHERE state = 'active'
+               AND backend_type = 'client backend'
+               AND pid <> pg_backend_pid()
+             GROUP BY 1, 2
+             ORDER BY 3 DESC, 1 NULLS FIRST, 2",
+            &[],
+        )
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read wait events"))?;
+
+    Ok(rows
+        .iter()
+        .map(|row| WaitEventCount {
+            wait_event_type: row.get(0),
+            wait_event: row.get(1),
+            count: row.get(2),
+        })
+        .collect())
+}
 
-        let stream = client
-            .copy_out(&copy_query)
-            .await
-            .map_err(|e| format!("Failed to export data for {}: {}", table_name, e))?;
+/// Column types (as `information_schema` names them) a histogram can be
+/// built over.
+const HISTOGRAM_TYPES: &[&str] = &["smallint", "integer", "bigint", "numeric", "real", "double precision"];
+const MAX_HISTOGRAM_BUCKETS: i32 = 1_000;
+
+/// Lay `(bucket, count)` pairs from `width_bucket` (1-based) out as `buckets`
+/// equal-width ranges over `min..=max`, with zero counts for empty ones. When
+/// every value is the same there is a single bucket.
+fn histogram_buckets(min: f64, max: f64, buckets: i32, counts: &[(i32, i64)]) -> Vec<HistogramBucket> {
+    let buckets = if min == max { 1 } else { buckets };
+    let width = (max - min) / buckets as f64;
+    (1..=buckets)
+        .map(|bucket| HistogramBucket {
+            range_start: min + width * (bucket - 1) as f64,
+            range_end: if bucket == buckets { max } else { min + width * bucket as f64 },
+            count: counts.iter().filter(|(b, _)| *b == bucket).map(|(_, count)| count).sum(),
+        })
+        .collect()
+}
 
-        let mut stream = Box::pin(stream);
+/// Frequency distribution of a numeric column over `buckets` equal-width
+/// ranges between its minimum and maximum, for an inline distribution chart.
+/// The column must be numeric in the schema cache. NULLs, NaN and infinities
+/// are left out, and values are compared as `float8`, so very large
+/// `numeric`s lose precision. An empty vector means no values to count.
+#[command]
+pub async fn column_histogram(
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: String,
+    table_name: String,
+    column_name: String,
+    buckets: i32,
+) -> Result<Vec<HistogramBucket>, AppError> {
+    if !(1..=MAX_HISTOGRAM_BUCKETS).contains(&buckets) {
+        return Err(AppError::invalid(format!("Bucket count must be between 1 and {}", MAX_HISTOGRAM_BUCKETS)));
+    }
 
-        while let Some(chunk) = stream.as_mut().next().await {
-            let bytes = chunk
-                .map_err(|e| format!("Failed to read COPY data: {}", e))?;
-            file.write_all(&bytes)
-                .await
-                .map_err(|e| format!("Failed to write COPY data: {}", e))?;
-        }
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
 
-        write_str(&mut file, "\\.\n").await?;
+    let schema_id = db::find_schema_id(&server.id, &target_database, &schema_name)?
+        .ok_or_else(|| AppError::not_found(format!("Schema {} is not cached; refresh the schema first", schema_name)))?;
+    let table = db::get_tables(&schema_id)?
+        .into_iter()
+        .find(|table| table.name == table_name)
+        .ok_or_else(|| AppError::not_found(format!("Table {} is not cached; refresh the schema first", table_name)))?;
+    let column = db::get_columns(&table.id)?
+        .into_iter()
+        .find(|column| column.name == column_name)
+        .ok_or_else(|| AppError::invalid(format!("Column {} does not exist on {}", column_name, table_name)))?;
+    if !HISTOGRAM_TYPES.contains(&column.data_type.as_str()) {
+        return Err(AppError::invalid(format!(
+            "Column {} is {}, not a numeric type",
+            column_name, column.data_type
+        )));
     }
 
-    file.flush()
+    // Bounds come from the same scan; width_bucket puts the maximum in
+    // bucket n + 1, so it is folded into the last one
+    let sql = format!(
+        "WITH vals AS (
+             SELECT {col}::float8 AS v FROM {schema}.{table}
+             WHERE {col} IS NOT NULL AND {col}::float8 NOT IN ('NaN', 'Infinity', '-Infinity')
+         ),
+         bounds AS (SELECT min(v) AS lo, max(v) AS hi FROM vals)
+         SELECT bounds.lo, bounds.hi,
+                CASE WHEN bounds.lo = bounds.hi THEN 1
+                     ELSE least(width_bucket(vals.v, bounds.lo, bounds.hi, $1), $1)
+                END AS bucket,
+                count(*)
+         FROM vals CROSS JOIN bounds
+         GROUP BY 1, 2, 3
+         ORDER BY 3",
+        col = quote_ident(&column_name),
+        schema = quote_ident(&schema_name),
+        table = quote_ident(&table_name),
+    );
+
+    let client = get_client(&server, &password, &target_database).await?;
+    let rows = client
+        .query(&sql, &[&buckets])
         .await
-        .map_err(|e| format!("Failed to finalize export file: {}", e))?;
+        .map_err(|e| AppError::from(e).context("Failed to build histogram"))?;
 
-    Ok(QueryResult {
-        columns: vec![],
-        rows: vec![],
-        rows_affected: None,
-        message: Some(format!("Table exported to {}", output_path)),
-    })
+    let Some(first) = rows.first() else {
+        return Ok(Vec::new());
+    };
+    let (min, max): (f64, f64) = (first.get(0), first.get(1));
+    let counts: Vec<(i32, i64)> = rows.iter().map(|row| (row.get(2), row.get(3))).collect();
+    Ok(histogram_buckets(min, max, buckets, &counts))
 }
 
-fn normalize_sql_head(sql: &str) -> String {
-    let mut s = sql.trim_start().to_string();
+/// Progress of running VACUUMs, index builds and COPYs across the server,
+/// from the `pg_stat_progress_*` views. Percent is blocks (bytes for COPY)
+/// done over total. Views missing on older servers are skipped: index
+/// progress needs Postgres 12, COPY progress 14.
+#[command]
+pub async fn get_operation_progress(server_id: String) -> Result<Vec<OperationProgress>, AppError> {
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
 
-    loop {
-        let trimmed = s.trim_start();
-        if trimmed.starts_with("--") {
-            if let Some(pos) = trimmed.find('\n') {
-                s = trimmed[pos + 1..].to_string();
-                continue;
-            }
-            return "".to_string();
-        }
-        if trimmed.starts_with("/*") {
-            if let Some(end) = trimmed.find("*/") {
-                s = trimmed[end + 2..].to_string();
-                continue;
-            }
-            return "".to_string();
-        }
-        return trimmed.to_lowercase();
+    let client = get_client(&server, &password, &server.database).await?;
+    let version: i32 = client
+        .query_one("SELECT current_setting('server_version_num')::int4", &[])
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read server version"))?
+        .get(0);
+
+    let mut parts = vec![
+        "SELECT pid, 'VACUUM' AS command, phase, heap_blks_scanned AS done, heap_blks_total AS total
+         FROM pg_stat_progress_vacuum",
+    ];
+    if version >= 120000 {
+        parts.push(
+            "SELECT pid, command, phase, blocks_done, blocks_total
+             FROM pg_stat_progress_create_index",
+        );
+    }
+    if version >= 140000 {
+        parts.push(
+            "SELECT pid, command, NULL, bytes_processed, bytes_total
+             FROM pg_stat_progress_copy",
+        );
     }
-}
 
-#[command]
-pub async fn cancel_query(query_id: String) -> Result<(), String> {
-    crate::postgres::cancel_query(&query_id)
+    let rows = client
+        .query(
+            &format!(
+                "SELECT pid, command, phase,
+                        round(100.0 * done / NULLIF(total, 0), 1)::float8
+                 FROM ({}) progress
+                 ORDER BY pid",
+                parts.join("\n UNION ALL\n")
+            ),
+            &[],
+        )
         .await
-        .map_err(|e| e.to_string())
+        .map_err(|e| AppError::from(e).context("Failed to read operation progress"))?;
+
+    Ok(rows
+        .iter()
+        .map(|row| OperationProgress {
+            pid: row.get(0),
+            command: row.get(1),
+            phase: row.get(2),
+            percent_complete: row.get(3),
+        })
+        .collect())
 }
 
+/// Have Postgres parse and analyze `sql` without running it. The statement is
+/// prepared (protocol-level, closed again when dropped) inside a transaction
+/// that is always rolled back, so nothing can persist even for DML or DDL.
+/// SQL problems come back as `valid: false` with the structured error and its
+/// position; connection problems are returned as errors.
 #[command]
-pub async fn get_schema_tree(server_id: String) -> Result<Vec<db::Schema>, String> {
-    db::get_schemas(&server_id).map_err(|e| e.to_string())
+pub async fn validate_sql(
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: Option<String>,
+    sql: String,
+) -> Result<SqlValidation, AppError> {
+    // Only trim the end so error positions still match the editor text
+    let statement = sql.trim_end().trim_end_matches(';');
+    if statement.trim().is_empty() {
+        return Ok(SqlValidation { valid: true, error: None });
+    }
+
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let mut client = get_client(&server, &password, &target_database).await?;
+    let tx = client.transaction().await?;
+    if let Some(schema) = schema_name.as_deref() {
+        tx.batch_execute(&format!("SET LOCAL search_path TO {}", quote_ident(schema)))
+            .await?;
+    }
+    let prepared = tx.prepare(statement).await;
+    tx.rollback().await?;
+
+    match prepared {
+        Ok(_) => Ok(SqlValidation { valid: true, error: None }),
+        Err(e) if e.as_db_error().is_some() => Ok(SqlValidation {
+            valid: false,
+            error: Some(AppError::from(e)),
+        }),
+        Err(e) => Err(AppError::from(e)),
+    }
 }
 
+/// Write the JSON plan of `sql` to `output_path` in the `EXPLAIN (FORMAT JSON)`
+/// shape plan visualizers accept, alongside the query text so the file makes
+/// sense on its own. `analyze` runs the statement, so it is only allowed for
+/// reads and still happens in a read-only transaction that is rolled back.
 #[command]
-pub async fn refresh_schema(window: Window, server_id: String) -> Result<(), String> {
-    let server = db::get_server_by_id(&server_id)
-        .map_err(|e| e.to_string())?
-        .ok_or("Server not found")?;
+pub async fn export_query_plan(
+    server_id: String,
+    database_name: Option<String>,
+    sql: String,
+    output_path: String,
+    analyze: bool,
+) -> Result<QueryResult, AppError> {
+    let statement = sql.trim().trim_end_matches(';');
+    if statement.is_empty() {
+        return Err(AppError::invalid("Query is required"));
+    }
+    if analyze && !crate::postgres::is_read_only_statement(statement) {
+        return Err(AppError::invalid(
+            "EXPLAIN ANALYZE executes the query, so it is only allowed for read-only statements",
+        ));
+    }
 
-    let password = credentials::retrieve_password(&server.credential_key)
-        .map_err(|e| format!("Failed to retrieve password: {}", e))?;
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
 
-    crate::schema::refresh_schema_for_server(&server, &password)
+    let mut client = get_client(&server, &password, &target_database).await?;
+    let tx = client.read_only_transaction().await?;
+    let options = if analyze { "ANALYZE, BUFFERS, FORMAT JSON" } else { "FORMAT JSON" };
+    let row = tx
+        .query_one(&format!("EXPLAIN ({}) {}", options, statement), &[])
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to explain query"))?;
+    tx.rollback().await?;
+
+    let plan: serde_json::Value = row.get(0);
+    let document = serde_json::json!({
+        "query": statement,
+        "analyze": analyze,
+        "plan": plan,
+    });
+    let json = serde_json::to_string_pretty(&document)
+        .map_err(|e| AppError::internal(e.to_string()))?;
+    tokio::fs::write(&output_path, json)
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| AppError::from(e).context("Failed to write plan file"))?;
 
-    // Emit updated schema
-    let updated_schemas = db::get_schemas(&server_id).map_err(|e| e.to_string())?;
+    Ok(QueryResult {
+        columns: vec![],
+        rows: vec![],
+        rows_affected: None,
+        message: Some(format!("Query plan exported to {}", output_path)),
+    })
+}
 
-    #[derive(Serialize, Clone)]
-    struct SchemaUpdate {
-        #[serde(rename = "serverId")]
-        server_id: String,
-        schemas: Vec<db::Schema>,
+/// Explain `sql` without running it and give a reason for every sequential
+/// scan, based on the filter the planner applied and the table's cached
+/// indexes. The reasons are heuristics meant as hints, not guarantees.
+#[command]
+pub async fn diagnose_seq_scan(
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: Option<String>,
+    sql: String,
+) -> Result<Vec<SeqScanDiagnosis>, AppError> {
+    let statement = sql.trim().trim_end_matches(';');
+    if statement.is_empty() {
+        return Err(AppError::invalid("Query is required"));
     }
 
-    window
-        .emit(
-            "schema_updated",
-            SchemaUpdate {
-                server_id: server_id.clone(),
-                schemas: updated_schemas,
-            },
-        )
-        .map_err(|e: Error| e.to_string())?;
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
 
-    Ok(())
-}
+    let mut client = get_client(&server, &password, &target_database).await?;
+    let tx = client.transaction().await?;
+    if let Some(schema) = schema_name.as_deref() {
+        tx.batch_execute(&format!("SET LOCAL search_path TO {}", quote_ident(schema)))
+            .await?;
+    }
+    let row = tx
+        .query_one(&format!("EXPLAIN (VERBOSE, FORMAT JSON) {}", statement), &[])
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to explain query"))?;
+    tx.rollback().await?;
 
-#[command]
-pub async fn get_query_history(server_id: String) -> Result<Vec<db::QueryHistory>, String> {
-    db::get_query_history(&server_id, 100).map_err(|e| e.to_string())
-}
+    let plan: serde_json::Value = row.get(0);
+    let mut scans = Vec::new();
+    if let Some(root) = plan.get(0).and_then(|entry| entry.get("Plan")) {
+        collect_seq_scans(root, &mut scans);
+    }
 
-#[command]
-pub async fn get_tables(schema_id: String) -> Result<Vec<db::Table>, String> {
-    db::get_tables(&schema_id).map_err(|e| e.to_string())
-}
+    let mut diagnoses = Vec::new();
+    for (schema, table, filter) in scans {
+        let label = format!("{}.{}", schema, table);
+        let cached_table = db::find_schema_id(&server.id, &target_database, &schema)?
+            .map(|schema_id| db::get_tables(&schema_id))
+            .transpose()?
+            .and_then(|tables| tables.into_iter().find(|t| t.name == table));
+
+        let Some(cached_table) = cached_table else {
+            diagnoses.push(SeqScanDiagnosis {
+                table: label,
+                reason: "Table is not in the schema cache; refresh the schema to check its indexes".to_string(),
+            });
+            continue;
+        };
 
-#[command]
-pub async fn get_views(schema_id: String) -> Result<Vec<db::View>, String> {
-    db::get_views(&schema_id).map_err(|e| e.to_string())
-}
+        let Some(filter) = filter else {
+            diagnoses.push(SeqScanDiagnosis {
+                table: label,
+                reason: "Every row is read: the query has no condition on this table that an index could serve".to_string(),
+            });
+            continue;
+        };
 
-#[command]
-pub async fn get_columns(table_id: String) -> Result<Vec<db::Column>, String> {
-    db::get_columns(&table_id).map_err(|e| e.to_string())
-}
+        let columns: Vec<String> = db::get_columns(&cached_table.id)?
+            .into_iter()
+            .map(|column| column.name)
+            .collect();
+        let indexes: Vec<(String, Vec<String>)> = db::get_indexes(&cached_table.id)?
+            .into_iter()
+            .map(|index| {
+                let keys = index_key_columns(&index.definition);
+                (index.name, keys)
+            })
+            .collect();
+
+        let mut seen = std::collections::HashSet::new();
+        for (column, call) in filter_identifiers(&filter) {
+            if !columns.contains(&column) || !seen.insert((column.clone(), call.clone())) {
+                continue;
+            }
+            let column_key = column.to_lowercase();
+
+            let reason = if let Some(function) = call {
+                let leading_key_matches =
+                    |keys: &Vec<String>| keys.first().is_some_and(|key| expression_index_matches(key, &function, &column_key));
+                match indexes.iter().find(|(_, keys)| leading_key_matches(keys)) {
+                    Some((name, _)) => format!(
+                        "Expression index {} on {}({}) exists, but the planner preferred a sequential scan; the table may be small or the condition not selective",
+                        name, function, column
+                    ),
+                    None => format!(
+                        "Column {} is wrapped in {}(), so a plain index on it can't be used; an expression index on {}({}) could",
+                        column, function, function, column
+                    ),
+                }
+            } else if let Some((name, _)) = indexes.iter().find(|(_, keys)| keys.first() == Some(&column_key)) {
+                format!(
+                    "Index {} on {} exists, but the planner preferred a sequential scan; the table may be small, statistics stale (run ANALYZE), or the condition matches many rows",
+                    name, column
+                )
+            } else if let Some((name, _)) = indexes.iter().find(|(_, keys)| keys.contains(&column_key)) {
+                format!(
+                    "Column {} is only a non-leading column of index {}, which can't be searched by {} alone",
+                    column, name, column
+                )
+            } else {
+                format!("Filter on {} has no index", column)
+            };
+
+            diagnoses.push(SeqScanDiagnosis {
+                table: label.clone(),
+                reason,
+            });
+        }
 
-#[command]
-pub async fn get_indexes(table_id: String) -> Result<Vec<db::Index>, String> {
-    let cached = db::get_indexes(&table_id).map_err(|e| e.to_string())?;
-    if !cached.is_empty() {
-        return Ok(cached);
+        if seen.is_empty() {
+            diagnoses.push(SeqScanDiagnosis {
+                table: label,
+                reason: format!("Filter {} doesn't reference an indexable column directly", filter),
+            });
+        }
     }
 
-    let context = db::get_table_context(&table_id).map_err(|e| e.to_string())?;
-    let Some((table_name, schema_name, server_id, database_name)) = context else {
-        return Ok(vec![]);
-    };
-
-    let server = db::get_server_by_id(&server_id)
-        .map_err(|e| e.to_string())?
-        .ok_or("Server not found")?;
+    Ok(diagnoses)
+}
 
-    let password = credentials::retrieve_password(&server.credential_key)
-        .map_err(|e| format!("Failed to retrieve password: {}", e))?;
+/// Split a table's on-disk size into heap, indexes and TOAST.
+#[command]
+pub async fn get_table_size_breakdown(
+    server_id: String,
+    database_name: Option<String>,
+    schema_name: String,
+    table_name: String,
+) -> Result<TableSizeBreakdown, AppError> {
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let client = get_client(&server, &password, &target_database).await?;
+    // pg_table_size includes TOAST, so it's subtracted back out of the heap
+    let row = client
+        .query_opt(
+            "SELECT pg_table_size(c.oid),
+                    pg_indexes_size(c.oid),
+                    COALESCE(pg_total_relation_size(NULLIF(c.reltoastrelid, 0)), 0),
+                    pg_total_relation_size(c.oid)
+             FROM pg_class c
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             WHERE n.nspname = $1 AND c.relname = $2",
+            &[&schema_name, &table_name],
+        )
+        .await
+        .map_err(|e| AppError::from(e).context(format!("Failed to read size of {}", table_name)))?
+        .ok_or_else(|| AppError::not_found(format!("Table {}.{} not found", schema_name, table_name)))?;
 
-    let target_database = if database_name.is_empty() {
-        server.database.clone()
-    } else {
-        database_name
-    };
+    let table_with_toast: i64 = row.get(0);
+    let toast_bytes: i64 = row.get(2);
 
-    let pool = crate::postgres::get_or_create_pool(
-        &server.id,
-        &server.host,
-        server.port as u16,
-        &server.username,
-        &password,
-        &target_database,
-    )
-    .await
-    .map_err(|e| e.to_string())?;
+    Ok(TableSizeBreakdown {
+        table_bytes: table_with_toast - toast_bytes,
+        indexes_bytes: row.get(1),
+        toast_bytes,
+        total_bytes: row.get(3),
+    })
+}
 
-    let client = pool.get().await.map_err(|e| e.to_string())?;
+/// On-disk size and table count of every user schema, largest first.
+#[command]
+pub async fn get_schema_sizes(
+    server_id: String,
+    database_name: Option<String>,
+) -> Result<Vec<SchemaSize>, AppError> {
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
+
+    let client = get_client(&server, &password, &target_database).await?;
+    // Partitions are ordinary tables, so summing relkind 'r' already covers
+    // partitioned data; 'p' parents only add to the count
     let rows = client
         .query(
-            "SELECT indexname, indexdef FROM pg_indexes WHERE schemaname = $1 AND tablename = $2",
-            &[&schema_name, &table_name],
+            "SELECT n.nspname,
+                    COALESCE(SUM(pg_total_relation_size(c.oid)), 0)::bigint,
+                    COUNT(c.oid)
+             FROM pg_namespace n
+             LEFT JOIN pg_class c
+               ON c.relnamespace = n.oid AND c.relkind IN ('r', 'p')
+             WHERE n.nspname NOT IN ('pg_catalog', 'information_schema')
+               AND n.nspname NOT LIKE 'pg_toast%'
+               AND n.nspname NOT LIKE 'pg_temp_%'
+             GROUP BY n.nspname
+             ORDER BY 2 DESC, 1",
+            &[],
         )
         .await
-        .map_err(|e| e.to_string())?;
-
-    let mut indexes: Vec<db::Index> = Vec::new();
-    for row in rows {
-        let name: String = row.get(0);
-        let definition: String = row.get(1);
-        indexes.push(db::Index {
-            id: Uuid::new_v4().to_string(),
-            table_id: table_id.clone(),
-            name,
-            definition,
-        });
-    }
-
-    db::replace_indexes_for_table(&table_id, &indexes).map_err(|e| e.to_string())?;
-
-    Ok(indexes)
+        .map_err(|e| AppError::from(e).context("Failed to read schema sizes"))?;
+
+    Ok(rows
+        .iter()
+        .map(|row| SchemaSize {
+            schema: row.get(0),
+            size_bytes: row.get(1),
+            table_count: row.get(2),
+        })
+        .collect())
 }
 
 #[command]
-pub async fn get_primary_key_columns(
+pub async fn get_constraints(
     server_id: String,
     database_name: Option<String>,
     schema_name: String,
     table_name: String,
-) -> Result<Vec<String>, String> {
-    if schema_name.trim().is_empty() || table_name.trim().is_empty() {
-        return Ok(vec![]);
-    }
-
-    let server = db::get_server_by_id(&server_id)
-        .map_err(|e| e.to_string())?
-        .ok_or("Server not found")?;
-
-    let password = credentials::retrieve_password(&server.credential_key)
-        .map_err(|e| format!("Failed to retrieve password: {}", e))?;
+) -> Result<Vec<ConstraintInfo>, AppError> {
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+    let target_database = resolve_database(&server, database_name);
 
-    let target_database = database_name
-        .filter(|name| !name.trim().is_empty())
-        .unwrap_or_else(|| server.database.clone());
-
-    let pool = crate::postgres::get_or_create_pool(
-        &server.id,
-        &server.host,
-        server.port as u16,
-        &server.username,
-        &password,
-        &target_database,
-    )
-    .await
-    .map_err(|e| e.to_string())?;
-
-    let client = pool.get().await.map_err(|e| e.to_string())?;
+    let client = get_client(&server, &password, &target_database).await?;
     let rows = client
         .query(
-            "SELECT kcu.column_name\n             FROM information_schema.table_constraints tc\n             JOIN information_schema.key_column_usage kcu\n               ON tc.constraint_name = kcu.constraint_name\n              AND tc.table_schema = kcu.table_schema\n              AND tc.table_name = kcu.table_name\n             WHERE tc.constraint_type = 'PRIMARY KEY'\n               AND tc.table_schema = $1\n               AND tc.table_name = $2\n             ORDER BY kcu.ordinal_position",
+            "SELECT con.conname, con.contype::text, pg_get_constraintdef(con.oid),
+                    CASE WHEN con.confrelid <> 0 THEN rn.nspname || '.' || rc.relname END
+             FROM pg_constraint con
+             JOIN pg_class c ON con.conrelid = c.oid
+             JOIN pg_namespace n ON c.relnamespace = n.oid
+             LEFT JOIN pg_class rc ON rc.oid = con.confrelid
+             LEFT JOIN pg_namespace rn ON rn.oid = rc.relnamespace
+             WHERE n.nspname = $1 AND c.relname = $2
+             ORDER BY con.conname",
             &[&schema_name, &table_name],
         )
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| AppError::from(e).context(format!("Failed to read constraints for {}", table_name)))?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let contype: String = row.get(1);
+            let type_ = match contype.as_str() {
+                "p" => "PK",
+                "f" => "FK",
+                "u" => "UNIQUE",
+                "c" => "CHECK",
+                "x" => "EXCLUDE",
+                "t" => "TRIGGER",
+                other => other,
+            };
+            ConstraintInfo {
+                name: row.get(0),
+                type_: type_.to_string(),
+                definition: row.get(2),
+                referenced_table: row.get(3),
+            }
+        })
+        .collect())
+}
 
-    Ok(rows.iter().map(|row| row.get::<_, String>(0)).collect())
+#[command]
+pub async fn get_autocomplete_items(server_id: String) -> Result<db::AutocompleteItems, AppError> {
+    db::get_autocomplete_items(&server_id).map_err(AppError::from)
 }
 
+/// Refresh only the autocomplete name cache, without the full schema tree scan
 #[command]
-pub async fn get_autocomplete_items(server_id: String) -> Result<db::AutocompleteItems, String> {
-    db::get_autocomplete_items(&server_id).map_err(|e| e.to_string())
+pub async fn refresh_autocomplete(server_id: String) -> Result<db::AutocompleteItems, AppError> {
+    let server = load_server(&server_id)?;
+    let password = server_password(&server)?;
+
+    let items = crate::schema::fetch_autocomplete_items(&server, &password)
+        .await?;
+
+    db::replace_autocomplete_items(&server_id, &items)?;
+
+    Ok(items)
 }
 
 #[command]
-pub async fn add_server(server: db::Server, password: String) -> Result<(), String> {
+pub async fn add_server(server: db::Server, password: String) -> Result<(), AppError> {
+    crate::postgres::validate_host(&server.host).map_err(AppError::invalid)?;
+    if let Some(replica_host) = server.replica_host.as_deref().filter(|host| !host.trim().is_empty()) {
+        crate::postgres::validate_host(replica_host)
+            .map_err(|e| AppError::invalid(format!("Replica: {}", e)))?;
+    }
+
     // Store password in credential manager
     credentials::store_password(&server.credential_key, &server.username, &password)
-        .map_err(|e| format!("Failed to store password: {}", e))?;
+        .map_err(|e| AppError::internal(format!("Failed to store password: {}", e)))?;
 
     // Add server to DB
-    db::add_server(&server).map_err(|e| e.to_string())?;
+    db::add_server(&server)?;
 
     Ok(())
 }
@@ -1421,9 +7328,31 @@ pub async fn add_server(server: db::Server, password: String) -> Result<(), Stri
 pub async fn get_query_history_dedup(
     server_id: String,
     limit: Option<usize>,
-) -> Result<Vec<QueryHistoryEntry>, String> {
+) -> Result<Vec<QueryHistoryEntry>, AppError> {
     let limit = limit.unwrap_or(500);
-    db::get_query_history_dedup(&server_id, limit).map_err(|e| e.to_string())
+    db::get_query_history_dedup(&server_id, limit).map_err(AppError::from)
+}
+
+/// Most frequently run queries. `sort` is `frequency` (default) or `score`,
+/// which weights frequency by how recently the query was last run.
+#[command]
+pub async fn get_top_queries(
+    server_id: String,
+    limit: Option<usize>,
+    sort: Option<String>,
+) -> Result<Vec<QueryHistoryEntry>, AppError> {
+    let by_score = match sort.as_deref().unwrap_or("frequency") {
+        "frequency" => false,
+        "score" => true,
+        other => {
+            return Err(AppError::invalid(format!(
+                "Unknown sort '{}': expected frequency or score",
+                other
+            )))
+        }
+    };
+    db::get_top_queries(&server_id, limit.unwrap_or(20), by_score, Utc::now().timestamp())
+        .map_err(AppError::from)
 }
 
 /// Search query history with case-insensitive partial matching
@@ -1432,24 +7361,565 @@ pub async fn search_query_history(
     server_id: String,
     search_term: String,
     limit: Option<usize>,
-) -> Result<Vec<QueryHistoryEntry>, String> {
+) -> Result<Vec<QueryHistoryEntry>, AppError> {
     let limit = limit.unwrap_or(500);
     if search_term.trim().is_empty() {
         // If search term is empty, return all history
-        db::get_query_history_dedup(&server_id, limit).map_err(|e| e.to_string())
+        db::get_query_history_dedup(&server_id, limit).map_err(AppError::from)
     } else {
-        db::search_query_history_dedup(&server_id, &search_term, limit).map_err(|e| e.to_string())
+        db::search_query_history_dedup(&server_id, &search_term, limit).map_err(AppError::from)
     }
 }
 
+/// Save an editor tab; a new tab (no `id`) gets one assigned.
+#[command]
+pub async fn save_tab(
+    id: Option<String>,
+    server_id: String,
+    tab_name: String,
+    sql: String,
+    cursor_pos: i64,
+) -> Result<db::WorkspaceTab, AppError> {
+    let tab = db::WorkspaceTab {
+        id: id.unwrap_or_else(|| Uuid::new_v4().to_string()),
+        server_id,
+        tab_name,
+        sql,
+        cursor_pos,
+        updated_at: Utc::now().timestamp(),
+    };
+    db::save_tab(&tab)?;
+    Ok(tab)
+}
+
+#[command]
+pub async fn get_tabs(server_id: String) -> Result<Vec<db::WorkspaceTab>, AppError> {
+    db::get_tabs(&server_id).map_err(AppError::from)
+}
+
+/// All saved tabs across servers, for restoring the session on startup
+#[command]
+pub async fn get_all_tabs() -> Result<Vec<db::WorkspaceTab>, AppError> {
+    db::get_all_tabs().map_err(AppError::from)
+}
+
+#[command]
+pub async fn delete_tab(tab_id: String) -> Result<(), AppError> {
+    db::delete_tab(&tab_id).map_err(AppError::from)
+}
+
 /// Delete a specific query from history
 #[command]
-pub async fn delete_query_history_entry(entry_id: String) -> Result<(), String> {
-    db::delete_query_history_entry(&entry_id).map_err(|e| e.to_string())
+pub async fn delete_query_history_entry(entry_id: String) -> Result<(), AppError> {
+    db::delete_query_history_entry(&entry_id).map_err(AppError::from)
 }
 
 /// Clear all query history for a server
 #[command]
-pub async fn clear_query_history(server_id: String) -> Result<(), String> {
-    db::clear_query_history_dedup(&server_id).map_err(|e| e.to_string())
+pub async fn clear_query_history(server_id: String) -> Result<(), AppError> {
+    db::clear_query_history_dedup(&server_id).map_err(AppError::from)
+}
+
+/// Clear query history for all servers
+#[command]
+pub async fn clear_all_history() -> Result<(), AppError> {
+    db::clear_all_history().map_err(AppError::from)
+}
+
+/// Reclaim unused space in the local database file
+#[command]
+pub async fn vacuum_local_db() -> Result<(), AppError> {
+    db::vacuum_local_db().map_err(AppError::from)
+}
+
+/// Export a server's deduplicated history to a `json` or `csv` file
+#[command]
+pub async fn export_query_history(
+    server_id: String,
+    output_path: String,
+    format: String,
+) -> Result<QueryResult, AppError> {
+    let entries: Vec<HistoryExportEntry> =
+        db::get_query_history_dedup(&server_id, db::MAX_DEDUP_HISTORY_ENTRIES)?
+            .into_iter()
+            .map(|entry| HistoryExportEntry {
+                sql: entry.sql,
+                normalized_sql: entry.normalized_sql,
+                last_executed_at: entry.last_executed_at,
+                execution_count: entry.execution_count,
+            })
+            .collect();
+
+    let mut file = File::create(&output_path)
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to create export file"))?;
+
+    match format.to_lowercase().as_str() {
+        "json" => {
+            let json = serde_json::to_string_pretty(&entries)
+                .map_err(|e| AppError::internal(e.to_string()))?;
+            write_str(&mut file, &json).await?;
+        }
+        "csv" => {
+            write_str(&mut file, "sql,normalized_sql,last_executed_at,execution_count\n").await?;
+            for entry in &entries {
+                write_str(
+                    &mut file,
+                    &format!(
+                        "{},{},{},{}\n",
+                        csv_field(&entry.sql),
+                        csv_field(&entry.normalized_sql),
+                        entry.last_executed_at,
+                        entry.execution_count
+                    ),
+                )
+                .await?;
+            }
+        }
+        other => {
+            return Err(AppError::invalid(format!(
+                "Unsupported history format '{}': expected json or csv",
+                other
+            )))
+        }
+    }
+
+    file.flush()
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to finalize export file"))?;
+
+    Ok(QueryResult {
+        columns: vec![],
+        rows: vec![],
+        rows_affected: Some(entries.len()),
+        message: Some(format!("Exported {} history entries to {}", entries.len(), output_path)),
+    })
+}
+
+/// Write the statements successfully run on a server since `since_timestamp`
+/// (Unix seconds) to a `.sql` script, in execution order, so an interactive
+/// session can be replayed.
+#[command]
+pub async fn export_session_script(
+    server_id: String,
+    since_timestamp: i64,
+    output_path: String,
+) -> Result<QueryResult, AppError> {
+    let server = load_server(&server_id)?;
+    let entries = db::get_session_history(&server_id, since_timestamp)?;
+    if entries.is_empty() {
+        return Err(AppError::not_found("No successful queries since the given time"));
+    }
+
+    let mut file = File::create(&output_path)
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to create script file"))?;
+
+    write_str(&mut file, "-- FastDB session script\n").await?;
+    write_str(&mut file, &format!("-- Server: {}\n", server.name)).await?;
+    write_str(&mut file, &format!("-- Statements: {}\n\n", entries.len())).await?;
+
+    for entry in &entries {
+        let statement = entry.sql.trim().trim_end_matches(';').trim_end();
+        if statement.is_empty() {
+            continue;
+        }
+        // Keep the terminator out of a trailing line comment
+        let terminator = if statement.lines().last().is_some_and(|line| line.contains("--")) {
+            "\n;"
+        } else {
+            ";"
+        };
+        write_str(&mut file, &format!("{}{}\n\n", statement, terminator)).await?;
+    }
+
+    file.flush()
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to finalize script file"))?;
+
+    Ok(QueryResult {
+        columns: vec![],
+        rows: vec![],
+        rows_affected: Some(entries.len()),
+        message: Some(format!("Wrote {} statements to {}", entries.len(), output_path)),
+    })
+}
+
+/// Import history previously written by `export_query_history`, merging
+/// execution counts into existing entries with the same normalized SQL
+#[command]
+pub async fn import_query_history(
+    server_id: String,
+    input_path: String,
+    format: String,
+) -> Result<QueryResult, AppError> {
+    let contents = tokio::fs::read_to_string(&input_path)
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read history file"))?;
+
+    let entries: Vec<HistoryExportEntry> = match format.to_lowercase().as_str() {
+        "json" => serde_json::from_str(&contents)
+            .map_err(|e| AppError::invalid(format!("Invalid history file: {}", e)))?,
+        "csv" => parse_csv(&contents)
+            .into_iter()
+            .skip(1)
+            .filter(|record| record.len() >= 4)
+            .map(|record| {
+                Ok(HistoryExportEntry {
+                    sql: record[0].clone(),
+                    normalized_sql: record[1].clone(),
+                    last_executed_at: record[2].trim().parse().map_err(|_| {
+                        AppError::invalid(format!("Invalid timestamp '{}'", record[2]))
+                    })?,
+                    execution_count: record[3].trim().parse().map_err(|_| {
+                        AppError::invalid(format!("Invalid execution count '{}'", record[3]))
+                    })?,
+                })
+            })
+            .collect::<Result<Vec<_>, AppError>>()?,
+        other => {
+            return Err(AppError::invalid(format!(
+                "Unsupported history format '{}': expected json or csv",
+                other
+            )))
+        }
+    };
+
+    for entry in &entries {
+        db::merge_query_history_dedup(
+            &server_id,
+            &entry.sql,
+            entry.last_executed_at,
+            entry.execution_count.max(1),
+        )?;
+    }
+
+    Ok(QueryResult {
+        columns: vec![],
+        rows: vec![],
+        rows_affected: Some(entries.len()),
+        message: Some(format!("Imported {} history entries", entries.len())),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_identifiers_tracks_wrapping_function() {
+        let found = filter_identifiers("((lower(u.email) = 'a''b'::text) AND (u.status = 'active'::character varying))");
+
+        assert_eq!(
+            found,
+            vec![
+                ("email".to_string(), Some("lower".to_string())),
+                ("status".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_index_key_columns() {
+        assert_eq!(
+            index_key_columns("CREATE INDEX idx ON public.users USING btree (lower((email)::text), \"Status\")"),
+            vec!["lower((email)::text)".to_string(), "status".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_expression_index_matches_pg_indexes_form() {
+        assert_eq!(normalize_index_expression("lower((email)::text)"), "lower(email)");
+        assert_eq!(
+            normalize_index_expression("date_trunc('day'::text, (created_at)::timestamp without time zone)"),
+            "date_trunc('day',created_at)"
+        );
+
+        assert!(expression_index_matches("lower((email)::text)", "lower", "email"));
+        assert!(expression_index_matches("lower((\"Email\")::character varying)", "lower", "email"));
+        assert!(expression_index_matches("date_trunc('day'::text, created_at)", "date_trunc", "created_at"));
+        assert!(!expression_index_matches("lower((name)::text)", "lower", "email"));
+        assert!(!expression_index_matches("upper((email)::text)", "lower", "email"));
+    }
+
+    #[test]
+    fn test_mask_dollar_quotes_skips_params_strings_and_comments() {
+        let (masked, bodies) = mask_dollar_quotes(
+            "CREATE FUNCTION f() RETURNS int AS $fn$ SELECT  1 $fn$ LANGUAGE sql;\n-- $$ in a comment\nSELECT $1, '$x$'",
+        );
+
+        assert_eq!(
+            masked,
+            "CREATE FUNCTION f() RETURNS int AS '__fastdb_dollar_quote_0__' LANGUAGE sql;\n-- $$ in a comment\nSELECT $1, '$x$'"
+        );
+        assert_eq!(bodies, vec!["$fn$ SELECT  1 $fn$".to_string()]);
+    }
+
+    #[test]
+    fn test_pretty_print_sql() {
+        assert_eq!(
+            pretty_print_sql("select a,b from t where x=1", sqlformat::Indent::Spaces(2), true),
+            "SELECT\n  a,\n  b\nFROM\n  t\nWHERE\n  x = 1"
+        );
+
+        let body = "$$\nbegin\n    perform  1;\nend\n$$";
+        let formatted = pretty_print_sql(&format!("do {}", body), sqlformat::Indent::Spaces(4), true);
+        assert!(formatted.contains(body));
+    }
+
+    #[test]
+    fn test_data_copy_query_applies_anonymize_rules() {
+        let columns: Vec<String> = ["id", "email", "Phone"].iter().map(|name| quote_ident(name)).collect();
+        let rules = vec![
+            AnonymizeColumn {
+                table: "users".to_string(),
+                column: "email".to_string(),
+                strategy: AnonymizeStrategy::Fake,
+            },
+            AnonymizeColumn {
+                table: "users".to_string(),
+                column: "Phone".to_string(),
+                strategy: AnonymizeStrategy::Null,
+            },
+        ];
+
+        assert_eq!(
+            data_copy_query("\"public\"", "\"orders\"", "orders", &columns, &rules),
+            r#"COPY "public"."orders" ("id", "email", "Phone") TO STDOUT"#
+        );
+        assert_eq!(
+            data_copy_query("\"public\"", "\"users\"", "users", &columns, &rules),
+            r#"COPY (SELECT "id", 'email_' || substr(md5("email"::text), 1, 8) AS "email", NULL AS "Phone" FROM "public"."users") TO STDOUT"#
+        );
+
+        let exported = [("users", columns.as_slice())];
+        assert!(check_anonymize_rules(&rules, exported).is_ok());
+        let typo = AnonymizeColumn {
+            table: "users".to_string(),
+            column: "phone".to_string(),
+            strategy: AnonymizeStrategy::Hash,
+        };
+        let err = check_anonymize_rules(&[typo], exported).unwrap_err();
+        assert!(err.message().contains("users.phone"));
+    }
+
+    #[test]
+    fn test_split_simple_insert() {
+        assert_eq!(
+            split_simple_insert("INSERT INTO public.t (a, \"values\") VALUES (1, 'it''s'), (2, NULL)"),
+            Some(("INSERT INTO public.t (a, \"values\") VALUES", "(1, 'it''s'), (2, NULL)"))
+        );
+        assert_eq!(
+            split_simple_insert("insert into t values (now(), ')')"),
+            Some(("insert into t values", "(now(), ')')"))
+        );
+
+        assert_eq!(split_simple_insert("INSERT INTO t VALUES (1) ON CONFLICT DO NOTHING"), None);
+        assert_eq!(split_simple_insert("INSERT INTO t VALUES (1) RETURNING id"), None);
+        assert_eq!(split_simple_insert("INSERT INTO t SELECT * FROM s"), None);
+        assert_eq!(split_simple_insert("INSERT INTO t DEFAULT VALUES"), None);
+        assert_eq!(split_simple_insert("INSERT INTO t AS x VALUES (1)"), None);
+        assert_eq!(split_simple_insert("INSERT INTO t VALUES (E'a\\'b')"), None);
+        assert_eq!(split_simple_insert("INSERT INTO t VALUES ($$x$$)"), None);
+        assert_eq!(split_simple_insert("UPDATE t SET a = 1"), None);
+    }
+
+    fn feed(buffer: &mut CopyBuffer, text: &str) -> Vec<CopyStep> {
+        text.chars()
+            .map(|ch| buffer.push(ch))
+            .filter(|step| *step != CopyStep::More)
+            .collect()
+    }
+
+    #[test]
+    fn test_copy_terminator_right_after_flush() {
+        // "1\ta\n" fills the 4-byte chunk just before the terminator line
+        let mut buffer = CopyBuffer::new(4);
+        assert_eq!(feed(&mut buffer, "1\ta\n"), vec![CopyStep::Flush]);
+        assert_eq!(std::mem::take(&mut buffer.chunk), b"1\ta\n");
+        assert_eq!(feed(&mut buffer, "\\.\n"), vec![CopyStep::End]);
+        assert!(buffer.chunk.is_empty());
+
+        // Same boundary with CRLF endings and the terminator split across reads
+        let mut buffer = CopyBuffer::new(4);
+        assert_eq!(feed(&mut buffer, "1\ta\r\n\\"), vec![CopyStep::Flush]);
+        buffer.chunk.clear();
+        assert_eq!(feed(&mut buffer, ".\r\n"), vec![CopyStep::End]);
+    }
+
+    #[test]
+    fn test_copy_buffer_chunks_and_eof_terminator() {
+        let mut buffer = CopyBuffer::new(1024);
+        assert!(feed(&mut buffer, "1\ta\n\n2\tb\n").is_empty());
+        assert_eq!(buffer.chunk, b"1\ta\n2\tb\n");
+        assert!(feed(&mut buffer, "\\.").is_empty());
+        assert!(buffer.ends_at_eof());
+
+        let mut buffer = CopyBuffer::new(1024);
+        feed(&mut buffer, "1\ta");
+        assert!(!buffer.ends_at_eof());
+    }
+
+    #[test]
+    fn test_transaction_control() {
+        assert_eq!(transaction_control("begin"), Some(true));
+        assert_eq!(transaction_control("start transaction isolation level serializable"), Some(true));
+        assert_eq!(transaction_control("commit"), Some(false));
+        assert_eq!(transaction_control("rollback"), Some(false));
+        assert_eq!(transaction_control("rollback to savepoint s1"), None);
+        assert_eq!(transaction_control("insert into t values (1)"), None);
+    }
+
+    #[test]
+    fn test_session_command_tag() {
+        assert_eq!(session_command_tag("set search_path to app").as_deref(), Some("SET"));
+        assert_eq!(session_command_tag("reset all;").as_deref(), Some("RESET"));
+        assert_eq!(session_command_tag("discard all;").as_deref(), Some("DISCARD ALL"));
+        assert_eq!(session_command_tag("select set_config('a', 'b', false)"), None);
+        assert_eq!(session_command_tag("settings"), None);
+    }
+
+    #[test]
+    fn test_parse_message_level() {
+        assert_eq!(parse_message_level(" DEBUG1 ").unwrap(), "debug1");
+        assert_eq!(parse_message_level("warning").unwrap(), "warning");
+        assert!(parse_message_level("info; DROP TABLE x").is_err());
+        assert!(parse_message_level("fatal").is_err());
+    }
+
+    #[test]
+    fn test_insert_values_sql() {
+        let casts = vec![None, Some("\"pg_catalog\".\"date\"".to_string())];
+        assert_eq!(
+            insert_values_sql("\"public\".\"t\"", "\"a\", \"b\"", 2, &casts),
+            "INSERT INTO \"public\".\"t\" (\"a\", \"b\") VALUES ($1, $2::text::\"pg_catalog\".\"date\"), ($3, $4::text::\"pg_catalog\".\"date\")"
+        );
+    }
+
+    #[test]
+    fn test_benchmark_stats() {
+        let stats = benchmark_stats(vec![4.0, 1.0, 3.0, 2.0]);
+        assert_eq!(stats.min, 1.0);
+        assert_eq!(stats.max, 4.0);
+        assert_eq!(stats.mean, 2.5);
+        assert_eq!(stats.median, 2.5);
+        assert_eq!(stats.p95, 4.0);
+        assert_eq!(stats.durations, vec![4.0, 1.0, 3.0, 2.0]);
+    }
+
+    #[test]
+    fn test_add_tablesample() {
+        assert_eq!(
+            add_tablesample("SELECT * FROM big_table WHERE a = 1;", 10.0).as_deref(),
+            Some("SELECT * FROM big_table TABLESAMPLE SYSTEM (10) WHERE a = 1")
+        );
+        assert_eq!(
+            add_tablesample("select a from public.\"Events\" e order by a", 0.5).as_deref(),
+            Some("select a from public.\"Events\" e TABLESAMPLE SYSTEM (0.5) order by a")
+        );
+        assert_eq!(
+            add_tablesample("SELECT count(*) FROM t AS x", 1.0).as_deref(),
+            Some("SELECT count(*) FROM t AS x TABLESAMPLE SYSTEM (1)")
+        );
+        assert_eq!(
+            add_tablesample("SELECT * FROM t WHERE note = 'from a join b'", 5.0).as_deref(),
+            Some("SELECT * FROM t TABLESAMPLE SYSTEM (5) WHERE note = 'from a join b'")
+        );
+
+        assert_eq!(add_tablesample("SELECT * FROM a JOIN b ON a.id = b.id", 10.0), None);
+        assert_eq!(add_tablesample("SELECT * FROM a, b", 10.0), None);
+        assert_eq!(add_tablesample("SELECT * FROM (SELECT 1) s", 10.0), None);
+        assert_eq!(add_tablesample("SELECT * FROM generate_series(1, 10)", 10.0), None);
+        assert_eq!(add_tablesample("SELECT 1 UNION SELECT 2 FROM t", 10.0), None);
+    }
+
+    #[test]
+    fn test_infer_json_paths() {
+        let values = vec![
+            serde_json::json!({"id": 1, "tags": ["a", "b"], "address": {"city": "Oslo"}}),
+            serde_json::json!({"id": "x2", "tags": [], "odd key": null}),
+        ];
+        let summary = infer_json_paths(&values);
+        let find = |path: &str| summary.iter().find(|s| s.path == path).unwrap();
+
+        assert_eq!(find("$").types, vec!["object"]);
+        assert_eq!(find("$.id").types, vec!["number", "string"]);
+        assert_eq!(find("$.id").present_in_percent, 100.0);
+        assert_eq!(find("$.tags[*]").types, vec!["string"]);
+        assert_eq!(find("$.tags[*]").present_in_percent, 50.0);
+        assert_eq!(find("$.address.city").present_in_percent, 50.0);
+        assert_eq!(find("$.\"odd key\"").types, vec!["null"]);
+    }
+
+    #[test]
+    fn test_plan_estimate_uses_largest_node_rows() {
+        let plan = serde_json::json!([{
+            "Plan": {
+                "Node Type": "ModifyTable",
+                "Total Cost": 4250.5,
+                "Plan Rows": 0,
+                "Plans": [{"Node Type": "Seq Scan", "Total Cost": 4250.5, "Plan Rows": 180000}]
+            }
+        }]);
+        assert_eq!(plan_estimate(&plan), Some((4250.5, 180000.0)));
+        assert_eq!(plan_estimate(&serde_json::json!([])), None);
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*.sql", "001_init.sql"));
+        assert!(wildcard_match("0??_*.sql", "002_users.sql"));
+        assert!(wildcard_match("*", "anything"));
+        assert!(!wildcard_match("*.sql", "notes.txt"));
+        assert!(!wildcard_match("*.sql", "dump.sql.bak"));
+        assert!(!wildcard_match("0??_*.sql", "1_users.sql"));
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 bytes");
+        assert_eq!(format_bytes(1536), "1.5 kB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.0 GB");
+    }
+
+    #[test]
+    fn test_ddl_affected_tables() {
+        let named = |schema: Option<&str>, table: &str| (schema.map(str::to_string), table.to_string());
+
+        assert_eq!(
+            ddl_affected_tables("-- new\nCREATE TABLE IF NOT EXISTS \"Sales\".Orders (id int)"),
+            Some(vec![named(Some("Sales"), "orders")])
+        );
+        assert_eq!(
+            ddl_affected_tables("drop table if exists a, public.b cascade"),
+            Some(vec![named(None, "a"), named(Some("public"), "b")])
+        );
+        assert_eq!(
+            ddl_affected_tables("ALTER TABLE ONLY s.x RENAME TO y"),
+            Some(vec![named(Some("s"), "x"), named(Some("s"), "y")])
+        );
+        assert_eq!(
+            ddl_affected_tables("alter table x set schema archive"),
+            Some(vec![named(None, "x"), named(Some("archive"), "x")])
+        );
+        assert_eq!(ddl_affected_tables("alter table t add column c int"), Some(vec![named(None, "t")]));
+        assert_eq!(ddl_affected_tables("CREATE TEMP TABLE scratch (id int)"), Some(vec![]));
+        assert_eq!(ddl_affected_tables("SELECT * FROM t"), Some(vec![]));
+        assert_eq!(ddl_affected_tables("drop table a;"), Some(vec![named(None, "a")]));
+        assert_eq!(ddl_affected_tables("insert into t values (';'); update t set x = 1;"), Some(vec![]));
+        assert_eq!(ddl_affected_tables("DROP TABLE a; DROP TABLE b;"), None);
+        assert_eq!(ddl_affected_tables("insert into t values (1); alter table t add column c int"), None);
+    }
+
+    #[test]
+    fn test_histogram_buckets() {
+        let buckets = histogram_buckets(0.0, 10.0, 4, &[(1, 3), (4, 2)]);
+        let ranges: Vec<(f64, f64, i64)> = buckets.iter().map(|b| (b.range_start, b.range_end, b.count)).collect();
+        assert_eq!(ranges, vec![(0.0, 2.5, 3), (2.5, 5.0, 0), (5.0, 7.5, 0), (7.5, 10.0, 2)]);
+
+        assert_eq!(
+            histogram_buckets(5.0, 5.0, 10, &[(1, 7)]),
+            vec![HistogramBucket { range_start: 5.0, range_end: 5.0, count: 7 }]
+        );
+    }
 }
@@ -1,7 +1,7 @@
 use once_cell::sync::Lazy;
 use rusqlite::{params, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard};
 
 // Global SQLite connection with optimized settings
 static DB: Lazy<Arc<Mutex<Connection>>> = Lazy::new(|| {
@@ -17,13 +17,37 @@ static DB: Lazy<Arc<Mutex<Connection>>> = Lazy::new(|| {
          PRAGMA temp_store = MEMORY;
          PRAGMA mmap_size = 30000000000;
          PRAGMA page_size = 4096;
-         PRAGMA cache_size = -64000;",
+         PRAGMA cache_size = -64000;
+         PRAGMA busy_timeout = 5000;",
     )
     .expect("Failed to set pragmas");
 
     Arc::new(Mutex::new(conn))
 });
 
+/// Lock the global connection, recovering if an earlier panic poisoned the
+/// mutex. The connection itself stays usable: an open rusqlite `Transaction`
+/// rolls back when dropped during unwinding, so no half-applied state remains.
+fn lock_db() -> MutexGuard<'static, Connection> {
+    DB.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// How long SQLite waits on a locked database file (e.g. a WAL checkpoint)
+/// before failing with `SQLITE_BUSY`.
+pub fn set_busy_timeout(ms: u64) -> Result<(), rusqlite::Error> {
+    let conn = lock_db();
+    conn.busy_timeout(std::time::Duration::from_millis(ms))
+}
+
+/// Fold the WAL back into the main database file and truncate it, so the
+/// file is left clean on exit. Returns false when readers or writers kept
+/// the checkpoint from completing.
+pub fn checkpoint_wal() -> Result<bool, rusqlite::Error> {
+    let conn = lock_db();
+    let busy: i64 = conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| row.get(0))?;
+    Ok(busy == 0)
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Server {
     pub id: String,
@@ -35,6 +59,67 @@ pub struct Server {
     pub credential_key: String,
     pub group_name: Option<String>,
     pub last_connected: Option<i64>,
+    /// Connect/checkout timeout; `None` uses `DEFAULT_CONNECT_TIMEOUT_MS`
+    #[serde(default)]
+    pub connect_timeout_ms: Option<i64>,
+    /// `session` (default) or `transaction` for servers behind PgBouncer in
+    /// transaction-pooling mode. See `Server::uses_transaction_pooling` for
+    /// what doesn't work in that mode.
+    #[serde(default)]
+    pub pooling_mode: Option<String>,
+    /// 1 when pinned to the top of the server list
+    #[serde(default)]
+    pub favorite: i32,
+    /// Optional read replica; read-only queries run there unless forced to the primary
+    #[serde(default)]
+    pub replica_host: Option<String>,
+    /// Replica port; `None` reuses `port`
+    #[serde(default)]
+    pub replica_port: Option<i32>,
+    /// Seconds idle before TCP keepalive probes start; `None` uses
+    /// `DEFAULT_KEEPALIVE_IDLE_SECS`, 0 turns keepalives off
+    #[serde(default)]
+    pub keepalive_idle_secs: Option<i64>,
+    /// 1 to have `execute_query` ask before running statements the planner
+    /// estimates as expensive
+    #[serde(default)]
+    pub confirm_expensive: i32,
+    /// Estimated total cost above which confirmation is needed; `None` uses
+    /// `DEFAULT_EXPENSIVE_COST`
+    #[serde(default)]
+    pub expensive_cost_threshold: Option<f64>,
+    /// Estimated row count above which confirmation is needed; `None` uses
+    /// `DEFAULT_EXPENSIVE_ROWS`
+    #[serde(default)]
+    pub expensive_rows_threshold: Option<i64>,
+    /// Session time zone for every connection (e.g. `Europe/Berlin`), which
+    /// `timestamptz` values are shown in; `None` keeps the server's default
+    #[serde(default)]
+    pub timezone: Option<String>,
+}
+
+pub const DEFAULT_CONNECT_TIMEOUT_MS: i64 = 10_000;
+pub const DEFAULT_KEEPALIVE_IDLE_SECS: i64 = 30;
+pub const DEFAULT_EXPENSIVE_COST: f64 = 1_000_000.0;
+pub const DEFAULT_EXPENSIVE_ROWS: i64 = 1_000_000;
+
+impl Server {
+    /// True when the server sits behind a transaction-pooling proxy. Each
+    /// transaction may then land on a different backend, so FastDB runs every
+    /// statement inside an explicit transaction and never relies on session
+    /// state. Limitations: session-level `SET`, `LISTEN`, advisory locks,
+    /// temporary tables and `WITH HOLD` cursors don't survive between
+    /// statements, and named prepared statements only work on PgBouncer 1.21+
+    /// with `max_prepared_statements` enabled. Settings made at login would only
+    /// reach one backend (and PgBouncer rejects the `options` startup
+    /// parameter), so the preview role and `timezone` are applied with
+    /// `SET LOCAL` in each transaction instead.
+    pub fn uses_transaction_pooling(&self) -> bool {
+        self.pooling_mode
+            .as_deref()
+            .map(|mode| mode.eq_ignore_ascii_case("transaction"))
+            .unwrap_or(false)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -62,6 +147,15 @@ pub struct Column {
     pub name: String,
     pub data_type: String,
     pub nullable: i32,
+    /// 1-based position in the table, matching `SELECT *` order
+    #[serde(default)]
+    pub ordinal_position: i32,
+    /// 1 for identity and serial (`nextval` default) columns
+    #[serde(default)]
+    pub is_identity: i32,
+    /// 1 for `GENERATED ALWAYS AS (...) STORED` columns
+    #[serde(default)]
+    pub is_generated: i32,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -79,7 +173,15 @@ pub struct View {
     pub name: String,
 }
 
+/// A user-defined enum type and its labels in `enumsortorder` order.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EnumType {
+    pub schema_id: String,
+    pub name: String,
+    pub labels: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct AutocompleteItems {
     pub tables: Vec<String>,
     pub columns: Vec<String>,
@@ -94,6 +196,21 @@ pub struct QueryHistory {
     pub sql: String,
     pub executed_at: i64,
     pub success: i32,
+    /// OS user that ran the query; `None` when it couldn't be determined
+    #[serde(default)]
+    pub executed_by: Option<String>,
+}
+
+/// An editor tab persisted so it can be reopened after a restart.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WorkspaceTab {
+    pub id: String,
+    pub server_id: String,
+    pub tab_name: String,
+    pub sql: String,
+    /// Cursor offset in `sql`
+    pub cursor_pos: i64,
+    pub updated_at: i64,
 }
 
 /// Deduplicated query history entry
@@ -111,8 +228,19 @@ pub struct QueryHistoryEntry {
     pub execution_count: i64,
 }
 
+/// Add a column to an existing table, ignoring the error if it is already there.
+fn add_column_if_missing(conn: &Connection, table: &str, column_def: &str) -> Result<(), rusqlite::Error> {
+    if let Err(err) = conn.execute(&format!("ALTER TABLE {} ADD COLUMN {}", table, column_def), []) {
+        let err_str = err.to_string();
+        if !err_str.contains("duplicate column name") {
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
 pub fn init_db() -> Result<(), rusqlite::Error> {
-    let conn = DB.lock().unwrap();
+    let conn = lock_db();
 
     conn.execute_batch(
         r#"
@@ -157,6 +285,9 @@ pub fn init_db() -> Result<(), rusqlite::Error> {
             name TEXT NOT NULL,
             data_type TEXT NOT NULL,
             nullable INTEGER NOT NULL,
+            ordinal_position INTEGER NOT NULL DEFAULT 0,
+            is_identity INTEGER NOT NULL DEFAULT 0,
+            is_generated INTEGER NOT NULL DEFAULT 0,
             FOREIGN KEY (table_id) REFERENCES tables(id) ON DELETE CASCADE
         );
 
@@ -181,6 +312,15 @@ pub fn init_db() -> Result<(), rusqlite::Error> {
 
         CREATE INDEX IF NOT EXISTS idx_views_schema_name ON views(schema_id, name);
 
+        CREATE TABLE IF NOT EXISTS enum_values (
+            schema_id TEXT NOT NULL,
+            type_name TEXT NOT NULL,
+            position INTEGER NOT NULL,
+            label TEXT NOT NULL,
+            PRIMARY KEY (schema_id, type_name, position),
+            FOREIGN KEY (schema_id) REFERENCES schemas(id) ON DELETE CASCADE
+        ) WITHOUT ROWID;
+
         CREATE TABLE IF NOT EXISTS query_history (
             id TEXT PRIMARY KEY,
             server_id TEXT NOT NULL,
@@ -210,19 +350,55 @@ pub fn init_db() -> Result<(), rusqlite::Error> {
 
         CREATE UNIQUE INDEX IF NOT EXISTS idx_query_history_dedup_normalized
             ON query_history_dedup(server_id, normalized_sql);
+
+        -- Open editor tabs, restored on startup in tab order
+        CREATE TABLE IF NOT EXISTS workspace (
+            id TEXT PRIMARY KEY,
+            server_id TEXT NOT NULL,
+            tab_name TEXT NOT NULL,
+            sql TEXT NOT NULL,
+            cursor_pos INTEGER NOT NULL DEFAULT 0,
+            updated_at INTEGER NOT NULL,
+            position INTEGER NOT NULL DEFAULT 0,
+            FOREIGN KEY (server_id) REFERENCES servers(id) ON DELETE CASCADE
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_workspace_server ON workspace(server_id);
+
+        -- Application-wide settings as key/value pairs
+        CREATE TABLE IF NOT EXISTS app_settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );
+
+        -- Lightweight autocomplete cache, refreshed independently of the schema tree
+        CREATE TABLE IF NOT EXISTS autocomplete_items (
+            server_id TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            name TEXT NOT NULL,
+            PRIMARY KEY (server_id, kind, name),
+            FOREIGN KEY (server_id) REFERENCES servers(id) ON DELETE CASCADE
+        ) WITHOUT ROWID;
         "#
     )?;
 
-    // Ensure database_name column exists for older installs
-    if let Err(err) = conn.execute(
-        "ALTER TABLE schemas ADD COLUMN database_name TEXT NOT NULL DEFAULT ''",
-        [],
-    ) {
-        let err_str = err.to_string();
-        if !err_str.contains("duplicate column name") {
-            return Err(err);
-        }
-    }
+    // Ensure columns added after the initial release exist for older installs
+    add_column_if_missing(&conn, "schemas", "database_name TEXT NOT NULL DEFAULT ''")?;
+    add_column_if_missing(&conn, "servers", "connect_timeout_ms INTEGER")?;
+    add_column_if_missing(&conn, "servers", "pooling_mode TEXT")?;
+    add_column_if_missing(&conn, "servers", "favorite INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(&conn, "servers", "replica_host TEXT")?;
+    add_column_if_missing(&conn, "servers", "replica_port INTEGER")?;
+    add_column_if_missing(&conn, "servers", "keepalive_idle_secs INTEGER")?;
+    add_column_if_missing(&conn, "servers", "confirm_expensive INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(&conn, "servers", "expensive_cost_threshold REAL")?;
+    add_column_if_missing(&conn, "servers", "expensive_rows_threshold INTEGER")?;
+    add_column_if_missing(&conn, "servers", "timezone TEXT")?;
+    add_column_if_missing(&conn, "columns", "ordinal_position INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(&conn, "columns", "is_identity INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(&conn, "columns", "is_generated INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(&conn, "query_history", "executed_by TEXT")?;
+    add_column_if_missing(&conn, "workspace", "position INTEGER NOT NULL DEFAULT 0")?;
 
     // Create index after ensuring database_name exists
     conn.execute(
@@ -233,62 +409,86 @@ pub fn init_db() -> Result<(), rusqlite::Error> {
     Ok(())
 }
 
+// Settings operations
+
+/// When "true", history entries are stored pretty-printed.
+pub const SETTING_FORMAT_HISTORY_SQL: &str = "format_history_sql";
+
+pub fn get_setting(key: &str) -> Result<Option<String>, rusqlite::Error> {
+    let conn = lock_db();
+    let mut stmt = conn.prepare_cached("SELECT value FROM app_settings WHERE key = ?")?;
+    stmt.query_row([key], |row| row.get(0)).optional()
+}
+
+pub fn set_setting(key: &str, value: &str) -> Result<(), rusqlite::Error> {
+    let conn = lock_db();
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO app_settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+    )?;
+    stmt.execute(params![key, value])?;
+    Ok(())
+}
+
 // Server operations
+const SERVER_COLUMNS: &str =
+    "id, name, host, port, database, username, credential_key, group_name, last_connected, connect_timeout_ms, pooling_mode, favorite, replica_host, replica_port, keepalive_idle_secs, confirm_expensive, expensive_cost_threshold, expensive_rows_threshold, timezone";
+
+fn server_from_row(row: &rusqlite::Row) -> Result<Server, rusqlite::Error> {
+    Ok(Server {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        host: row.get(2)?,
+        port: row.get(3)?,
+        database: row.get(4)?,
+        username: row.get(5)?,
+        credential_key: row.get(6)?,
+        group_name: row.get(7)?,
+        last_connected: row.get(8)?,
+        connect_timeout_ms: row.get(9)?,
+        pooling_mode: row.get(10)?,
+        favorite: row.get(11)?,
+        replica_host: row.get(12)?,
+        replica_port: row.get(13)?,
+        keepalive_idle_secs: row.get(14)?,
+        confirm_expensive: row.get(15)?,
+        expensive_cost_threshold: row.get(16)?,
+        expensive_rows_threshold: row.get(17)?,
+        timezone: row.get(18)?,
+    })
+}
+
 pub fn get_servers() -> Result<Vec<Server>, rusqlite::Error> {
-    let conn = DB.lock().unwrap();
-    let mut stmt = conn.prepare_cached(
-        "SELECT id, name, host, port, database, username, credential_key, group_name, last_connected
+    let conn = lock_db();
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {}
          FROM servers
-         ORDER BY last_connected DESC NULLS LAST, name"
-    )?;
+         ORDER BY favorite DESC, last_connected DESC NULLS LAST, name",
+        SERVER_COLUMNS
+    ))?;
 
     let servers = stmt
-        .query_map([], |row| {
-            Ok(Server {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                host: row.get(2)?,
-                port: row.get(3)?,
-                database: row.get(4)?,
-                username: row.get(5)?,
-                credential_key: row.get(6)?,
-                group_name: row.get(7)?,
-                last_connected: row.get(8)?,
-            })
-        })?
+        .query_map([], server_from_row)?
         .collect::<Result<Vec<_>, _>>()?;
 
     Ok(servers)
 }
 
 pub fn get_server_by_id(server_id: &str) -> Result<Option<Server>, rusqlite::Error> {
-    let conn = DB.lock().unwrap();
-    let mut stmt = conn.prepare_cached(
-        "SELECT id, name, host, port, database, username, credential_key, group_name, last_connected
-         FROM servers WHERE id = ?"
-    )?;
-
-    stmt.query_row([server_id], |row| {
-        Ok(Server {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            host: row.get(2)?,
-            port: row.get(3)?,
-            database: row.get(4)?,
-            username: row.get(5)?,
-            credential_key: row.get(6)?,
-            group_name: row.get(7)?,
-            last_connected: row.get(8)?,
-        })
-    })
-    .optional()
+    let conn = lock_db();
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {} FROM servers WHERE id = ?",
+        SERVER_COLUMNS
+    ))?;
+
+    stmt.query_row([server_id], server_from_row).optional()
 }
 
 pub fn add_server(server: &Server) -> Result<(), rusqlite::Error> {
-    let conn = DB.lock().unwrap();
+    let conn = lock_db();
     let mut stmt = conn.prepare_cached(
-        "INSERT INTO servers (id, name, host, port, database, username, credential_key, group_name, last_connected)
-         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        "INSERT INTO servers (id, name, host, port, database, username, credential_key, group_name, last_connected, connect_timeout_ms, pooling_mode, favorite, replica_host, replica_port, keepalive_idle_secs, confirm_expensive, expensive_cost_threshold, expensive_rows_threshold, timezone)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
     )?;
 
     stmt.execute(params![
@@ -300,7 +500,17 @@ pub fn add_server(server: &Server) -> Result<(), rusqlite::Error> {
         server.username,
         server.credential_key,
         server.group_name,
-        server.last_connected
+        server.last_connected,
+        server.connect_timeout_ms,
+        server.pooling_mode,
+        server.favorite,
+        server.replica_host,
+        server.replica_port,
+        server.keepalive_idle_secs,
+        server.confirm_expensive,
+        server.expensive_cost_threshold,
+        server.expensive_rows_threshold,
+        server.timezone
     ])?;
 
     Ok(())
@@ -310,22 +520,93 @@ pub fn update_server_last_connected(
     server_id: &str,
     timestamp: i64,
 ) -> Result<(), rusqlite::Error> {
-    let conn = DB.lock().unwrap();
+    let conn = lock_db();
     let mut stmt = conn.prepare_cached("UPDATE servers SET last_connected = ? WHERE id = ?")?;
     stmt.execute(params![timestamp, server_id])?;
     Ok(())
 }
 
+/// Turn the expensive-query confirmation on or off and set its thresholds
+/// (`None` for the defaults). Returns false when the server doesn't exist.
+pub fn set_expensive_query_guard(
+    server_id: &str,
+    enabled: bool,
+    cost_threshold: Option<f64>,
+    rows_threshold: Option<i64>,
+) -> Result<bool, rusqlite::Error> {
+    let conn = lock_db();
+    let mut stmt = conn.prepare_cached(
+        "UPDATE servers
+         SET confirm_expensive = ?2, expensive_cost_threshold = ?3, expensive_rows_threshold = ?4
+         WHERE id = ?1",
+    )?;
+    let updated = stmt.execute(params![server_id, enabled as i32, cost_threshold, rows_threshold])?;
+    Ok(updated > 0)
+}
+
+/// Set or clear a server's session time zone. Returns false when the server
+/// doesn't exist.
+pub fn set_server_timezone(server_id: &str, timezone: Option<&str>) -> Result<bool, rusqlite::Error> {
+    let conn = lock_db();
+    let mut stmt = conn.prepare_cached("UPDATE servers SET timezone = ?2 WHERE id = ?1")?;
+    Ok(stmt.execute(params![server_id, timezone])? > 0)
+}
+
+/// Set a server's connect timeout; `None` goes back to the default.
+pub fn set_connect_timeout(server_id: &str, timeout_ms: Option<i64>) -> Result<bool, rusqlite::Error> {
+    let conn = lock_db();
+    let mut stmt = conn.prepare_cached("UPDATE servers SET connect_timeout_ms = ?2 WHERE id = ?1")?;
+    Ok(stmt.execute(params![server_id, timeout_ms])? > 0)
+}
+
+/// Flip a server's favorite flag, returning the new state.
+pub fn toggle_favorite_server(server_id: &str) -> Result<Option<bool>, rusqlite::Error> {
+    let conn = lock_db();
+    let mut stmt = conn.prepare_cached(
+        "UPDATE servers SET favorite = 1 - favorite WHERE id = ? RETURNING favorite",
+    )?;
+    stmt.query_row([server_id], |row| row.get::<_, i32>(0))
+        .optional()
+        .map(|favorite| favorite.map(|value| value != 0))
+}
+
+/// Delete a server and everything cached or saved for it; foreign keys
+/// aren't relied on to cascade.
 pub fn delete_server(server_id: &str) -> Result<(), rusqlite::Error> {
-    let conn = DB.lock().unwrap();
-    let mut stmt = conn.prepare_cached("DELETE FROM servers WHERE id = ?")?;
-    stmt.execute([server_id])?;
-    Ok(())
+    delete_servers(&[server_id.to_string()]).map(|_| ())
+}
+
+/// Delete several servers and everything cached for them in one transaction.
+/// SQLite foreign keys aren't enabled, so dependent rows are removed
+/// explicitly. Returns how many server rows were deleted.
+pub fn delete_servers(server_ids: &[String]) -> Result<usize, rusqlite::Error> {
+    let mut conn = lock_db();
+    let tx = conn.transaction()?;
+    let mut deleted = 0;
+
+    for server_id in server_ids {
+        let schema_ids = "SELECT id FROM schemas WHERE server_id = ?1";
+        let table_ids = format!("SELECT id FROM tables WHERE schema_id IN ({})", schema_ids);
+        tx.execute(&format!("DELETE FROM views WHERE schema_id IN ({})", schema_ids), [server_id])?;
+        tx.execute(&format!("DELETE FROM enum_values WHERE schema_id IN ({})", schema_ids), [server_id])?;
+        tx.execute(&format!("DELETE FROM indexes WHERE table_id IN ({})", table_ids), [server_id])?;
+        tx.execute(&format!("DELETE FROM columns WHERE table_id IN ({})", table_ids), [server_id])?;
+        tx.execute(&format!("DELETE FROM tables WHERE schema_id IN ({})", schema_ids), [server_id])?;
+        tx.execute("DELETE FROM schemas WHERE server_id = ?1", [server_id])?;
+        tx.execute("DELETE FROM autocomplete_items WHERE server_id = ?1", [server_id])?;
+        tx.execute("DELETE FROM query_history WHERE server_id = ?1", [server_id])?;
+        tx.execute("DELETE FROM query_history_dedup WHERE server_id = ?1", [server_id])?;
+        tx.execute("DELETE FROM workspace WHERE server_id = ?1", [server_id])?;
+        deleted += tx.execute("DELETE FROM servers WHERE id = ?1", [server_id])?;
+    }
+
+    tx.commit()?;
+    Ok(deleted)
 }
 
 // Schema operations
 pub fn get_schemas(server_id: &str) -> Result<Vec<Schema>, rusqlite::Error> {
-    let conn = DB.lock().unwrap();
+    let conn = lock_db();
     let mut stmt = conn.prepare_cached(
         "SELECT id, server_id, database_name, name, last_updated
          FROM schemas
@@ -348,13 +629,46 @@ pub fn get_schemas(server_id: &str) -> Result<Vec<Schema>, rusqlite::Error> {
     Ok(schemas)
 }
 
+/// Escape `%`, `_` and the escape character itself so `term` matches
+/// literally in a `LIKE ... ESCAPE '\'` pattern.
+fn escape_like(term: &str) -> String {
+    term.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+/// Cached schemas whose name contains `pattern` (case-insensitive for ASCII),
+/// so large multi-tenant servers needn't send every schema to the frontend.
+pub fn search_schemas(server_id: &str, pattern: &str, limit: usize) -> Result<Vec<Schema>, rusqlite::Error> {
+    let conn = lock_db();
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, server_id, database_name, name, last_updated
+         FROM schemas
+         WHERE server_id = ? AND name LIKE ? ESCAPE '\\'
+         ORDER BY database_name, name
+         LIMIT ?",
+    )?;
+
+    let schemas = stmt
+        .query_map(params![server_id, format!("%{}%", escape_like(pattern)), limit], |row| {
+            Ok(Schema {
+                id: row.get(0)?,
+                server_id: row.get(1)?,
+                database_name: row.get(2)?,
+                name: row.get(3)?,
+                last_updated: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(schemas)
+}
+
 // Batch insert schemas (much faster than individual inserts)
 pub fn batch_insert_schemas(schemas: &[Schema]) -> Result<(), rusqlite::Error> {
     if schemas.is_empty() {
         return Ok(());
     }
 
-    let mut conn = DB.lock().unwrap();
+    let mut conn = lock_db();
     let tx = conn.transaction()?;
 
     {
@@ -379,7 +693,7 @@ pub fn batch_insert_schemas(schemas: &[Schema]) -> Result<(), rusqlite::Error> {
 
 // Table operations
 pub fn get_tables(schema_id: &str) -> Result<Vec<Table>, rusqlite::Error> {
-    let conn = DB.lock().unwrap();
+    let conn = lock_db();
     let mut stmt = conn.prepare_cached(
         "SELECT id, schema_id, name, type
          FROM tables
@@ -406,7 +720,7 @@ pub fn batch_insert_tables(tables: &[Table]) -> Result<(), rusqlite::Error> {
         return Ok(());
     }
 
-    let mut conn = DB.lock().unwrap();
+    let mut conn = lock_db();
     let tx = conn.transaction()?;
 
     {
@@ -422,14 +736,111 @@ pub fn batch_insert_tables(tables: &[Table]) -> Result<(), rusqlite::Error> {
     Ok(())
 }
 
+pub fn find_schema_id(
+    server_id: &str,
+    database_name: &str,
+    schema_name: &str,
+) -> Result<Option<String>, rusqlite::Error> {
+    let conn = lock_db();
+    let mut stmt = conn.prepare_cached(
+        "SELECT id FROM schemas WHERE server_id = ? AND database_name = ? AND name = ?",
+    )?;
+    stmt.query_row(params![server_id, database_name, schema_name], |row| row.get(0))
+        .optional()
+}
+
+fn delete_table_rows(
+    tx: &rusqlite::Transaction,
+    schema_id: &str,
+    table_name: &str,
+) -> Result<(), rusqlite::Error> {
+    let table_ids = "SELECT id FROM tables WHERE schema_id = ?1 AND name = ?2";
+    tx.execute(
+        &format!("DELETE FROM indexes WHERE table_id IN ({})", table_ids),
+        params![schema_id, table_name],
+    )?;
+    tx.execute(
+        &format!("DELETE FROM columns WHERE table_id IN ({})", table_ids),
+        params![schema_id, table_name],
+    )?;
+    tx.execute(
+        "DELETE FROM tables WHERE schema_id = ?1 AND name = ?2",
+        params![schema_id, table_name],
+    )?;
+    Ok(())
+}
+
+/// Replace a single cached table (and its columns and indexes) without
+/// touching the rest of the server's schema cache.
+pub fn replace_table(
+    table: &Table,
+    columns: &[Column],
+    indexes: &[Index],
+) -> Result<(), rusqlite::Error> {
+    let mut conn = lock_db();
+    let tx = conn.transaction()?;
+
+    delete_table_rows(&tx, &table.schema_id, &table.name)?;
+
+    tx.execute(
+        "INSERT INTO tables (id, schema_id, name, type) VALUES (?, ?, ?, ?)",
+        params![table.id, table.schema_id, table.name, table.type_],
+    )?;
+
+    {
+        let mut stmt = tx.prepare_cached(
+            "INSERT INTO columns (id, table_id, name, data_type, nullable, ordinal_position, is_identity, is_generated)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )?;
+        for column in columns {
+            stmt.execute(params![
+                column.id,
+                column.table_id,
+                column.name,
+                column.data_type,
+                column.nullable,
+                column.ordinal_position,
+                column.is_identity,
+                column.is_generated
+            ])?;
+        }
+    }
+
+    {
+        let mut stmt = tx.prepare_cached(
+            "INSERT INTO indexes (id, table_id, name, definition) VALUES (?, ?, ?, ?)",
+        )?;
+        for index in indexes {
+            stmt.execute(params![
+                index.id,
+                index.table_id,
+                index.name,
+                index.definition
+            ])?;
+        }
+    }
+
+    tx.commit()?;
+    Ok(())
+}
+
+/// Remove a single cached table along with its columns and indexes.
+pub fn delete_table(schema_id: &str, table_name: &str) -> Result<(), rusqlite::Error> {
+    let mut conn = lock_db();
+    let tx = conn.transaction()?;
+    delete_table_rows(&tx, schema_id, table_name)?;
+    tx.commit()?;
+    Ok(())
+}
+
 // Column operations
 pub fn get_columns(table_id: &str) -> Result<Vec<Column>, rusqlite::Error> {
-    let conn = DB.lock().unwrap();
+    let conn = lock_db();
     let mut stmt = conn.prepare_cached(
-        "SELECT id, table_id, name, data_type, nullable
+        "SELECT id, table_id, name, data_type, nullable, ordinal_position, is_identity, is_generated
          FROM columns
          WHERE table_id = ?
-         ORDER BY name",
+         ORDER BY ordinal_position, name",
     )?;
 
     let columns = stmt
@@ -440,6 +851,9 @@ pub fn get_columns(table_id: &str) -> Result<Vec<Column>, rusqlite::Error> {
                 name: row.get(2)?,
                 data_type: row.get(3)?,
                 nullable: row.get(4)?,
+                ordinal_position: row.get(5)?,
+                is_identity: row.get(6)?,
+                is_generated: row.get(7)?,
             })
         })?
         .collect::<Result<Vec<_>, _>>()?;
@@ -450,7 +864,7 @@ pub fn get_columns(table_id: &str) -> Result<Vec<Column>, rusqlite::Error> {
 pub fn get_table_context(
     table_id: &str,
 ) -> Result<Option<(String, String, String, String)>, rusqlite::Error> {
-    let conn = DB.lock().unwrap();
+    let conn = lock_db();
     let mut stmt = conn.prepare_cached(
         "SELECT t.name, s.name, s.server_id, s.database_name
          FROM tables t
@@ -466,7 +880,7 @@ pub fn get_table_context(
 
 // View operations
 pub fn get_views(schema_id: &str) -> Result<Vec<View>, rusqlite::Error> {
-    let conn = DB.lock().unwrap();
+    let conn = lock_db();
     let mut stmt = conn.prepare_cached(
         "SELECT id, schema_id, name
          FROM views
@@ -489,7 +903,7 @@ pub fn get_views(schema_id: &str) -> Result<Vec<View>, rusqlite::Error> {
 
 // Index operations
 pub fn get_indexes(table_id: &str) -> Result<Vec<Index>, rusqlite::Error> {
-    let conn = DB.lock().unwrap();
+    let conn = lock_db();
     let mut stmt = conn.prepare_cached(
         "SELECT id, table_id, name, definition
          FROM indexes
@@ -512,7 +926,29 @@ pub fn get_indexes(table_id: &str) -> Result<Vec<Index>, rusqlite::Error> {
 }
 
 pub fn get_autocomplete_items(server_id: &str) -> Result<AutocompleteItems, rusqlite::Error> {
-    let conn = DB.lock().unwrap();
+    let conn = lock_db();
+
+    // Prefer the lightweight cache when it has been populated for this server
+    let cached: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM autocomplete_items WHERE server_id = ?",
+        [server_id],
+        |row| row.get(0),
+    )?;
+    if cached > 0 {
+        let mut stmt = conn.prepare_cached(
+            "SELECT name FROM autocomplete_items WHERE server_id = ? AND kind = ? ORDER BY name",
+        )?;
+        let mut load = |kind: &str| -> Result<Vec<String>, rusqlite::Error> {
+            stmt.query_map(params![server_id, kind], |row| row.get(0))?
+                .collect::<Result<Vec<String>, _>>()
+        };
+        return Ok(AutocompleteItems {
+            tables: load("table")?,
+            columns: load("column")?,
+            indexes: load("index")?,
+            schemas: load("schema")?,
+        });
+    }
 
     let mut tables_stmt = conn.prepare_cached(
         "SELECT t.name
@@ -567,11 +1003,131 @@ pub fn get_autocomplete_items(server_id: &str) -> Result<AutocompleteItems, rusq
     })
 }
 
+/// Cached labels of an enum type in its defined order, or `None` when it isn't
+/// cached. Without `schema_name`, `public` is preferred, then the first schema
+/// (by name) that has the type.
+pub fn get_enum_values(
+    server_id: &str,
+    database_name: &str,
+    schema_name: Option<&str>,
+    type_name: &str,
+) -> Result<Option<Vec<String>>, rusqlite::Error> {
+    let conn = lock_db();
+    let mut stmt = conn.prepare_cached(
+        "SELECT label FROM enum_values
+         WHERE type_name = ?4 AND schema_id = (
+             SELECT s.id FROM schemas s
+             JOIN enum_values ev ON ev.schema_id = s.id AND ev.type_name = ?4
+             WHERE s.server_id = ?1 AND s.database_name = ?2 AND (?3 IS NULL OR s.name = ?3)
+             ORDER BY s.name <> 'public', s.name
+             LIMIT 1
+         )
+         ORDER BY position",
+    )?;
+    let labels = stmt
+        .query_map(params![server_id, database_name, schema_name, type_name], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+
+    Ok(if labels.is_empty() { None } else { Some(labels) })
+}
+
+/// Replace the lightweight autocomplete cache for a server in a single transaction.
+pub fn replace_autocomplete_items(
+    server_id: &str,
+    items: &AutocompleteItems,
+) -> Result<(), rusqlite::Error> {
+    let mut conn = lock_db();
+    let tx = conn.transaction()?;
+
+    tx.execute("DELETE FROM autocomplete_items WHERE server_id = ?", [server_id])?;
+
+    {
+        let mut stmt = tx.prepare_cached(
+            "INSERT OR IGNORE INTO autocomplete_items (server_id, kind, name) VALUES (?, ?, ?)",
+        )?;
+        let groups = [
+            ("table", &items.tables),
+            ("column", &items.columns),
+            ("index", &items.indexes),
+            ("schema", &items.schemas),
+        ];
+        for (kind, names) in groups {
+            for name in names {
+                stmt.execute(params![server_id, kind, name])?;
+            }
+        }
+    }
+
+    tx.commit()?;
+    Ok(())
+}
+
+/// Patch the lightweight autocomplete cache after DDL on a few tables: add
+/// `added` names and drop `removed` ones that no cached table, column or
+/// index still uses. Does nothing when the cache hasn't been populated, since
+/// autocomplete then reads the schema cache directly.
+pub fn update_autocomplete_items(
+    server_id: &str,
+    added: &AutocompleteItems,
+    removed: &AutocompleteItems,
+) -> Result<(), rusqlite::Error> {
+    let mut conn = lock_db();
+    let tx = conn.transaction()?;
+
+    let cached: i64 = tx.query_row(
+        "SELECT COUNT(*) FROM autocomplete_items WHERE server_id = ?",
+        [server_id],
+        |row| row.get(0),
+    )?;
+    if cached == 0 {
+        return Ok(());
+    }
+
+    {
+        // Kind, names, and the cached rows that would keep a name in use
+        let still_used = [
+            ("table", &removed.tables, "SELECT 1 FROM tables t", "t.name"),
+            ("column", &removed.columns, "SELECT 1 FROM columns c JOIN tables t ON t.id = c.table_id", "c.name"),
+            ("index", &removed.indexes, "SELECT 1 FROM indexes i JOIN tables t ON t.id = i.table_id", "i.name"),
+        ];
+        for (kind, names, used, name_column) in still_used {
+            let mut stmt = tx.prepare_cached(&format!(
+                "DELETE FROM autocomplete_items
+                 WHERE server_id = ?1 AND kind = ?2 AND name = ?3
+                   AND NOT EXISTS ({} JOIN schemas s ON s.id = t.schema_id
+                                   WHERE s.server_id = ?1 AND {} = ?3)",
+                used, name_column
+            ))?;
+            for name in names {
+                stmt.execute(params![server_id, kind, name])?;
+            }
+        }
+
+        let mut stmt = tx.prepare_cached(
+            "INSERT OR IGNORE INTO autocomplete_items (server_id, kind, name) VALUES (?, ?, ?)",
+        )?;
+        let groups = [
+            ("table", &added.tables),
+            ("column", &added.columns),
+            ("index", &added.indexes),
+            ("schema", &added.schemas),
+        ];
+        for (kind, names) in groups {
+            for name in names {
+                stmt.execute(params![server_id, kind, name])?;
+            }
+        }
+    }
+
+    tx.commit()?;
+    Ok(())
+}
+
 pub fn replace_indexes_for_table(
     table_id: &str,
     indexes: &[Index],
 ) -> Result<(), rusqlite::Error> {
-    let mut conn = DB.lock().unwrap();
+    let mut conn = lock_db();
     let tx = conn.transaction()?;
 
     tx.execute("DELETE FROM indexes WHERE table_id = ?", [table_id])?;
@@ -599,12 +1155,13 @@ pub fn batch_insert_columns(columns: &[Column]) -> Result<(), rusqlite::Error> {
         return Ok(());
     }
 
-    let mut conn = DB.lock().unwrap();
+    let mut conn = lock_db();
     let tx = conn.transaction()?;
 
     {
         let mut stmt = tx.prepare_cached(
-            "INSERT INTO columns (id, table_id, name, data_type, nullable) VALUES (?, ?, ?, ?, ?)",
+            "INSERT INTO columns (id, table_id, name, data_type, nullable, ordinal_position, is_identity, is_generated)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
         )?;
 
         for column in columns {
@@ -613,7 +1170,10 @@ pub fn batch_insert_columns(columns: &[Column]) -> Result<(), rusqlite::Error> {
                 column.table_id,
                 column.name,
                 column.data_type,
-                column.nullable
+                column.nullable,
+                column.ordinal_position,
+                column.is_identity,
+                column.is_generated
             ])?;
         }
     }
@@ -623,27 +1183,57 @@ pub fn batch_insert_columns(columns: &[Column]) -> Result<(), rusqlite::Error> {
 }
 
 // Query history operations
+/// `executed_by` restricts results to one OS user; `None` returns everyone's.
 pub fn get_query_history(
     server_id: &str,
     limit: usize,
+    executed_by: Option<&str>,
 ) -> Result<Vec<QueryHistory>, rusqlite::Error> {
-    let conn = DB.lock().unwrap();
+    let conn = lock_db();
     let mut stmt = conn.prepare_cached(
-        "SELECT id, server_id, sql, executed_at, success
+        "SELECT id, server_id, sql, executed_at, success, executed_by
          FROM query_history
-         WHERE server_id = ?
+         WHERE server_id = ?1 AND (?3 IS NULL OR executed_by = ?3)
          ORDER BY executed_at DESC
-         LIMIT ?",
+         LIMIT ?2",
     )?;
 
     let history = stmt
-        .query_map(params![server_id, limit], |row| {
+        .query_map(params![server_id, limit, executed_by], |row| {
+            Ok(QueryHistory {
+                id: row.get(0)?,
+                server_id: row.get(1)?,
+                sql: row.get(2)?,
+                executed_at: row.get(3)?,
+                success: row.get(4)?,
+                executed_by: row.get(5)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(history)
+}
+
+/// Successful statements run on a server since `since` (inclusive), oldest
+/// first. `rowid` breaks ties between statements run in the same second.
+pub fn get_session_history(server_id: &str, since: i64) -> Result<Vec<QueryHistory>, rusqlite::Error> {
+    let conn = lock_db();
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, server_id, sql, executed_at, success, executed_by
+         FROM query_history
+         WHERE server_id = ?1 AND executed_at >= ?2 AND success = 1
+         ORDER BY executed_at, rowid",
+    )?;
+
+    let history = stmt
+        .query_map(params![server_id, since], |row| {
             Ok(QueryHistory {
                 id: row.get(0)?,
                 server_id: row.get(1)?,
                 sql: row.get(2)?,
                 executed_at: row.get(3)?,
                 success: row.get(4)?,
+                executed_by: row.get(5)?,
             })
         })?
         .collect::<Result<Vec<_>, _>>()?;
@@ -652,9 +1242,9 @@ pub fn get_query_history(
 }
 
 pub fn add_query_history(history: &QueryHistory) -> Result<(), rusqlite::Error> {
-    let conn = DB.lock().unwrap();
+    let conn = lock_db();
     let mut stmt = conn.prepare_cached(
-        "INSERT INTO query_history (id, server_id, sql, executed_at, success) VALUES (?, ?, ?, ?, ?)"
+        "INSERT INTO query_history (id, server_id, sql, executed_at, success, executed_by) VALUES (?, ?, ?, ?, ?, ?)"
     )?;
 
     stmt.execute(params![
@@ -662,7 +1252,8 @@ pub fn add_query_history(history: &QueryHistory) -> Result<(), rusqlite::Error>
         history.server_id,
         history.sql,
         history.executed_at,
-        history.success
+        history.success,
+        history.executed_by
     ])?;
 
     // Enforce 10,000 entry limit per server — delete oldest
@@ -683,6 +1274,9 @@ pub fn add_query_history(history: &QueryHistory) -> Result<(), rusqlite::Error>
 // Deduplicated Query History Operations
 // ============================================================================
 
+/// Per-server cap on entries kept in `query_history_dedup`.
+pub const MAX_DEDUP_HISTORY_ENTRIES: usize = 10_000;
+
 /// Normalize SQL for deduplication:
 /// - Trim leading/trailing whitespace
 /// - Collapse consecutive whitespace characters into single spaces
@@ -692,20 +1286,49 @@ fn normalize_sql(sql: &str) -> String {
 
 /// Upsert a query into the deduplicated history.
 /// If the normalized SQL already exists for this server, update it.
-/// Otherwise, insert a new entry.
+/// Otherwise, insert a new entry. `display_sql` (e.g. a pretty-printed copy)
+/// is stored in place of `sql`, while deduplication still keys on `sql`.
 pub fn upsert_query_history_dedup(
+    server_id: &str,
+    sql: &str,
+    display_sql: Option<&str>,
+    executed_at: i64,
+) -> Result<(), rusqlite::Error> {
+    merge_dedup_entry(
+        server_id,
+        display_sql.unwrap_or(sql),
+        &normalize_sql(sql),
+        executed_at,
+        1,
+    )
+}
+
+/// Upsert a query that was executed `execution_count` times, adding to the
+/// existing count on collision. The newest execution wins for `sql` and
+/// `last_executed_at`, so importing older entries never rewinds history.
+pub fn merge_query_history_dedup(
     server_id: &str,
     sql: &str,
     executed_at: i64,
+    execution_count: i64,
 ) -> Result<(), rusqlite::Error> {
-    let normalized = normalize_sql(sql);
-    let conn = DB.lock().unwrap();
+    merge_dedup_entry(server_id, sql, &normalize_sql(sql), executed_at, execution_count)
+}
+
+fn merge_dedup_entry(
+    server_id: &str,
+    sql: &str,
+    normalized: &str,
+    executed_at: i64,
+    execution_count: i64,
+) -> Result<(), rusqlite::Error> {
+    let conn = lock_db();
 
     // Try to find existing entry
     let existing_id: Option<String> = conn
         .query_row(
             "SELECT id FROM query_history_dedup WHERE server_id = ? AND normalized_sql = ?",
-            params![server_id, &normalized],
+            params![server_id, normalized],
             |row| row.get(0),
         )
         .optional()?;
@@ -714,18 +1337,20 @@ pub fn upsert_query_history_dedup(
         // Update existing entry
         let mut stmt = conn.prepare_cached(
             "UPDATE query_history_dedup 
-             SET sql = ?, last_executed_at = ?, execution_count = execution_count + 1
-             WHERE id = ?"
+             SET sql = CASE WHEN ?2 >= last_executed_at THEN ?1 ELSE sql END,
+                 last_executed_at = MAX(last_executed_at, ?2),
+                 execution_count = execution_count + ?3
+             WHERE id = ?4"
         )?;
-        stmt.execute(params![sql, executed_at, id])?;
+        stmt.execute(params![sql, executed_at, execution_count, id])?;
     } else {
         // Insert new entry
         let id = uuid::Uuid::new_v4().to_string();
         let mut stmt = conn.prepare_cached(
             "INSERT INTO query_history_dedup (id, server_id, sql, normalized_sql, last_executed_at, execution_count)
-             VALUES (?, ?, ?, ?, ?, 1)"
+             VALUES (?, ?, ?, ?, ?, ?)"
         )?;
-        stmt.execute(params![id, server_id, sql, &normalized, executed_at])?;
+        stmt.execute(params![id, server_id, sql, normalized, executed_at, execution_count])?;
 
         // Enforce 10,000 entry limit per server — delete oldest
         conn.execute(
@@ -747,7 +1372,7 @@ pub fn get_query_history_dedup(
     server_id: &str,
     limit: usize,
 ) -> Result<Vec<QueryHistoryEntry>, rusqlite::Error> {
-    let conn = DB.lock().unwrap();
+    let conn = lock_db();
     let mut stmt = conn.prepare_cached(
         "SELECT id, server_id, sql, normalized_sql, last_executed_at, execution_count
          FROM query_history_dedup
@@ -772,6 +1397,52 @@ pub fn get_query_history_dedup(
     Ok(history)
 }
 
+/// Days after which a query's weight in the combined score has halved.
+const TOP_QUERY_HALF_LIFE_DAYS: f64 = 7.0;
+
+/// Most-run queries for a server. By default ordered by execution count; with
+/// `by_score`, by count weighted for recency (`count / (1 + age / half-life)`),
+/// so a query run often last year ranks below one run often this week.
+pub fn get_top_queries(
+    server_id: &str,
+    limit: usize,
+    by_score: bool,
+    now: i64,
+) -> Result<Vec<QueryHistoryEntry>, rusqlite::Error> {
+    let conn = lock_db();
+    let order = if by_score {
+        "execution_count / (1.0 + MAX(?3 - last_executed_at, 0) / 86400.0 / ?4) DESC, last_executed_at DESC"
+    } else {
+        "execution_count DESC, last_executed_at DESC"
+    };
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT id, server_id, sql, normalized_sql, last_executed_at, execution_count
+         FROM query_history_dedup
+         WHERE server_id = ?1
+         ORDER BY {}
+         LIMIT ?2",
+        order
+    ))?;
+
+    // The frequency order has no placeholders for the score inputs
+    let bind = params![server_id, limit, now, TOP_QUERY_HALF_LIFE_DAYS];
+    let bind = if by_score { bind } else { &bind[..2] };
+    let history = stmt
+        .query_map(bind, |row| {
+            Ok(QueryHistoryEntry {
+                id: row.get(0)?,
+                server_id: row.get(1)?,
+                sql: row.get(2)?,
+                normalized_sql: row.get(3)?,
+                last_executed_at: row.get(4)?,
+                execution_count: row.get(5)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(history)
+}
+
 /// Search query history with case-insensitive partial matching.
 /// Results are still sorted by most recently executed first.
 pub fn search_query_history_dedup(
@@ -779,7 +1450,7 @@ pub fn search_query_history_dedup(
     search_term: &str,
     limit: usize,
 ) -> Result<Vec<QueryHistoryEntry>, rusqlite::Error> {
-    let conn = DB.lock().unwrap();
+    let conn = lock_db();
     let search_pattern = format!("%{}%", search_term);
     
     let mut stmt = conn.prepare_cached(
@@ -808,7 +1479,7 @@ pub fn search_query_history_dedup(
 
 /// Delete a specific query from the deduplicated history.
 pub fn delete_query_history_entry(entry_id: &str) -> Result<(), rusqlite::Error> {
-    let conn = DB.lock().unwrap();
+    let conn = lock_db();
     let mut stmt = conn.prepare_cached("DELETE FROM query_history_dedup WHERE id = ?")?;
     stmt.execute([entry_id])?;
     Ok(())
@@ -816,15 +1487,105 @@ pub fn delete_query_history_entry(entry_id: &str) -> Result<(), rusqlite::Error>
 
 /// Clear all deduplicated query history for a server.
 pub fn clear_query_history_dedup(server_id: &str) -> Result<(), rusqlite::Error> {
-    let conn = DB.lock().unwrap();
+    let conn = lock_db();
     let mut stmt = conn.prepare_cached("DELETE FROM query_history_dedup WHERE server_id = ?")?;
     stmt.execute([server_id])?;
     Ok(())
 }
 
+/// Clear query history for every server, both raw and deduplicated.
+pub fn clear_all_history() -> Result<(), rusqlite::Error> {
+    let mut conn = lock_db();
+    let tx = conn.transaction()?;
+    tx.execute("DELETE FROM query_history", [])?;
+    tx.execute("DELETE FROM query_history_dedup", [])?;
+    tx.commit()
+}
+
+/// Rebuild the local database file to reclaim space left by large deletes.
+/// `VACUUM` cannot run inside a transaction, so this runs on the bare
+/// connection; holding the lock guarantees no transaction is open.
+pub fn vacuum_local_db() -> Result<(), rusqlite::Error> {
+    let conn = lock_db();
+    conn.execute_batch("VACUUM")
+}
+
+// Workspace tabs
+const WORKSPACE_COLUMNS: &str = "id, server_id, tab_name, sql, cursor_pos, updated_at";
+
+fn workspace_tab_from_row(row: &rusqlite::Row) -> Result<WorkspaceTab, rusqlite::Error> {
+    Ok(WorkspaceTab {
+        id: row.get(0)?,
+        server_id: row.get(1)?,
+        tab_name: row.get(2)?,
+        sql: row.get(3)?,
+        cursor_pos: row.get(4)?,
+        updated_at: row.get(5)?,
+    })
+}
+
+/// Insert or update a tab. A new tab goes after the server's other tabs;
+/// updating keeps its place in the tab order.
+pub fn save_tab(tab: &WorkspaceTab) -> Result<(), rusqlite::Error> {
+    let conn = lock_db();
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO workspace (id, server_id, tab_name, sql, cursor_pos, updated_at, position)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6,
+                 (SELECT COALESCE(MAX(position), 0) + 1 FROM workspace WHERE server_id = ?2))
+         ON CONFLICT(id) DO UPDATE SET
+             server_id = excluded.server_id,
+             tab_name = excluded.tab_name,
+             sql = excluded.sql,
+             cursor_pos = excluded.cursor_pos,
+             updated_at = excluded.updated_at",
+    )?;
+    stmt.execute(params![
+        tab.id,
+        tab.server_id,
+        tab.tab_name,
+        tab.sql,
+        tab.cursor_pos,
+        tab.updated_at
+    ])?;
+    Ok(())
+}
+
+/// A server's tabs in the order they were opened.
+pub fn get_tabs(server_id: &str) -> Result<Vec<WorkspaceTab>, rusqlite::Error> {
+    let conn = lock_db();
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {} FROM workspace WHERE server_id = ? ORDER BY position, id",
+        WORKSPACE_COLUMNS
+    ))?;
+    let tabs = stmt
+        .query_map([server_id], workspace_tab_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(tabs)
+}
+
+/// Every saved tab, grouped by server, for restoring the whole session.
+pub fn get_all_tabs() -> Result<Vec<WorkspaceTab>, rusqlite::Error> {
+    let conn = lock_db();
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {} FROM workspace ORDER BY server_id, position, id",
+        WORKSPACE_COLUMNS
+    ))?;
+    let tabs = stmt
+        .query_map([], workspace_tab_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(tabs)
+}
+
+pub fn delete_tab(tab_id: &str) -> Result<(), rusqlite::Error> {
+    let conn = lock_db();
+    let mut stmt = conn.prepare_cached("DELETE FROM workspace WHERE id = ?")?;
+    stmt.execute([tab_id])?;
+    Ok(())
+}
+
 // Bulk operations for schema refresh
 pub fn clear_server_schema_data(server_id: &str) -> Result<(), rusqlite::Error> {
-    let mut conn = DB.lock().unwrap();
+    let mut conn = lock_db();
     let tx = conn.transaction()?;
 
     // Delete in reverse order of foreign key dependencies
@@ -834,6 +1595,12 @@ pub fn clear_server_schema_data(server_id: &str) -> Result<(), rusqlite::Error>
         [server_id],
     )?;
 
+    tx.execute(
+        "DELETE FROM enum_values WHERE schema_id IN
+         (SELECT id FROM schemas WHERE server_id = ?)",
+        [server_id],
+    )?;
+
     tx.execute(
         "DELETE FROM indexes WHERE table_id IN
          (SELECT id FROM tables WHERE schema_id IN
@@ -868,8 +1635,9 @@ pub fn refresh_server_schema(
     columns: &[Column],
     indexes: &[Index],
     views: &[View],
+    enums: &[EnumType],
 ) -> Result<(), rusqlite::Error> {
-    let mut conn = DB.lock().unwrap();
+    let mut conn = lock_db();
     let tx = conn.transaction()?;
 
     // Clear old data
@@ -879,6 +1647,12 @@ pub fn refresh_server_schema(
         [server_id],
     )?;
 
+    tx.execute(
+        "DELETE FROM enum_values WHERE schema_id IN
+         (SELECT id FROM schemas WHERE server_id = ?)",
+        [server_id],
+    )?;
+
     tx.execute(
         "DELETE FROM indexes WHERE table_id IN
          (SELECT id FROM tables WHERE schema_id IN
@@ -901,6 +1675,9 @@ pub fn refresh_server_schema(
 
     tx.execute("DELETE FROM schemas WHERE server_id = ?", [server_id])?;
 
+    // The full tree is authoritative again; drop the lightweight autocomplete cache
+    tx.execute("DELETE FROM autocomplete_items WHERE server_id = ?", [server_id])?;
+
     // Batch insert new data
     {
         let mut stmt = tx.prepare_cached(
@@ -935,7 +1712,19 @@ pub fn refresh_server_schema(
 
     {
         let mut stmt = tx.prepare_cached(
-            "INSERT INTO columns (id, table_id, name, data_type, nullable) VALUES (?, ?, ?, ?, ?)",
+            "INSERT INTO enum_values (schema_id, type_name, position, label) VALUES (?, ?, ?, ?)",
+        )?;
+        for enum_type in enums {
+            for (position, label) in enum_type.labels.iter().enumerate() {
+                stmt.execute(params![enum_type.schema_id, enum_type.name, position as i64, label])?;
+            }
+        }
+    }
+
+    {
+        let mut stmt = tx.prepare_cached(
+            "INSERT INTO columns (id, table_id, name, data_type, nullable, ordinal_position, is_identity, is_generated)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
         )?;
         for column in columns {
             stmt.execute(params![
@@ -943,7 +1732,10 @@ pub fn refresh_server_schema(
                 column.table_id,
                 column.name,
                 column.data_type,
-                column.nullable
+                column.nullable,
+                column.ordinal_position,
+                column.is_identity,
+                column.is_generated
             ])?;
         }
     }
@@ -968,7 +1760,7 @@ pub fn refresh_server_schema(
 
 // Utility: Check if schema is stale (older than threshold)
 pub fn is_schema_stale(server_id: &str, threshold_seconds: i64) -> Result<bool, rusqlite::Error> {
-    let conn = DB.lock().unwrap();
+    let conn = lock_db();
     let mut stmt = conn
         .prepare_cached("SELECT COUNT(*) FROM schemas WHERE server_id = ? AND last_updated < ?")?;
 
@@ -984,17 +1776,9 @@ pub fn is_schema_stale(server_id: &str, threshold_seconds: i64) -> Result<bool,
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_init_db() {
-        assert!(init_db().is_ok());
-    }
-
-    #[test]
-    fn test_server_crud() {
-        init_db().unwrap();
-
-        let server = Server {
-            id: "test-1".to_string(),
+    fn test_server(id: &str) -> Server {
+        Server {
+            id: id.to_string(),
             name: "Test Server".to_string(),
             host: "localhost".to_string(),
             port: 5432,
@@ -1003,12 +1787,143 @@ mod tests {
             credential_key: "key-1".to_string(),
             group_name: None,
             last_connected: None,
-        };
+            connect_timeout_ms: None,
+            pooling_mode: None,
+            favorite: 0,
+            replica_host: None,
+            replica_port: None,
+            keepalive_idle_secs: None,
+            confirm_expensive: 0,
+            expensive_cost_threshold: None,
+            expensive_rows_threshold: None,
+            timezone: None,
+        }
+    }
+
+    #[test]
+    fn test_init_db() {
+        assert!(init_db().is_ok());
+    }
 
-        add_server(&server).unwrap();
+    #[test]
+    fn test_server_crud() {
+        init_db().unwrap();
+
+        add_server(&test_server("test-1")).unwrap();
         let servers = get_servers().unwrap();
         assert!(servers.iter().any(|s| s.id == "test-1"));
 
         delete_server("test-1").unwrap();
     }
+
+    #[test]
+    fn test_set_connect_timeout() {
+        init_db().unwrap();
+        let server_id = "test-connect-timeout";
+        let _ = delete_server(server_id);
+        add_server(&test_server(server_id)).unwrap();
+
+        assert!(set_connect_timeout(server_id, Some(2_500)).unwrap());
+        let server = get_server_by_id(server_id).unwrap().unwrap();
+        assert_eq!(server.connect_timeout_ms, Some(2_500));
+
+        assert!(set_connect_timeout(server_id, None).unwrap());
+        let server = get_server_by_id(server_id).unwrap().unwrap();
+        assert_eq!(server.connect_timeout_ms, None);
+
+        assert!(!set_connect_timeout("no-such-server", Some(1)).unwrap());
+        delete_server(server_id).unwrap();
+    }
+
+    #[test]
+    fn test_recovers_from_poisoned_lock() {
+        init_db().unwrap();
+
+        let _ = std::thread::spawn(|| {
+            let _guard = DB.lock().unwrap();
+            panic!("poison the database lock");
+        })
+        .join();
+
+        assert!(DB.is_poisoned());
+        assert!(get_servers().is_ok());
+        assert!(get_query_history("no-such-server", 10, None).is_ok());
+    }
+
+    #[test]
+    fn test_top_queries_frequency_and_score() {
+        init_db().unwrap();
+        let server_id = "test-top-queries";
+        let now = 100 * 86400;
+        delete_server(server_id).unwrap();
+        add_server(&test_server(server_id)).unwrap();
+
+        // Run often but long ago, versus less often today
+        merge_query_history_dedup(server_id, "SELECT 1", now - 60 * 86400, 10).unwrap();
+        merge_query_history_dedup(server_id, "SELECT 2", now, 4).unwrap();
+
+        let by_count = get_top_queries(server_id, 10, false, now).unwrap();
+        assert_eq!(by_count[0].sql, "SELECT 1");
+
+        let by_score = get_top_queries(server_id, 10, true, now).unwrap();
+        assert_eq!(by_score[0].sql, "SELECT 2");
+
+        clear_query_history_dedup(server_id).unwrap();
+        delete_server(server_id).unwrap();
+    }
+
+    #[test]
+    fn test_workspace_tabs() {
+        init_db().unwrap();
+        let server_id = "test-workspace-tabs";
+        delete_server(server_id).unwrap();
+        add_server(&test_server(server_id)).unwrap();
+
+        // Opened second but sorts first by id, so order must come from position
+        let first = WorkspaceTab {
+            id: "test-workspace-tab-b".to_string(),
+            server_id: server_id.to_string(),
+            tab_name: "Query 0".to_string(),
+            sql: String::new(),
+            cursor_pos: 0,
+            updated_at: 1,
+        };
+        save_tab(&first).unwrap();
+
+        let mut tab = WorkspaceTab {
+            id: "test-workspace-tab-a".to_string(),
+            server_id: server_id.to_string(),
+            tab_name: "Query 1".to_string(),
+            sql: "SELECT 1".to_string(),
+            cursor_pos: 8,
+            updated_at: 1,
+        };
+        save_tab(&tab).unwrap();
+
+        tab.sql = "SELECT 2".to_string();
+        tab.updated_at = 2;
+        save_tab(&tab).unwrap();
+
+        let tabs = get_tabs(server_id).unwrap();
+        assert_eq!(tabs.len(), 2);
+        assert_eq!(tabs[0].id, first.id);
+        assert_eq!(tabs[1].sql, "SELECT 2");
+
+        delete_tab(&tab.id).unwrap();
+        assert_eq!(get_tabs(server_id).unwrap().len(), 1);
+        delete_tab(&first.id).unwrap();
+        assert!(get_tabs(server_id).unwrap().is_empty());
+
+        // Tabs left behind go with their server
+        save_tab(&first).unwrap();
+        delete_server(server_id).unwrap();
+        assert!(get_tabs(server_id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_escape_like() {
+        assert_eq!(escape_like("tenant_1"), "tenant\\_1");
+        assert_eq!(escape_like("100%"), "100\\%");
+        assert_eq!(escape_like("a\\b"), "a\\\\b");
+    }
 }
@@ -0,0 +1,498 @@
+//! Typed decoding of Postgres wire values into JSON.
+//!
+//! Every path that hands rows to the frontend or writes them out (query
+//! results, previews, NDJSON export) goes through `row_to_json`, so a type
+//! gets the same representation everywhere and supporting a new one is a
+//! single arm in `value_to_json`.
+
+use tokio_postgres::types::{Field, FromSql, Kind, Type};
+
+/// Follow domain types down to the base type they were declared over.
+pub(crate) fn resolve_base_type(ty: &Type) -> &Type {
+    match ty.kind() {
+        Kind::Domain(base) => resolve_base_type(base),
+        _ => ty,
+    }
+}
+
+/// Types whose binary wire format is plain UTF-8 text, including extension
+/// types such as `citext` that have no built-in OID.
+fn is_text_like(type_name: &str) -> bool {
+    matches!(
+        type_name,
+        "text" | "varchar" | "bpchar" | "name" | "unknown" | "citext" | "ltree" | "lquery" | "ltxtquery"
+    )
+}
+
+/// Raw wire bytes of any column, so decoding can dispatch on the column type
+/// (including nested composite fields) rather than on a Rust type.
+struct RawValue<'a>(&'a [u8]);
+
+impl<'a> FromSql<'a> for RawValue<'a> {
+    fn from_sql(
+        _ty: &Type,
+        raw: &'a [u8],
+    ) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        Ok(RawValue(raw))
+    }
+
+    fn accepts(_ty: &Type) -> bool {
+        true
+    }
+}
+
+/// Decode `raw` as `T` when `T` understands the (already resolved) type.
+fn decode_raw<'a, T: FromSql<'a>>(ty: &Type, raw: &'a [u8]) -> Option<T> {
+    if !T::accepts(ty) {
+        return None;
+    }
+    T::from_sql(ty, raw).ok()
+}
+
+/// Decode a binary record (`i32` field count, then per field an OID, an
+/// `i32` length with -1 for NULL, and the bytes) into an object keyed by
+/// field name.
+fn decode_composite(fields: &[Field], raw: &[u8]) -> Option<serde_json::Value> {
+    fn read_i32(raw: &[u8], pos: &mut usize) -> Option<i32> {
+        let bytes = raw.get(*pos..*pos + 4)?;
+        *pos += 4;
+        Some(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    let mut pos = 0;
+    let count = read_i32(raw, &mut pos)?;
+    let mut map = serde_json::Map::new();
+
+    for idx in 0..count.max(0) as usize {
+        let _oid = read_i32(raw, &mut pos)?;
+        let len = read_i32(raw, &mut pos)?;
+        let value = if len < 0 {
+            None
+        } else {
+            let bytes = raw.get(pos..pos + len as usize)?;
+            pos += len as usize;
+            Some(bytes)
+        };
+
+        let field = fields.get(idx)?;
+        map.insert(field.name().to_string(), value_to_json(field.type_(), value));
+    }
+
+    Some(serde_json::Value::Object(map))
+}
+
+/// Prefix on cells whose text wasn't valid UTF-8 and was decoded lossily.
+const NON_UTF8_MARKER: &str = "[non-UTF8] ";
+
+/// Decode text, keeping invalid UTF-8 visible instead of dropping the cell.
+/// tokio-postgres always requests `client_encoding=UTF8`, so this only
+/// happens for bytes the server can't convert, e.g. in `SQL_ASCII` databases.
+fn text_to_json(raw: &[u8]) -> serde_json::Value {
+    match std::str::from_utf8(raw) {
+        Ok(text) => text.into(),
+        Err(_) => format!("{}{}", NON_UTF8_MARKER, String::from_utf8_lossy(raw)).into(),
+    }
+}
+
+/// Render a binary `numeric` (`i16` digit count, weight, sign, display scale,
+/// then base-10000 digits) as its exact decimal text.
+pub(crate) fn decode_numeric(raw: &[u8]) -> Option<String> {
+    let word = |idx: usize| -> Option<u16> {
+        let bytes = raw.get(idx * 2..idx * 2 + 2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    };
+    let ndigits = word(0)? as usize;
+    let weight = word(1)? as i16 as i64;
+    let sign = word(2)?;
+    let dscale = word(3)? as usize;
+    let digits = (0..ndigits).map(|i| word(4 + i)).collect::<Option<Vec<u16>>>()?;
+
+    match sign {
+        0xC000 => return Some("NaN".to_string()),
+        0xD000 => return Some("Infinity".to_string()),
+        0xF000 => return Some("-Infinity".to_string()),
+        _ => {}
+    }
+    let digit = |idx: i64| -> u16 {
+        if idx >= 0 {
+            digits.get(idx as usize).copied().unwrap_or(0)
+        } else {
+            0
+        }
+    };
+
+    let mut text = String::new();
+    if sign == 0x4000 {
+        text.push('-');
+    }
+    if weight < 0 {
+        text.push('0');
+    } else {
+        text.push_str(&digit(0).to_string());
+        for idx in 1..=weight {
+            text.push_str(&format!("{:04}", digit(idx)));
+        }
+    }
+    if dscale > 0 {
+        let mut fraction = String::new();
+        for k in 0..dscale.div_ceil(4) as i64 {
+            fraction.push_str(&format!("{:04}", digit(weight + 1 + k)));
+        }
+        fraction.truncate(dscale);
+        text.push('.');
+        text.push_str(&fraction);
+    }
+    Some(text)
+}
+
+/// A numeric as a JSON number only when an `f64` holds it exactly, so no
+/// digits are lost. Anything else, including NaN and infinities, stays a
+/// string.
+fn exact_numeric_to_json(text: String) -> serde_json::Value {
+    if let Ok(v) = text.parse::<i64>() {
+        return v.into();
+    }
+    // `f64`'s Display is the shortest decimal that round-trips, so compare
+    // against the text without insignificant trailing zeros
+    let significant = if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        text.as_str()
+    };
+    match text.parse::<f64>() {
+        Ok(v) if v.to_string() == significant => serde_json::Number::from_f64(v)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::String(text)),
+        _ => serde_json::Value::String(text),
+    }
+}
+
+/// Convert one wire value to JSON. Domains decode as their base type,
+/// composites become nested objects, and types without a decoder are `null`.
+fn value_to_json(ty: &Type, raw: Option<&[u8]>) -> serde_json::Value {
+    let Some(raw) = raw else {
+        return serde_json::Value::Null;
+    };
+    let base = resolve_base_type(ty);
+
+    let value: Option<serde_json::Value> = match (base.kind(), base.name()) {
+        (Kind::Composite(fields), _) => decode_composite(fields, raw),
+        // Enums travel as their label text
+        (Kind::Enum(_), _) => Some(text_to_json(raw)),
+        (_, "void") => None,
+        (_, "int2") => decode_raw::<i16>(base, raw).map(|v: i16| v.into()),
+        (_, "int4") => decode_raw::<i32>(base, raw).map(|v: i32| v.into()),
+        (_, "int8") => decode_raw::<i64>(base, raw).map(|v: i64| v.into()),
+        (_, "float4") => decode_raw::<f32>(base, raw).map(|v: f32| v.into()),
+        (_, "float8") => decode_raw::<f64>(base, raw).map(|v: f64| v.into()),
+        (_, "bool") => decode_raw::<bool>(base, raw).map(|v: bool| v.into()),
+        (_, "oid") => decode_raw::<u32>(base, raw).map(|v: u32| v.into()),
+        (_, "numeric") => decode_numeric(raw).map(exact_numeric_to_json),
+        (_, "json" | "jsonb") => decode_raw::<serde_json::Value>(base, raw),
+        // Text-like extension types have no built-in OID, so skip `accepts`
+        (_, name) if is_text_like(name) => Some(text_to_json(raw)),
+        _ if String::accepts(base) => Some(text_to_json(raw)),
+        _ => None,
+    };
+
+    value.unwrap_or(serde_json::Value::Null)
+}
+
+/// Whether `value_to_json` can render `ty`; keep the two in step. Columns of
+/// other types (arrays, dates, extension types such as `hstore` or
+/// `vector`) are selected as `::text` by the query layer instead of coming
+/// back `null`.
+pub(crate) fn has_decoder(ty: &Type) -> bool {
+    let base = resolve_base_type(ty);
+    match (base.kind(), base.name()) {
+        (Kind::Composite(fields), _) => fields.iter().all(|field| has_decoder(field.type_())),
+        (Kind::Enum(_), _) => true,
+        (
+            _,
+            "void" | "int2" | "int4" | "int8" | "float4" | "float8" | "bool" | "oid" | "numeric"
+            | "json" | "jsonb",
+        ) => true,
+        (_, name) if is_text_like(name) => true,
+        _ => String::accepts(base),
+    }
+}
+
+/// Which kind of cell renderer suits a column of type `ty`: `datetime`,
+/// `number`, `boolean`, `json`, `uuid`, `binary` or `text`.
+pub(crate) fn render_hint(ty: &Type) -> &'static str {
+    let base = resolve_base_type(ty);
+    match (base.kind(), base.name()) {
+        // Composites decode to nested objects
+        (Kind::Composite(_), _) => "json",
+        (_, "date" | "time" | "timetz" | "timestamp" | "timestamptz") => "datetime",
+        (_, "int2" | "int4" | "int8" | "float4" | "float8" | "numeric" | "oid" | "money") => "number",
+        (_, "bool") => "boolean",
+        (_, "json" | "jsonb") => "json",
+        (_, "uuid") => "uuid",
+        (_, "bytea") => "binary",
+        _ => "text",
+    }
+}
+
+/// Base type and raw wire bytes of column `idx`, for callers that build their
+/// own typed output instead of JSON (e.g. the Arrow export).
+pub(crate) fn raw_column(row: &tokio_postgres::Row, idx: usize) -> (&Type, Option<&[u8]>) {
+    let raw = row
+        .try_get::<_, Option<RawValue>>(idx)
+        .ok()
+        .flatten()
+        .map(|v| v.0);
+    (resolve_base_type(row.columns()[idx].type_()), raw)
+}
+
+/// Convert column `idx` of a row to JSON.
+pub(crate) fn column_to_json(row: &tokio_postgres::Row, idx: usize) -> serde_json::Value {
+    let (ty, raw) = raw_column(row, idx);
+    value_to_json(ty, raw)
+}
+
+/// Convert a row into a JSON object keyed by column name.
+pub(crate) fn row_to_json(row: &tokio_postgres::Row) -> serde_json::Value {
+    let mut map = serde_json::Map::new();
+    for (idx, col) in row.columns().iter().enumerate() {
+        map.insert(col.name().to_string(), column_to_json(row, idx));
+    }
+    serde_json::Value::Object(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field_bytes(oid: u32, value: Option<&[u8]>) -> Vec<u8> {
+        let mut out = oid.to_be_bytes().to_vec();
+        match value {
+            Some(bytes) => {
+                out.extend_from_slice(&(bytes.len() as i32).to_be_bytes());
+                out.extend_from_slice(bytes);
+            }
+            None => out.extend_from_slice(&(-1i32).to_be_bytes()),
+        }
+        out
+    }
+
+    /// A column of `CREATE TYPE address AS (street text, zip int4, geo point_pair)`
+    /// where `point_pair` is itself a composite of two float8s.
+    #[test]
+    fn test_composite_column_decodes_to_nested_object() {
+        let point_pair = Type::new(
+            "point_pair".to_string(),
+            90002,
+            Kind::Composite(vec![
+                Field::new("lat".to_string(), Type::FLOAT8),
+                Field::new("lon".to_string(), Type::FLOAT8),
+            ]),
+            "public".to_string(),
+        );
+        let address = Type::new(
+            "address".to_string(),
+            90001,
+            Kind::Composite(vec![
+                Field::new("street".to_string(), Type::TEXT),
+                Field::new("zip".to_string(), Type::INT4),
+                Field::new("unit".to_string(), Type::TEXT),
+                Field::new("geo".to_string(), point_pair),
+            ]),
+            "public".to_string(),
+        );
+
+        let mut geo = 2i32.to_be_bytes().to_vec();
+        geo.extend(field_bytes(701, Some(&51.5f64.to_be_bytes())));
+        geo.extend(field_bytes(701, Some(&(-0.25f64).to_be_bytes())));
+
+        let mut raw = 4i32.to_be_bytes().to_vec();
+        raw.extend(field_bytes(25, Some(b"1 Main St")));
+        raw.extend(field_bytes(23, Some(&12345i32.to_be_bytes())));
+        raw.extend(field_bytes(25, None));
+        raw.extend(field_bytes(90002, Some(&geo)));
+
+        assert_eq!(
+            value_to_json(&address, Some(&raw)),
+            serde_json::json!({
+                "street": "1 Main St",
+                "zip": 12345,
+                "unit": null,
+                "geo": { "lat": 51.5, "lon": -0.25 }
+            })
+        );
+    }
+
+    #[test]
+    fn test_invalid_utf8_text_is_kept_with_marker() {
+        let raw = [b'c', b'a', b'f', 0xe9];
+
+        assert_eq!(
+            value_to_json(&Type::TEXT, Some(&raw)),
+            serde_json::Value::String(format!("{}caf\u{fffd}", NON_UTF8_MARKER))
+        );
+        assert_eq!(value_to_json(&Type::VARCHAR, Some(b"ok")), serde_json::json!("ok"));
+    }
+
+    #[test]
+    fn test_truncated_composite_is_null() {
+        let pair = Type::new(
+            "pair".to_string(),
+            90003,
+            Kind::Composite(vec![Field::new("a".to_string(), Type::INT4)]),
+            "public".to_string(),
+        );
+        let raw = 1i32.to_be_bytes();
+
+        assert_eq!(value_to_json(&pair, Some(&raw)), serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_numeric_decodes_to_json_number() {
+        fn numeric(weight: i16, sign: u16, dscale: u16, digits: &[u16]) -> Vec<u8> {
+            let mut out = (digits.len() as u16).to_be_bytes().to_vec();
+            out.extend_from_slice(&weight.to_be_bytes());
+            out.extend_from_slice(&sign.to_be_bytes());
+            out.extend_from_slice(&dscale.to_be_bytes());
+            for digit in digits {
+                out.extend_from_slice(&digit.to_be_bytes());
+            }
+            out
+        }
+
+        assert_eq!(decode_numeric(&numeric(1, 0, 3, &[1, 2345, 6780])).as_deref(), Some("12345.678"));
+        assert_eq!(decode_numeric(&numeric(-1, 0x4000, 2, &[500])).as_deref(), Some("-0.05"));
+        assert_eq!(decode_numeric(&numeric(2, 0, 0, &[12])).as_deref(), Some("1200000000"));
+        assert_eq!(decode_numeric(&numeric(0, 0xC000, 0, &[])).as_deref(), Some("NaN"));
+
+        assert_eq!(
+            value_to_json(&Type::NUMERIC, Some(&numeric(1, 0, 3, &[1, 2345, 6780]))),
+            serde_json::json!(12345.678)
+        );
+        assert_eq!(
+            value_to_json(&Type::NUMERIC, Some(&numeric(0, 0, 0, &[42]))),
+            serde_json::json!(42)
+        );
+        assert_eq!(
+            value_to_json(&Type::NUMERIC, Some(&numeric(0, 0xD000, 0, &[]))),
+            serde_json::json!("Infinity")
+        );
+        // 19 significant digits; an f64 would round to 12345678901234568
+        assert_eq!(
+            value_to_json(&Type::NUMERIC, Some(&numeric(4, 0, 2, &[1, 2345, 6789, 123, 4567, 8900]))),
+            serde_json::json!("12345678901234567.89")
+        );
+    }
+
+    #[test]
+    fn test_exact_numeric_keeps_lossy_values_as_strings() {
+        assert_eq!(exact_numeric_to_json("42".to_string()), serde_json::json!(42));
+        assert_eq!(exact_numeric_to_json("12.50".to_string()), serde_json::json!(12.5));
+        assert_eq!(exact_numeric_to_json("-0.05".to_string()), serde_json::json!(-0.05));
+        assert_eq!(
+            exact_numeric_to_json("12345678901234567890.12".to_string()),
+            serde_json::json!("12345678901234567890.12")
+        );
+        assert_eq!(
+            exact_numeric_to_json("0.12345678901234567890".to_string()),
+            serde_json::json!("0.12345678901234567890")
+        );
+        assert_eq!(exact_numeric_to_json("NaN".to_string()), serde_json::json!("NaN"));
+    }
+
+    #[test]
+    fn test_jsonb_decodes_to_nested_value() {
+        let mut raw = vec![1u8];
+        raw.extend_from_slice(br#"{"a": [1, true, null]}"#);
+
+        assert_eq!(
+            value_to_json(&Type::JSONB, Some(&raw)),
+            serde_json::json!({"a": [1, true, null]})
+        );
+    }
+
+    #[test]
+    fn test_scalar_types() {
+        assert_eq!(value_to_json(&Type::INT2, Some(&7i16.to_be_bytes())), serde_json::json!(7));
+        assert_eq!(value_to_json(&Type::INT4, Some(&(-3i32).to_be_bytes())), serde_json::json!(-3));
+        assert_eq!(
+            value_to_json(&Type::INT8, Some(&9_007_199_254_740_993i64.to_be_bytes())),
+            serde_json::json!(9_007_199_254_740_993i64)
+        );
+        assert_eq!(value_to_json(&Type::FLOAT4, Some(&1.5f32.to_be_bytes())), serde_json::json!(1.5));
+        assert_eq!(value_to_json(&Type::FLOAT8, Some(&(-2.25f64).to_be_bytes())), serde_json::json!(-2.25));
+        assert_eq!(value_to_json(&Type::BOOL, Some(&[1])), serde_json::json!(true));
+        assert_eq!(value_to_json(&Type::OID, Some(&16384u32.to_be_bytes())), serde_json::json!(16384));
+        assert_eq!(value_to_json(&Type::NAME, Some(b"pg_class")), serde_json::json!("pg_class"));
+    }
+
+    #[test]
+    fn test_null_and_undecodable_values() {
+        assert_eq!(value_to_json(&Type::INT4, None), serde_json::Value::Null);
+        // Wrong width for the type
+        assert_eq!(value_to_json(&Type::INT4, Some(&[0, 1])), serde_json::Value::Null);
+        assert_eq!(value_to_json(&Type::BYTEA, Some(&[0xde, 0xad])), serde_json::Value::Null);
+        assert_eq!(value_to_json(&Type::VOID, Some(&[])), serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_domain_decodes_as_base_type() {
+        let positive = Type::new(
+            "positive_int".to_string(),
+            90004,
+            Kind::Domain(Type::INT4),
+            "public".to_string(),
+        );
+
+        assert_eq!(value_to_json(&positive, Some(&5i32.to_be_bytes())), serde_json::json!(5));
+    }
+
+    #[test]
+    fn test_json_decodes_to_nested_value() {
+        assert_eq!(
+            value_to_json(&Type::JSON, Some(br#"[{"n": 1.5}, "x"]"#)),
+            serde_json::json!([{"n": 1.5}, "x"])
+        );
+    }
+
+    #[test]
+    fn test_enum_decodes_to_label() {
+        let mood = Type::new(
+            "mood".to_string(),
+            90005,
+            Kind::Enum(vec!["sad".to_string(), "happy".to_string()]),
+            "public".to_string(),
+        );
+
+        assert_eq!(value_to_json(&mood, Some(b"happy")), serde_json::json!("happy"));
+    }
+
+    #[test]
+    fn test_has_decoder_flags_extension_types() {
+        let vector = Type::new("vector".to_string(), 90010, Kind::Simple, "public".to_string());
+        let citext = Type::new("citext".to_string(), 90011, Kind::Simple, "public".to_string());
+
+        assert!(has_decoder(&Type::INT4));
+        assert!(has_decoder(&Type::JSONB));
+        assert!(has_decoder(&citext));
+        assert!(!has_decoder(&vector));
+        assert!(!has_decoder(&Type::INT4_ARRAY));
+    }
+
+    #[test]
+    fn test_render_hint_follows_base_type() {
+        let amount = Type::new(
+            "amount".to_string(),
+            90012,
+            Kind::Domain(Type::NUMERIC),
+            "public".to_string(),
+        );
+
+        assert_eq!(render_hint(&Type::TIMESTAMPTZ), "datetime");
+        assert_eq!(render_hint(&amount), "number");
+        assert_eq!(render_hint(&Type::BOOL), "boolean");
+        assert_eq!(render_hint(&Type::JSONB), "json");
+        assert_eq!(render_hint(&Type::UUID), "uuid");
+        assert_eq!(render_hint(&Type::BYTEA), "binary");
+        assert_eq!(render_hint(&Type::INT4_ARRAY), "text");
+    }
+}
@@ -0,0 +1,243 @@
+use serde::Serialize;
+use std::fmt;
+
+/// Error returned by every Tauri command.
+///
+/// Serializes as a tagged object, e.g. `{ "kind": "sql", "code": "42P01", "message": "..." }`,
+/// where `code` is the Postgres SQLSTATE (`23505` unique violation, `42P01` undefined table),
+/// so the frontend can react to the category (prompt for a password on `auth`,
+/// offer a reconnect on `connection`) instead of parsing strings. Every variant
+/// carries a human-readable `message`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum AppError {
+    Connection { message: String },
+    Auth { message: String },
+    Sql {
+        code: String,
+        message: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        detail: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        hint: Option<String>,
+        /// 1-based character offset into the submitted SQL where the error was found
+        #[serde(skip_serializing_if = "Option::is_none")]
+        position: Option<u32>,
+        /// `ERROR`, `FATAL` or `PANIC`; boxed to keep `AppError` within
+        /// clippy's `result_large_err` limit
+        #[serde(skip_serializing_if = "Option::is_none")]
+        severity: Option<Box<str>>,
+    },
+    NotFound { message: String },
+    Io { message: String },
+    Invalid { message: String },
+    Internal { message: String },
+    /// The planner expects the statement to be expensive and the server asks
+    /// for confirmation; re-run it with `confirmed` to go ahead.
+    ConfirmationRequired {
+        message: String,
+        #[serde(rename = "estimatedCost")]
+        estimated_cost: f64,
+        #[serde(rename = "estimatedRows")]
+        estimated_rows: f64,
+    },
+}
+
+impl AppError {
+    pub fn connection(message: impl Into<String>) -> Self {
+        AppError::Connection { message: message.into() }
+    }
+
+    pub fn auth(message: impl Into<String>) -> Self {
+        AppError::Auth { message: message.into() }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        AppError::NotFound { message: message.into() }
+    }
+
+    pub fn io(message: impl Into<String>) -> Self {
+        AppError::Io { message: message.into() }
+    }
+
+    pub fn invalid(message: impl Into<String>) -> Self {
+        AppError::Invalid { message: message.into() }
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        AppError::Internal { message: message.into() }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            AppError::Connection { message }
+            | AppError::Auth { message }
+            | AppError::Sql { message, .. }
+            | AppError::NotFound { message }
+            | AppError::Io { message }
+            | AppError::Invalid { message }
+            | AppError::Internal { message }
+            | AppError::ConfirmationRequired { message, .. } => message,
+        }
+    }
+
+    /// Rewrite the message while keeping the category.
+    pub fn map_message(mut self, f: impl FnOnce(&str) -> String) -> Self {
+        match &mut self {
+            AppError::Connection { message }
+            | AppError::Auth { message }
+            | AppError::Sql { message, .. }
+            | AppError::NotFound { message }
+            | AppError::Io { message }
+            | AppError::Invalid { message }
+            | AppError::Internal { message }
+            | AppError::ConfirmationRequired { message, .. } => {
+                *message = f(message);
+            }
+        }
+        self
+    }
+
+    /// Prefix the message with what was being attempted, keeping the category.
+    pub fn context(self, context: impl fmt::Display) -> Self {
+        self.map_message(|message| format!("{}: {}", context, message))
+    }
+
+    /// Drop the error position, for errors from SQL FastDB rewrote before
+    /// running: the position points into the rewrite, not what the user wrote.
+    pub fn without_position(mut self) -> Self {
+        if let AppError::Sql { position, .. } = &mut self {
+            *position = None;
+        }
+        self
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Sql {
+                code,
+                message,
+                detail,
+                hint,
+                ..
+            } => {
+                write!(f, "{}: {}", code, message)?;
+                if let Some(detail) = detail {
+                    write!(f, "\nDetail: {}", detail)?;
+                }
+                if let Some(hint) = hint {
+                    write!(f, "\nHint: {}", hint)?;
+                }
+                Ok(())
+            }
+            other => f.write_str(other.message()),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<tokio_postgres::Error> for AppError {
+    fn from(error: tokio_postgres::Error) -> Self {
+        if let Some(db_err) = error.as_db_error() {
+            let code = db_err.code().code();
+            // 28000 invalid_authorization_specification, 28P01 invalid_password
+            if code.starts_with("28") {
+                return AppError::auth(db_err.message());
+            }
+            return AppError::Sql {
+                code: code.to_string(),
+                message: db_err.message().to_string(),
+                detail: db_err.detail().map(str::to_string),
+                hint: db_err.hint().map(str::to_string),
+                // Internal positions point into a query the server generated
+                position: match db_err.position() {
+                    Some(tokio_postgres::error::ErrorPosition::Original(position)) => Some(*position),
+                    _ => None,
+                },
+                severity: Some(db_err.severity().into()),
+            };
+        }
+
+        let is_io = std::error::Error::source(&error)
+            .map(|source| source.is::<std::io::Error>())
+            .unwrap_or(false);
+        if error.is_closed() || is_io {
+            AppError::connection(error.to_string())
+        } else if error.to_string().starts_with("authentication") {
+            AppError::auth(error.to_string())
+        } else {
+            AppError::internal(error.to_string())
+        }
+    }
+}
+
+impl From<crate::postgres::QueryError> for AppError {
+    fn from(error: crate::postgres::QueryError) -> Self {
+        let rewritten = error.rewritten;
+        let error = AppError::from(error.error);
+        if rewritten {
+            error.without_position()
+        } else {
+            error
+        }
+    }
+}
+
+impl From<deadpool_postgres::PoolError> for AppError {
+    fn from(error: deadpool_postgres::PoolError) -> Self {
+        match error {
+            deadpool_postgres::PoolError::Backend(e) => e.into(),
+            other => AppError::connection(other.to_string()),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(error: rusqlite::Error) -> Self {
+        AppError::io(error.to_string())
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(error: std::io::Error) -> Self {
+        AppError::io(error.to_string())
+    }
+}
+
+impl From<tauri::Error> for AppError {
+    fn from(error: tauri::Error) -> Self {
+        AppError::internal(error.to_string())
+    }
+}
+
+impl From<Box<dyn std::error::Error>> for AppError {
+    fn from(error: Box<dyn std::error::Error>) -> Self {
+        let error = match error.downcast::<tokio_postgres::Error>() {
+            Ok(e) => return (*e).into(),
+            Err(other) => other,
+        };
+        let error = match error.downcast::<crate::postgres::QueryError>() {
+            Ok(e) => return (*e).into(),
+            Err(other) => other,
+        };
+        let error = match error.downcast::<deadpool_postgres::PoolError>() {
+            Ok(e) => return (*e).into(),
+            Err(other) => other,
+        };
+        let error = match error.downcast::<rusqlite::Error>() {
+            Ok(e) => return (*e).into(),
+            Err(other) => other,
+        };
+        let error = match error.downcast::<std::io::Error>() {
+            Ok(e) => return (*e).into(),
+            Err(other) => other,
+        };
+        match error.downcast::<AppError>() {
+            Ok(e) => *e,
+            Err(other) => AppError::internal(other.to_string()),
+        }
+    }
+}
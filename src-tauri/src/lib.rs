@@ -1,5 +1,10 @@
+pub mod arrow_export;
 pub mod commands;
 pub mod db;
 pub mod credentials;
+pub mod decode;
+pub mod error;
+pub mod params;
 pub mod postgres;
 pub mod schema;
+pub mod test_data;
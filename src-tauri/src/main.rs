@@ -1,7 +1,31 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use fastdb::{commands, db};
-use tauri::{WebviewWindowBuilder, WebviewUrl};
+use fastdb::{commands, db, postgres};
+use std::time::Duration;
+use tauri::{Emitter, RunEvent, WebviewWindowBuilder, WebviewUrl};
+
+/// Upper bound on exit cleanup, so a hung connection can't block quitting.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Close Postgres pools and checkpoint the SQLite WAL before exiting. Runs on
+/// its own thread (the event loop callback can't block on the runtime) and is
+/// abandoned after `SHUTDOWN_TIMEOUT`.
+fn shutdown(runtime: tokio::runtime::Handle) {
+    let (done_tx, done_rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        runtime.block_on(postgres::close_all_pools());
+        match db::checkpoint_wal() {
+            Ok(true) => {}
+            Ok(false) => eprintln!("WAL checkpoint incomplete: database busy"),
+            Err(e) => eprintln!("Failed to checkpoint WAL: {}", e),
+        }
+        let _ = done_tx.send(());
+    });
+
+    if done_rx.recv_timeout(SHUTDOWN_TIMEOUT).is_err() {
+        eprintln!("Shutdown cleanup timed out; exiting anyway");
+    }
+}
 
 #[tokio::main]
 async fn main() {
@@ -11,6 +35,11 @@ async fn main() {
     // Start pool cleanup task
     // postgres::start_cleanup_task();
 
+    // Drop cancel tokens left behind by abandoned queries
+    postgres::start_cancel_token_sweep();
+
+    let runtime = tokio::runtime::Handle::current();
+
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .setup(|app| {
@@ -27,6 +56,25 @@ async fn main() {
                 .build()
                 .expect("Failed to create main window");
 
+            // Let the sidebar follow connection status without polling
+            let app_handle = app.handle().clone();
+            postgres::set_state_listener(move |server_id, state| {
+                #[derive(serde::Serialize, Clone)]
+                struct ConnectionStateEvent {
+                    #[serde(rename = "serverId")]
+                    server_id: String,
+                    state: postgres::ConnectionState,
+                }
+
+                let event = ConnectionStateEvent {
+                    server_id: server_id.to_string(),
+                    state,
+                };
+                if let Err(e) = app_handle.emit("connection_state", event) {
+                    eprintln!("Failed to emit connection_state: {}", e);
+                }
+            });
+
             // Show window after 100ms to avoid white flash
             std::thread::spawn(move || {
                 std::thread::sleep(std::time::Duration::from_millis(100));
@@ -37,15 +85,72 @@ async fn main() {
         })
         .invoke_handler(tauri::generate_handler![
             commands::get_cached_servers,
+            commands::toggle_favorite_server,
+            commands::set_expensive_query_guard,
+            commands::set_server_timezone,
+            commands::set_connect_timeout,
+            commands::delete_servers,
+            commands::set_busy_timeout,
+            commands::set_cancel_token_ttl,
+            commands::format_sql,
+            commands::get_setting,
+            commands::set_setting,
             commands::get_dashboard_metrics,
+            commands::terminate_idle_connections,
+            commands::get_operation_progress,
+            commands::get_wait_events,
+            commands::column_histogram,
+            commands::get_vacuum_health,
+            commands::get_recently_modified_tables,
+            commands::get_bloat_estimate,
+            commands::generate_server_report,
+            commands::get_table_privileges,
+            commands::get_table_dependencies,
+            commands::get_insertable_columns,
+            commands::get_all_dashboard_metrics,
+            commands::get_schema_sizes,
+            commands::get_table_size_breakdown,
+            commands::count_table_rows,
+            commands::compare_row_counts,
+            commands::get_sequences,
+            commands::get_enum_values,
+            commands::find_duplicate_indexes,
+            commands::find_unindexed_fks,
+            commands::find_tables_without_pk,
+            commands::validate_sql,
+            commands::diagnose_seq_scan,
+            commands::export_query_plan,
+            commands::get_session_settings,
+            commands::get_prepared_statements,
             commands::connect_to_server,
             commands::execute_query,
+            commands::execute_scalar,
+            commands::execute_query_sampled,
+            commands::execute_parameterized_query,
+            commands::insert_rows,
+            commands::generate_test_data,
+            commands::benchmark_query,
+            commands::infer_jsonb_schema,
             commands::cancel_query,
+            commands::retry_last_query,
+            commands::list_active_pools,
+            commands::drop_pool,
             commands::get_sql_file_metadata,
             commands::execute_sql_file,
+            commands::execute_sql_directory,
+            commands::estimate_export_size,
             commands::export_schema_sql,
             commands::export_table_sql,
+            commands::export_tables,
+            commands::export_table_ndjson,
+            commands::export_query_arrow,
+            commands::stream_query_csv,
+            commands::add_column,
+            commands::rename_object,
+            commands::clone_table_structure,
+            commands::generate_alter,
             commands::get_schema_tree,
+            commands::search_schemas,
             commands::refresh_schema,
             commands::get_query_history,
             commands::get_tables,
@@ -53,13 +158,34 @@ async fn main() {
             commands::get_columns,
             commands::get_indexes,
             commands::get_primary_key_columns,
+            commands::get_constraints,
+            commands::is_table_editable,
+            commands::preview_table_as_role,
+            commands::preview_delete,
+            commands::check_fk_references,
             commands::get_autocomplete_items,
+            commands::refresh_autocomplete,
             commands::add_server,
             commands::get_query_history_dedup,
+            commands::get_top_queries,
             commands::search_query_history,
+            commands::save_tab,
+            commands::get_tabs,
+            commands::get_all_tabs,
+            commands::delete_tab,
             commands::delete_query_history_entry,
             commands::clear_query_history,
+            commands::clear_all_history,
+            commands::vacuum_local_db,
+            commands::export_query_history,
+            commands::import_query_history,
+            commands::export_session_script,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(move |_app, event| {
+            if let RunEvent::Exit = event {
+                shutdown(runtime.clone());
+            }
+        });
 }
@@ -0,0 +1,222 @@
+//! Binding JSON values from the frontend as query parameters.
+//!
+//! Values are converted against the parameter types Postgres inferred when
+//! preparing the statement, so `1` binds as `int2`, `int4` or `int8` as the
+//! query needs. JSON arrays bind to Postgres arrays (`WHERE id = ANY($1)`)
+//! and `null` binds as a NULL of whatever type the parameter has.
+
+use bytes::BytesMut;
+use serde_json::Value;
+use std::error::Error;
+use tokio_postgres::types::{to_sql_checked, IsNull, Kind, ToSql, Type};
+
+pub type SqlParam = Box<dyn ToSql + Sync + Send>;
+
+/// A NULL that binds to a parameter of any type.
+#[derive(Debug)]
+struct SqlNull;
+
+impl ToSql for SqlNull {
+    fn to_sql(&self, _ty: &Type, _out: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        Ok(IsNull::Yes)
+    }
+
+    fn accepts(_ty: &Type) -> bool {
+        true
+    }
+
+    to_sql_checked!();
+}
+
+/// JSON kind name for error messages.
+fn kind_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Convert `value` for a parameter of type `ty`. Types without a binary
+/// encoder here can still be bound by casting in SQL, e.g. `$1::text::date`.
+pub fn json_to_param(value: &Value, ty: &Type) -> Result<SqlParam, String> {
+    match (value, ty.kind()) {
+        (Value::Null, _) => Ok(Box::new(SqlNull)),
+        (Value::Array(items), Kind::Array(member)) => array_param(items, member),
+        (Value::Array(_), _) => Err(format!("got an array but the parameter is {}", ty.name())),
+        _ => scalar_param(value, ty),
+    }
+}
+
+fn mismatch(value: &Value, ty: &Type) -> String {
+    format!("can't bind a {} to a {} parameter", kind_name(value), ty.name())
+}
+
+fn unsupported(ty: &Type) -> String {
+    format!(
+        "{} parameters can't be bound from JSON; cast in SQL instead, e.g. $1::text::{}",
+        ty.name(),
+        ty.name()
+    )
+}
+
+fn scalar_param(value: &Value, ty: &Type) -> Result<SqlParam, String> {
+    let param: Option<SqlParam> = match ty.name() {
+        "bool" => value.as_bool().map(|v| Box::new(v) as SqlParam),
+        "int2" => value.as_i64().and_then(|v| i16::try_from(v).ok()).map(|v| Box::new(v) as SqlParam),
+        "int4" => value.as_i64().and_then(|v| i32::try_from(v).ok()).map(|v| Box::new(v) as SqlParam),
+        "int8" => value.as_i64().map(|v| Box::new(v) as SqlParam),
+        "float4" => value.as_f64().map(|v| Box::new(v as f32) as SqlParam),
+        "float8" => value.as_f64().map(|v| Box::new(v) as SqlParam),
+        "json" | "jsonb" => Some(Box::new(value.clone()) as SqlParam),
+        _ if String::accepts(ty) => value.as_str().map(|v| Box::new(v.to_string()) as SqlParam),
+        _ => return Err(unsupported(ty)),
+    };
+    param.ok_or_else(|| mismatch(value, ty))
+}
+
+/// Convert every element with `convert`, keeping JSON nulls as NULL elements.
+fn collect_elements<T>(
+    items: &[Value],
+    member: &Type,
+    convert: impl Fn(&Value) -> Option<T>,
+) -> Result<Vec<Option<T>>, String> {
+    items
+        .iter()
+        .map(|item| match item {
+            Value::Null => Ok(None),
+            _ => convert(item).map(Some).ok_or_else(|| mismatch(item, member)),
+        })
+        .collect()
+}
+
+fn array_param(items: &[Value], member: &Type) -> Result<SqlParam, String> {
+    // Postgres arrays hold a single element type
+    let mut kinds = items.iter().filter(|item| !item.is_null()).map(kind_name);
+    if let Some(first) = kinds.next() {
+        if let Some(other) = kinds.find(|kind| *kind != first) {
+            return Err(format!(
+                "array mixes {} and {} values; all elements must have the same type",
+                first, other
+            ));
+        }
+    }
+
+    let param: SqlParam = match member.name() {
+        "bool" => Box::new(collect_elements(items, member, Value::as_bool)?),
+        "int2" => Box::new(collect_elements(items, member, |v| {
+            v.as_i64().and_then(|v| i16::try_from(v).ok())
+        })?),
+        "int4" => Box::new(collect_elements(items, member, |v| {
+            v.as_i64().and_then(|v| i32::try_from(v).ok())
+        })?),
+        "int8" => Box::new(collect_elements(items, member, Value::as_i64)?),
+        "float4" => Box::new(collect_elements(items, member, |v| v.as_f64().map(|v| v as f32))?),
+        "float8" => Box::new(collect_elements(items, member, Value::as_f64)?),
+        _ if String::accepts(member) => Box::new(collect_elements(items, member, |v| {
+            v.as_str().map(str::to_string)
+        })?),
+        _ => return Err(unsupported(member)),
+    };
+    Ok(param)
+}
+
+/// Whether values for a parameter of type `ty` can be bound from JSON
+/// directly, without a cast in the SQL.
+pub fn can_bind(ty: &Type) -> bool {
+    let scalar = |ty: &Type| {
+        matches!(ty.name(), "bool" | "int2" | "int4" | "int8" | "float4" | "float8") || String::accepts(ty)
+    };
+    match ty.kind() {
+        Kind::Array(member) => scalar(member),
+        _ => scalar(ty) || matches!(ty.name(), "json" | "jsonb"),
+    }
+}
+
+/// Convert all parameters for a prepared statement, checking the count.
+pub fn bind_params(values: &[Value], types: &[Type]) -> Result<Vec<SqlParam>, String> {
+    if values.len() != types.len() {
+        return Err(format!(
+            "Query expects {} parameter{} but {} {} given",
+            types.len(),
+            if types.len() == 1 { "" } else { "s" },
+            values.len(),
+            if values.len() == 1 { "was" } else { "were" }
+        ));
+    }
+    values
+        .iter()
+        .zip(types)
+        .enumerate()
+        .map(|(idx, (value, ty))| json_to_param(value, ty).map_err(|e| format!("Parameter ${}: {}", idx + 1, e)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn encode(value: &Value, ty: &Type) -> Result<Option<Vec<u8>>, String> {
+        let param = json_to_param(value, ty)?;
+        let mut out = BytesMut::new();
+        match param.to_sql_checked(ty, &mut out).map_err(|e| e.to_string())? {
+            IsNull::Yes => Ok(None),
+            IsNull::No => Ok(Some(out.to_vec())),
+        }
+    }
+
+    #[test]
+    fn test_scalars_follow_parameter_type() {
+        assert_eq!(encode(&json!(7), &Type::INT4), Ok(Some(7i32.to_be_bytes().to_vec())));
+        assert_eq!(encode(&json!(7), &Type::INT8), Ok(Some(7i64.to_be_bytes().to_vec())));
+        assert_eq!(encode(&json!("ab"), &Type::TEXT), Ok(Some(b"ab".to_vec())));
+        assert!(encode(&json!(70000), &Type::INT2).is_err());
+        assert!(encode(&json!("7"), &Type::INT4).is_err());
+    }
+
+    #[test]
+    fn test_null_binds_to_any_type() {
+        assert_eq!(encode(&Value::Null, &Type::INT4), Ok(None));
+        assert_eq!(encode(&Value::Null, &Type::TIMESTAMPTZ), Ok(None));
+    }
+
+    #[test]
+    fn test_arrays_bind_to_postgres_arrays() {
+        let ints = encode(&json!([1, null, 3]), &Type::INT4_ARRAY).unwrap().unwrap();
+        let mut expected = Vec::new();
+        expected.extend_from_slice(&1i32.to_be_bytes()); // dimensions
+        expected.extend_from_slice(&1i32.to_be_bytes()); // has nulls
+        expected.extend_from_slice(&Type::INT4.oid().to_be_bytes());
+        expected.extend_from_slice(&3i32.to_be_bytes()); // length
+        expected.extend_from_slice(&1i32.to_be_bytes()); // lower bound
+        for element in [Some(1i32), None, Some(3)] {
+            match element {
+                Some(v) => {
+                    expected.extend_from_slice(&4i32.to_be_bytes());
+                    expected.extend_from_slice(&v.to_be_bytes());
+                }
+                None => expected.extend_from_slice(&(-1i32).to_be_bytes()),
+            }
+        }
+        assert_eq!(ints, expected);
+
+        assert!(encode(&json!(["a", "b"]), &Type::TEXT_ARRAY).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_mixed_arrays_are_rejected() {
+        let err = encode(&json!([1, "two"]), &Type::INT4_ARRAY).unwrap_err();
+        assert!(err.contains("mixes number and string"), "{}", err);
+        assert!(encode(&json!([1, 2]), &Type::INT4).is_err());
+    }
+
+    #[test]
+    fn test_bind_params_checks_count() {
+        let err = bind_params(&[json!(1)], &[Type::INT4, Type::TEXT]).unwrap_err();
+        assert_eq!(err, "Query expects 2 parameters but 1 was given");
+    }
+}
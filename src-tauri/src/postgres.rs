@@ -1,65 +1,501 @@
-use deadpool_postgres::{Config, ManagerConfig, Pool, RecyclingMethod, Runtime, PoolConfig};
-use deadpool::managed::QueueMode;
-use tokio_postgres::{NoTls, CancelToken};
+use crate::db::{Server, DEFAULT_CONNECT_TIMEOUT_MS, DEFAULT_KEEPALIVE_IDLE_SECS};
+use deadpool_postgres::{ClientWrapper, Config, ManagerConfig, RecyclingMethod, Runtime, PoolConfig};
+use deadpool::managed::{Metrics, QueueMode, RecycleResult};
+use tokio_postgres::{AsyncMessage, NoTls, CancelToken, IsolationLevel};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use serde::Serialize;
 
 static POOLS: once_cell::sync::Lazy<Arc<Mutex<HashMap<String, Pool>>>> =
     once_cell::sync::Lazy::new(|| Arc::new(Mutex::new(HashMap::new())));
 
-static CANCEL_TOKENS: once_cell::sync::Lazy<Arc<Mutex<HashMap<String, CancelToken>>>> =
+pub type Pool = deadpool::managed::Pool<NoticeManager>;
+pub type Object = deadpool::managed::Object<NoticeManager>;
+
+/// Notices the server sent on one connection: `None` until a statement asks
+/// for them with `collect_notices`, so notices nobody reads aren't kept.
+type NoticeSink = std::sync::Mutex<Option<Vec<Notice>>>;
+
+/// Notice sinks by pooled connection, keyed by the address of its statement
+/// cache, which lives exactly as long as the connection. The connection task
+/// holds the only strong reference.
+static NOTICE_SINKS: once_cell::sync::Lazy<std::sync::Mutex<HashMap<usize, std::sync::Weak<NoticeSink>>>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(HashMap::new()));
+
+/// Notices kept per statement; a `RAISE NOTICE` in a long loop would
+/// otherwise grow the result without bound.
+const MAX_NOTICES: usize = 1_000;
+
+/// A message the server sent while a statement ran: `RAISE NOTICE` output,
+/// warnings, or `DEBUG`/`LOG` lines when `client_min_messages` asks for them.
+#[derive(Serialize, Clone, Debug)]
+pub struct Notice {
+    pub severity: String,
+    pub code: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+fn notice_sink_key(client: &ClientWrapper) -> usize {
+    Arc::as_ptr(&client.statement_cache) as usize
+}
+
+/// Pool manager that opens connections itself so their notices reach
+/// `NOTICE_SINKS` instead of being dropped by the connection task;
+/// recycling is left to deadpool-postgres.
+pub struct NoticeManager {
+    pg_config: tokio_postgres::Config,
+    recycler: deadpool_postgres::Manager,
+}
+
+impl NoticeManager {
+    fn new(pg_config: tokio_postgres::Config, config: ManagerConfig) -> Self {
+        let recycler = deadpool_postgres::Manager::from_config(pg_config.clone(), NoTls, config);
+        Self { pg_config, recycler }
+    }
+}
+
+impl deadpool::managed::Manager for NoticeManager {
+    type Type = ClientWrapper;
+    type Error = tokio_postgres::Error;
+
+    async fn create(&self) -> Result<ClientWrapper, tokio_postgres::Error> {
+        let (client, mut connection) = self.pg_config.connect(NoTls).await?;
+        let sink = Arc::new(NoticeSink::default());
+        let task_sink = sink.clone();
+        let conn_task = tokio::spawn(async move {
+            while let Some(message) = std::future::poll_fn(|cx| connection.poll_message(cx)).await {
+                match message {
+                    Ok(AsyncMessage::Notice(notice)) => {
+                        let mut collected = task_sink.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                        if let Some(notices) = collected.as_mut().filter(|notices| notices.len() < MAX_NOTICES) {
+                            notices.push(Notice {
+                                severity: notice.severity().to_string(),
+                                code: notice.code().code().to_string(),
+                                message: notice.message().to_string(),
+                                detail: notice.detail().map(str::to_string),
+                                hint: notice.hint().map(str::to_string),
+                            });
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        eprintln!("Connection error: {}", e);
+                        break;
+                    }
+                }
+            }
+        });
+
+        let client = ClientWrapper::new(client, conn_task);
+        let mut sinks = NOTICE_SINKS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        sinks.retain(|_, sink| sink.strong_count() > 0);
+        sinks.insert(notice_sink_key(&client), Arc::downgrade(&sink));
+        Ok(client)
+    }
+
+    async fn recycle(&self, client: &mut ClientWrapper, metrics: &Metrics) -> RecycleResult<tokio_postgres::Error> {
+        deadpool::managed::Manager::recycle(&self.recycler, client, metrics).await
+    }
+
+    fn detach(&self, client: &mut ClientWrapper) {
+        let mut sinks = NOTICE_SINKS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        sinks.remove(&notice_sink_key(client));
+    }
+}
+
+/// Start keeping the notices `client`'s connection receives, dropping any
+/// left over from an earlier statement.
+fn collect_notices(client: &ClientWrapper) -> Option<Arc<NoticeSink>> {
+    let sinks = NOTICE_SINKS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let sink = sinks.get(&notice_sink_key(client))?.upgrade()?;
+    *sink.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(Vec::new());
+    Some(sink)
+}
+
+/// Stop collecting and return what `collect_notices` gathered.
+fn take_notices(sink: Option<Arc<NoticeSink>>) -> Vec<Notice> {
+    sink.and_then(|sink| sink.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take())
+        .unwrap_or_default()
+}
+
+type CancelTokens = HashMap<String, (CancelToken, Instant)>;
+
+/// Cancel tokens by query id, with when they were registered so abandoned
+/// ones can be swept.
+static CANCEL_TOKENS: once_cell::sync::Lazy<Arc<Mutex<CancelTokens>>> =
     once_cell::sync::Lazy::new(|| Arc::new(Mutex::new(HashMap::new())));
 
-fn pool_key(server_id: &str, dbname: &str) -> String {
-    format!("{}::{}", server_id, dbname)
+/// Default age after which a cancel token is dropped. Long enough that a
+/// query still running is unlikely to lose its cancel button.
+pub const DEFAULT_CANCEL_TOKEN_TTL: Duration = Duration::from_secs(6 * 60 * 60);
+
+static CANCEL_TOKEN_TTL_SECS: AtomicU64 = AtomicU64::new(DEFAULT_CANCEL_TOKEN_TTL.as_secs());
+
+/// How often the sweep looks for expired cancel tokens.
+const CANCEL_TOKEN_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// SQL of the most recent `execute_query` per server that hasn't succeeded
+/// yet, so it can be re-run after a reconnect. Results are never kept.
+static LAST_ATTEMPTS: once_cell::sync::Lazy<Arc<Mutex<HashMap<String, LastAttempt>>>> =
+    once_cell::sync::Lazy::new(|| Arc::new(Mutex::new(HashMap::new())));
+
+/// A server's connection status as reported to the frontend.
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionState {
+    /// A connection was checked out successfully
+    Connected,
+    /// The server's pools were closed
+    Disconnected,
+    /// Creating a pool, checking out a connection or running a query failed
+    /// at the connection level
+    Error,
+}
+
+type StateListener = Box<dyn Fn(&str, ConnectionState) + Send + Sync>;
+
+/// Set once at startup; keeps this module free of any Tauri types.
+static STATE_LISTENER: once_cell::sync::OnceCell<StateListener> = once_cell::sync::OnceCell::new();
+
+static LAST_STATES: once_cell::sync::Lazy<std::sync::Mutex<HashMap<String, ConnectionState>>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(HashMap::new()));
+
+/// Register the callback told about connection state transitions.
+pub fn set_state_listener(listener: impl Fn(&str, ConnectionState) + Send + Sync + 'static) {
+    let _ = STATE_LISTENER.set(Box::new(listener));
+}
+
+/// Record a server's connection state, notifying the listener only when it
+/// differs from the last one reported.
+pub fn report_state(server_id: &str, state: ConnectionState) {
+    let previous = LAST_STATES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .insert(server_id.to_string(), state);
+    if previous != Some(state) {
+        if let Some(listener) = STATE_LISTENER.get() {
+            listener(server_id, state);
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct LastAttempt {
+    pub sql: String,
+    pub database: String,
+    pub schema_name: Option<String>,
+}
+
+pub async fn remember_attempt(server_id: &str, attempt: LastAttempt) {
+    LAST_ATTEMPTS.lock().await.insert(server_id.to_string(), attempt);
+}
+
+pub async fn forget_attempt(server_id: &str) {
+    LAST_ATTEMPTS.lock().await.remove(server_id);
+}
+
+pub async fn last_attempt(server_id: &str) -> Option<LastAttempt> {
+    LAST_ATTEMPTS.lock().await.get(server_id).cloned()
+}
+
+/// Pools are keyed `server_id::dbname`, plus `::role=<role>` for pools whose
+/// connections start as a specific role and `::replica` for pools on the
+/// server's read replica. Everything for a server shares the `server_id::`
+/// prefix, which `drop_pools` relies on.
+fn pool_key(server_id: &str, dbname: &str, role: Option<&str>, replica: bool) -> String {
+    let mut key = format!("{}::{}", server_id, dbname);
+    if let Some(role) = role {
+        key.push_str(&format!("::role={}", role));
+    }
+    if replica {
+        key.push_str("::replica");
+    }
+    key
+}
+
+/// A live pool as reported by `list_pools`.
+pub struct PoolInfo {
+    pub server_id: String,
+    pub database: String,
+    pub role: Option<String>,
+    pub replica: bool,
+    pub status: deadpool_postgres::Status,
+}
+
+/// Inverse of `pool_key`.
+fn pool_info(key: &str, status: deadpool_postgres::Status) -> PoolInfo {
+    let mut parts = key.split("::");
+    let server_id = parts.next().unwrap_or(key).to_string();
+    let database = parts.next().unwrap_or("").to_string();
+    let mut role = None;
+    let mut replica = false;
+    for part in parts {
+        if part == "replica" {
+            replica = true;
+        } else if let Some(name) = part.strip_prefix("role=") {
+            role = Some(name.to_string());
+        }
+    }
+    PoolInfo {
+        server_id,
+        database,
+        role,
+        replica,
+        status,
+    }
 }
 
 pub async fn get_or_create_pool(
-    server_id: &str,
-    host: &str,
-    port: u16,
-    user: &str,
+    server: &Server,
+    password: &str,
+    dbname: &str,
+) -> Result<Pool, Box<dyn std::error::Error>> {
+    get_or_create_pool_for_role(server, password, dbname, None).await
+}
+
+/// Like `get_or_create_pool`, but every connection in the pool starts with
+/// `role` as its session role (via the `role` startup option), so role
+/// switching doesn't need a `SET ROLE` on shared connections.
+pub async fn get_or_create_pool_for_role(
+    server: &Server,
     password: &str,
     dbname: &str,
+    role: Option<&str>,
+) -> Result<Pool, Box<dyn std::error::Error>> {
+    get_or_create_keyed_pool(server, password, dbname, role, false).await
+}
+
+/// Pool on the server's read replica, or `None` when no replica is configured.
+pub async fn get_or_create_replica_pool(
+    server: &Server,
+    password: &str,
+    dbname: &str,
+) -> Result<Option<Pool>, Box<dyn std::error::Error>> {
+    if server.replica_host.as_deref().map(str::trim).unwrap_or("").is_empty() {
+        return Ok(None);
+    }
+    get_or_create_keyed_pool(server, password, dbname, None, true)
+        .await
+        .map(Some)
+}
+
+async fn get_or_create_keyed_pool(
+    server: &Server,
+    password: &str,
+    dbname: &str,
+    role: Option<&str>,
+    replica: bool,
 ) -> Result<Pool, Box<dyn std::error::Error>> {
     let mut pools = POOLS.lock().await;
-    let key = pool_key(server_id, dbname);
+    let key = pool_key(&server.id, dbname, role, replica);
 
     if let Some(pool) = pools.get(&key) {
         return Ok(pool.clone());
     }
 
+    // Bound both the TCP/handshake and the wait for a free pooled connection so
+    // an unreachable host fails fast instead of hanging on the OS default.
+    let connect_timeout = Duration::from_millis(
+        server
+            .connect_timeout_ms
+            .filter(|ms| *ms > 0)
+            .unwrap_or(DEFAULT_CONNECT_TIMEOUT_MS) as u64,
+    );
+
+    let (host, port) = match (replica, server.replica_host.as_deref()) {
+        (true, Some(host)) => (host.trim().to_string(), server.replica_port.unwrap_or(server.port)),
+        _ => (server.host.clone(), server.port),
+    };
+
+    validate_host(&host).inspect_err(|_| report_state(&server.id, ConnectionState::Error))?;
+
     let mut cfg = Config::new();
-    cfg.host = Some(host.to_string());
-    cfg.port = Some(port);
-    cfg.user = Some(user.to_string());
-    cfg.password = Some(password.to_string());
+    // tokio-postgres treats a host starting with `/` as a Unix socket
+    // directory and connects to `<dir>/.s.PGSQL.<port>`
+    cfg.host = Some(host);
+    cfg.port = Some(port as u16);
+    cfg.user = Some(server.username.clone());
+    // Peer and trust auth over a socket need no password
+    cfg.password = Some(password.to_string()).filter(|password| !password.is_empty());
     cfg.dbname = Some(dbname.to_string());
+    cfg.connect_timeout = Some(connect_timeout);
+    // Keep idle pooled connections alive through NAT/firewall timeouts and
+    // notice dead ones before a query is sent down them
+    let keepalive_idle = server.keepalive_idle_secs.unwrap_or(DEFAULT_KEEPALIVE_IDLE_SECS);
+    cfg.keepalives = Some(keepalive_idle > 0);
+    if keepalive_idle > 0 {
+        cfg.keepalives_idle = Some(Duration::from_secs(keepalive_idle as u64));
+    }
+    // Startup options are space separated; escape spaces inside values
+    let escape = |value: &str| value.replace('\\', "\\\\").replace(' ', "\\ ");
+    let mut options = Vec::new();
+    if let Some(role) = role {
+        options.push(format!("-c role={}", escape(role)));
+    }
+    // Set at startup so every connection, including reconnects, has it
+    if let Some(timezone) = server_timezone(server) {
+        options.push(format!("-c TimeZone={}", escape(timezone)));
+    }
+    // Behind a transaction pooler `local_settings` applies these per
+    // transaction instead, since each may run on a different backend
+    if !options.is_empty() && !server.uses_transaction_pooling() {
+        cfg.options = Some(options.join(" "));
+    }
+    // Behind a transaction pooler the backend changes between checkouts, so
+    // verify the link with a round trip instead of trusting the socket state.
+    // The round trip is a ROLLBACK so a command that returned early from a
+    // pinned transaction can't hold a pooler backend while sitting idle.
     cfg.manager = Some(ManagerConfig {
-        recycling_method: RecyclingMethod::Fast,
+        recycling_method: if server.uses_transaction_pooling() {
+            RecyclingMethod::Custom("ROLLBACK".to_string())
+        } else {
+            RecyclingMethod::Fast
+        },
     });
     cfg.pool = Some(PoolConfig {
         max_size: 10,
-        timeouts: deadpool_postgres::Timeouts::default(),
+        timeouts: deadpool_postgres::Timeouts {
+            wait: Some(connect_timeout),
+            create: Some(connect_timeout),
+            recycle: None,
+        },
         queue_mode: QueueMode::Fifo,
     });
 
-    let pool = cfg.create_pool(Some(Runtime::Tokio1), NoTls)?;
+    let build = || -> Result<Pool, Box<dyn std::error::Error>> {
+        let manager = NoticeManager::new(cfg.get_pg_config()?, cfg.get_manager_config());
+        Ok(Pool::builder(manager)
+            .config(cfg.get_pool_config())
+            .runtime(Runtime::Tokio1)
+            .build()?)
+    };
+    let pool = build().inspect_err(|_| report_state(&server.id, ConnectionState::Error))?;
     pools.insert(key, pool.clone());
     Ok(pool)
 }
 
+/// True when `host` names a Unix socket directory rather than a TCP host.
+pub fn is_socket_host(host: &str) -> bool {
+    host.trim_start().starts_with('/')
+}
+
+/// Check a server or replica host before connecting. Socket hosts must be the
+/// directory holding the socket (e.g. `/var/run/postgresql`), not the
+/// `.s.PGSQL.<port>` file itself.
+pub fn validate_host(host: &str) -> Result<(), String> {
+    let host = host.trim();
+    if host.is_empty() {
+        return Err("Host is required".to_string());
+    }
+    if is_socket_host(host) {
+        if !cfg!(unix) {
+            return Err("Unix socket connections aren't supported on this platform".to_string());
+        }
+        if host.contains(".s.PGSQL.") {
+            return Err(format!(
+                "'{}' is a socket file; use its directory and set the port instead",
+                host
+            ));
+        }
+        return Ok(());
+    }
+    if host.chars().any(char::is_whitespace) {
+        return Err(format!("Invalid host '{}'", host));
+    }
+    Ok(())
+}
+
+/// Per-call options for `execute_query`.
+#[derive(Default)]
+pub struct ExecOptions<'a> {
+    /// Registers a cancel token so `cancel_query` can stop the statement
+    pub query_id: Option<&'a str>,
+    pub schema_name: Option<&'a str>,
+    pub isolation_level: Option<IsolationLevel>,
+    /// Skip the read replica, e.g. to read rows just written on the primary
+    pub force_primary: bool,
+    /// A `client_min_messages` level, set for the statement's transaction.
+    /// Must be one of the levels Postgres accepts; the caller validates it.
+    pub client_min_messages: Option<&'a str>,
+}
+
 pub enum QueryExecutionResult {
-    Rows(Vec<tokio_postgres::Row>),
+    /// `types` are the statement's own column types, which differ from the
+    /// rows' where the text fallback read a column as `text`
+    Rows {
+        rows: Vec<tokio_postgres::Row>,
+        types: Vec<tokio_postgres::types::Type>,
+    },
     Affected(u64),
 }
 
+/// A failed statement. `rewritten` is set when it was the rewrite from
+/// `text_fallback_query` that failed, so an error position points into that
+/// rather than the SQL as given.
+#[derive(Debug)]
+pub struct QueryError {
+    pub error: tokio_postgres::Error,
+    pub rewritten: bool,
+}
+
+impl From<tokio_postgres::Error> for QueryError {
+    fn from(error: tokio_postgres::Error) -> Self {
+        QueryError { error, rewritten: false }
+    }
+}
+
+impl std::ops::Deref for QueryError {
+    type Target = tokio_postgres::Error;
+
+    fn deref(&self) -> &Self::Target {
+        &self.error
+    }
+}
+
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl std::error::Error for QueryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
 fn quote_ident(ident: &str) -> String {
     format!("\"{}\"", ident.replace('"', "\"\""))
 }
 
+fn server_timezone(server: &Server) -> Option<&str> {
+    server.timezone.as_deref().map(str::trim).filter(|tz| !tz.is_empty())
+}
+
+/// `SET LOCAL` statements standing in for the startup options of a pool on a
+/// server behind a transaction pooler; `None` elsewhere or when there is
+/// nothing to set.
+pub fn local_settings(server: &Server, role: Option<&str>) -> Option<String> {
+    if !server.uses_transaction_pooling() {
+        return None;
+    }
+    let mut settings = Vec::new();
+    if let Some(role) = role {
+        settings.push(format!("SET LOCAL ROLE {}", quote_ident(role)));
+    }
+    if let Some(timezone) = server_timezone(server) {
+        settings.push(format!("SET LOCAL TimeZone TO '{}'", timezone.replace('\'', "''")));
+    }
+    (!settings.is_empty()).then(|| settings.join("; "))
+}
+
 fn strip_leading_comments(sql: &str) -> &str {
     let mut remaining = sql;
     loop {
@@ -84,47 +520,189 @@ fn strip_leading_comments(sql: &str) -> &str {
     }
 }
 
-pub async fn execute_query(
-    server_id: &str,
-    host: &str,
-    port: u16,
-    user: &str,
-    password: &str,
-    dbname: &str,
-    sql: &str,
-    query_id: Option<&str>,
-    schema_name: Option<&str>,
-) -> Result<QueryExecutionResult, Box<dyn std::error::Error>> {
-    // Ensure pool exists
-    get_or_create_pool(server_id, host, port, user, password, dbname).await?;
-    let pool = {
-        let pools = POOLS.lock().await;
-        pools
-            .get(&pool_key(server_id, dbname))
-            .cloned()
-            .ok_or("Pool not found for this server")?
-    };
-    let mut client = pool.get().await?;
+/// True when a failure came from the connection rather than the statement:
+/// the server never answered with an error, the socket was closed or the
+/// transport failed.
+fn is_connection_error(error: &tokio_postgres::Error) -> bool {
+    if error.as_db_error().is_some() {
+        return false;
+    }
+    error.is_closed()
+        || std::error::Error::source(error)
+            .map(|source| source.is::<std::io::Error>())
+            .unwrap_or(false)
+}
 
-    if let Some(id) = query_id {
-        let mut tokens = CANCEL_TOKENS.lock().await;
-        tokens.insert(id.to_string(), client.cancel_token());
+/// Functions that write or assign a transaction id, so a SELECT calling them
+/// is neither replica-safe nor safe to repeat.
+const WRITING_FUNCTIONS: &[&str] = &[
+    "nextval",
+    "setval",
+    "txid_current",
+    "pg_current_xact_id",
+    "lo_create",
+    "lo_import",
+    "lo_unlink",
+];
+
+/// Whether a lowercased SELECT-like statement can write anyway: `SELECT ...
+/// INTO` creates a table, `FOR UPDATE`/`FOR SHARE` lock rows, and some
+/// functions change state. Words inside literals count too, which only
+/// errs towards the primary.
+fn select_writes(head: &str) -> bool {
+    let words: Vec<&str> = head
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|word| !word.is_empty())
+        .collect();
+    words.iter().enumerate().any(|(idx, word)| match *word {
+        "into" => true,
+        "for" => matches!(words.get(idx + 1), Some(&("update" | "share" | "no" | "key"))),
+        word => WRITING_FUNCTIONS.contains(&word),
+    })
+}
+
+/// Statements that can run on a read replica: the retry-safe reads plus
+/// `WITH` queries whose CTEs don't modify data.
+fn is_replica_safe(head: &str) -> bool {
+    if is_retryable_read(head) {
+        return true;
     }
+    head.starts_with("with")
+        && !select_writes(head)
+        && !head
+            .split(|c: char| !c.is_alphanumeric() && c != '_')
+            .any(|word| matches!(word, "insert" | "update" | "delete" | "merge"))
+}
 
-    let trimmed = strip_leading_comments(sql).to_lowercase();
-    let is_query = trimmed.starts_with("select") || trimmed.starts_with("with") || trimmed.starts_with("show") || trimmed.starts_with("explain");
-    let has_multiple_statements = sql.matches(';').count() > 1
-        || sql.trim_end_matches(';').contains(';');
+/// Whether `sql` is a single statement that only reads, by the same rule used
+/// to route queries to a replica.
+pub fn is_read_only_statement(sql: &str) -> bool {
+    let trimmed = sql.trim_end().trim_end_matches(';');
+    !trimmed.contains(';') && is_replica_safe(&strip_leading_comments(trimmed).to_lowercase())
+}
 
-    let result = if let Some(schema) = schema_name {
-        let tx = client.transaction().await?;
-        let search_path_sql = format!("SET LOCAL search_path TO {}", quote_ident(schema));
-        tx.batch_execute(&search_path_sql).await?;
+/// Statements that are safe to re-run after a dropped connection. `WITH` is
+/// excluded because CTEs can modify data, `EXPLAIN ANALYZE` executes the
+/// statement it explains, and SELECTs that write are left out.
+fn is_retryable_read(head: &str) -> bool {
+    let first = head.split_whitespace().next().unwrap_or("");
+    match first {
+        "select" | "values" => !select_writes(head),
+        "show" | "table" => true,
+        "explain" => !head.contains("analyze"),
+        _ => false,
+    }
+}
+
+/// Rewrite a SELECT so columns without a decoder come back as text rather
+/// than `null`. The original query becomes a subquery with positional column
+/// aliases, so duplicate or unnamed output columns still work. `None` when
+/// every column decodes natively. Only plain SELECTs are rewritten; WITH
+/// (which may modify data), SHOW and EXPLAIN can't be wrapped.
+fn text_fallback_query(sql: &str, columns: &[tokio_postgres::Column]) -> Option<String> {
+    text_fallback_query_where(sql, columns, crate::decode::has_decoder)
+}
+
+/// `text_fallback_query` for callers with their own idea of which types they
+/// can handle natively.
+pub(crate) fn text_fallback_query_where(
+    sql: &str,
+    columns: &[tokio_postgres::Column],
+    decodable: impl Fn(&tokio_postgres::types::Type) -> bool,
+) -> Option<String> {
+    if !strip_leading_comments(sql).to_lowercase().starts_with("select")
+        || columns.iter().all(|col| decodable(col.type_()))
+    {
+        return None;
+    }
+
+    let aliases: Vec<String> = (1..=columns.len()).map(|idx| format!("c{}", idx)).collect();
+    let select_list: Vec<String> = columns
+        .iter()
+        .zip(&aliases)
+        .map(|(col, alias)| {
+            let cast = if decodable(col.type_()) { "" } else { "::text" };
+            format!("q.{}{} AS {}", alias, cast, quote_ident(col.name()))
+        })
+        .collect();
+
+    // Newline before the paren so a trailing line comment can't swallow it
+    Some(format!(
+        "SELECT {} FROM ({}\n) AS q({})",
+        select_list.join(", "),
+        sql.trim_end().trim_end_matches(';'),
+        aliases.join(", ")
+    ))
+}
+
+/// Run a row-returning statement, casting columns without a decoder to text.
+/// Returns the rows with the statement's original column types, so callers
+/// can still describe a column that came back as text.
+pub(crate) async fn query_with_text_fallback<C: tokio_postgres::GenericClient>(
+    client: &C,
+    sql: &str,
+    params: &[&(dyn tokio_postgres::types::ToSql + Sync)],
+) -> Result<(Vec<tokio_postgres::Row>, Vec<tokio_postgres::types::Type>), QueryError> {
+    let statement = client.prepare(sql).await?;
+    let types = statement.columns().iter().map(|col| col.type_().clone()).collect();
+    let rows = match text_fallback_query(sql, statement.columns()) {
+        Some(rewritten) => client
+            .query(rewritten.as_str(), params)
+            .await
+            .map_err(|error| QueryError { error, rewritten: true })?,
+        None => client.query(&statement, params).await?,
+    };
+    Ok((rows, types))
+}
+
+/// `run_statement`, along with the notices the server sent while it ran,
+/// including those raised before a failure.
+async fn run_on_client(
+    client: &mut Object,
+    sql: &str,
+    options: &ExecOptions<'_>,
+    is_query: bool,
+    has_multiple_statements: bool,
+    server: &Server,
+) -> (Result<QueryExecutionResult, QueryError>, Vec<Notice>) {
+    let sink = collect_notices(client);
+    let result = run_statement(client, sql, options, is_query, has_multiple_statements, server).await;
+    (result, take_notices(sink))
+}
+
+async fn run_statement(
+    client: &mut Object,
+    sql: &str,
+    options: &ExecOptions<'_>,
+    is_query: bool,
+    has_multiple_statements: bool,
+    server: &Server,
+) -> Result<QueryExecutionResult, QueryError> {
+    let needs_transaction = options.schema_name.is_some()
+        || server.uses_transaction_pooling()
+        || options.isolation_level.is_some()
+        || options.client_min_messages.is_some();
+    let result = if needs_transaction {
+        let mut builder = client.build_transaction();
+        if let Some(level) = options.isolation_level {
+            builder = builder.isolation_level(level);
+        }
+        let tx = builder.start().await?;
+        if let Some(settings) = local_settings(server, None) {
+            tx.batch_execute(&settings).await?;
+        }
+        if let Some(schema) = options.schema_name {
+            let search_path_sql = format!("SET LOCAL search_path TO {}", quote_ident(schema));
+            tx.batch_execute(&search_path_sql).await?;
+        }
+        if let Some(level) = options.client_min_messages {
+            tx.batch_execute(&format!("SET LOCAL client_min_messages = '{}'", level)).await?;
+        }
 
         if is_query {
-            let rows = tx.query(sql, &[]).await?;
+            let (rows, types) = query_with_text_fallback(&*tx, sql, &[]).await?;
             tx.commit().await?;
-            QueryExecutionResult::Rows(rows)
+            QueryExecutionResult::Rows { rows, types }
         } else if has_multiple_statements {
             tx.batch_execute(sql).await?;
             tx.commit().await?;
@@ -135,8 +713,8 @@ pub async fn execute_query(
             QueryExecutionResult::Affected(affected)
         }
     } else if is_query {
-        let rows = client.query(sql, &[]).await?;
-        QueryExecutionResult::Rows(rows)
+        let (rows, types) = query_with_text_fallback(&***client, sql, &[]).await?;
+        QueryExecutionResult::Rows { rows, types }
     } else if has_multiple_statements {
         let tx = client.transaction().await?;
         tx.batch_execute(sql).await?;
@@ -147,18 +725,361 @@ pub async fn execute_query(
         QueryExecutionResult::Affected(affected)
     };
 
+    Ok(result)
+}
+
+/// Run `sql` on a pooled connection.
+///
+/// Retry policy: a pooled connection may have been closed by the server
+/// (idle timeout, restart) without the pool noticing. When the statement
+/// fails with a connection-level error and is a single read-only statement
+/// (see `is_retryable_read`), the broken connection is discarded and the
+/// statement is run once more on a fresh one. Anything that may have side
+/// effects is never retried, since the server may have applied it before
+/// the connection dropped.
+///
+/// Reads (see `is_replica_safe`) go to the read replica when one is
+/// configured. A statement the replica refuses as a write (SQLSTATE 25006)
+/// is run again on the primary.
+///
+/// On servers using transaction pooling every statement runs inside an
+/// explicit transaction so its prepare and execute reach the same backend,
+/// with the server's time zone set in it. An `isolation_level` or
+/// `client_min_messages` likewise forces a transaction, even for reads.
+pub async fn execute_query(
+    server: &Server,
+    password: &str,
+    dbname: &str,
+    sql: &str,
+    options: &ExecOptions<'_>,
+) -> Result<QueryExecutionResult, Box<dyn std::error::Error>> {
+    let (result, _) = execute_query_with_notices(server, password, dbname, sql, options).await?;
+    Ok(result)
+}
+
+/// `execute_query`, also returning the notices (`RAISE NOTICE`, warnings,
+/// and with a low enough `client_min_messages` debug output) the server sent
+/// while the statement ran. Only the last attempt's notices are kept when
+/// the statement is retried. At most `MAX_NOTICES` are collected.
+pub async fn execute_query_with_notices(
+    server: &Server,
+    password: &str,
+    dbname: &str,
+    sql: &str,
+    options: &ExecOptions<'_>,
+) -> Result<(QueryExecutionResult, Vec<Notice>), Box<dyn std::error::Error>> {
+    let query_id = options.query_id;
+
+    let trimmed = strip_leading_comments(sql).to_lowercase();
+    let is_query = trimmed.starts_with("select") || trimmed.starts_with("with") || trimmed.starts_with("show") || trimmed.starts_with("explain");
+    let has_multiple_statements = sql.matches(';').count() > 1
+        || sql.trim_end_matches(';').contains(';');
+
+    // Reads go to the replica when one is configured; any problem reaching it
+    // falls back to the primary rather than failing the query
+    let primary = get_or_create_pool(server, password, dbname).await?;
+    let replica = if !options.force_primary && !has_multiple_statements && is_replica_safe(&trimmed) {
+        match get_or_create_replica_pool(server, password, dbname).await {
+            Ok(replica) => replica,
+            Err(e) => {
+                eprintln!("Failed to create replica pool, using primary: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let (mut pool, mut client, on_replica) = match replica {
+        Some(replica) => match replica.get().await {
+            Ok(client) => (replica, client, true),
+            Err(e) => {
+                eprintln!("Failed to reach replica, using primary: {}", e);
+                let client = checkout(server, &primary).await?;
+                (primary.clone(), client, false)
+            }
+        },
+        None => {
+            let client = checkout(server, &primary).await?;
+            (primary.clone(), client, false)
+        }
+    };
+
+    if let Some(id) = query_id {
+        let mut tokens = CANCEL_TOKENS.lock().await;
+        tokens.insert(id.to_string(), (client.cancel_token(), Instant::now()));
+    }
+
+    let (mut result, mut notices) = run_on_client(
+        &mut client,
+        sql,
+        options,
+        is_query,
+        has_multiple_statements,
+        server,
+    )
+    .await;
+
+    // A statement the classifier let through may still write; the replica
+    // refuses it before doing anything, so run it on the primary instead
+    let read_only_refused = |result: &Result<QueryExecutionResult, QueryError>| {
+        matches!(result, Err(e) if e.code() == Some(&tokio_postgres::error::SqlState::READ_ONLY_SQL_TRANSACTION))
+    };
+    if on_replica && read_only_refused(&result) {
+        client = match checkout(server, &primary).await {
+            Ok(client) => client,
+            Err(e) => {
+                if let Some(id) = query_id {
+                    remove_cancel_token(id).await;
+                }
+                return Err(e.into());
+            }
+        };
+        pool = primary;
+        if let Some(id) = query_id {
+            let mut tokens = CANCEL_TOKENS.lock().await;
+            tokens.insert(id.to_string(), (client.cancel_token(), Instant::now()));
+        }
+        (result, notices) = run_on_client(
+            &mut client,
+            sql,
+            options,
+            is_query,
+            has_multiple_statements,
+            server,
+        )
+        .await;
+    }
+
+    if let Err(e) = &result {
+        if is_connection_error(e) && !has_multiple_statements && is_retryable_read(&trimmed) {
+            // Keep the dead connection out of the pool before asking for another
+            let _ = Object::take(client);
+            client = match checkout(server, &pool).await {
+                Ok(client) => client,
+                Err(e) => {
+                    if let Some(id) = query_id {
+                        remove_cancel_token(id).await;
+                    }
+                    return Err(e.into());
+                }
+            };
+            if let Some(id) = query_id {
+                let mut tokens = CANCEL_TOKENS.lock().await;
+                tokens.insert(id.to_string(), (client.cancel_token(), Instant::now()));
+            }
+            (result, notices) = run_on_client(
+                &mut client,
+                sql,
+                options,
+                is_query,
+                has_multiple_statements,
+                server,
+            )
+            .await;
+        }
+    }
+
     if let Some(id) = query_id {
         let mut tokens = CANCEL_TOKENS.lock().await;
         tokens.remove(id);
     }
 
-    Ok(result)
+    if let Err(e) = &result {
+        if is_connection_error(e) {
+            report_state(&server.id, ConnectionState::Error);
+        }
+    }
+
+    Ok((result?, notices))
 }
 
+/// Check out a connection, reporting whether the server was reachable.
+pub async fn checkout(
+    server: &Server,
+    pool: &Pool,
+) -> Result<Object, deadpool_postgres::PoolError> {
+    let result = pool.get().await;
+    report_state(
+        &server.id,
+        if result.is_ok() { ConnectionState::Connected } else { ConnectionState::Error },
+    );
+    result
+}
+
+/// A pooled connection as handed to commands. Behind a transaction pooler
+/// each `Sync` may hand the next message to a different backend, so the
+/// prepare and execute halves of `query`, `query_one`, `query_opt` and
+/// `execute` would split up; there each call runs in its own transaction.
+/// Everything else derefs to the pooled client. Work spanning several round
+/// trips (COPY, cursors, a reused `Statement`) belongs in `transaction()` or
+/// between `pin_backend` and `unpin_backend`. The transactions these open
+/// start with the pool's `local_settings`.
+pub struct PooledClient {
+    client: Object,
+    transaction_pooling: bool,
+    local_settings: Option<String>,
+    pinned: AtomicBool,
+}
+
+impl PooledClient {
+    /// Wrap a connection checked out of the pool for `server`, logged in as
+    /// `role` if that pool has one.
+    pub fn new(client: Object, server: &Server, role: Option<&str>) -> Self {
+        Self {
+            client,
+            transaction_pooling: server.uses_transaction_pooling(),
+            local_settings: local_settings(server, role),
+            pinned: AtomicBool::new(false),
+        }
+    }
+
+    async fn begin(&self) -> Result<(), tokio_postgres::Error> {
+        match &self.local_settings {
+            Some(settings) => self.client.batch_execute(&format!("BEGIN; {}", settings)).await,
+            None => self.client.batch_execute("BEGIN").await,
+        }
+    }
+
+    /// `Object::transaction`, with the pool's `local_settings` applied.
+    pub async fn transaction(&mut self) -> Result<deadpool_postgres::Transaction<'_>, tokio_postgres::Error> {
+        let tx = self.client.transaction().await?;
+        if let Some(settings) = &self.local_settings {
+            tx.batch_execute(settings).await?;
+        }
+        Ok(tx)
+    }
+
+    /// A read-only `transaction`.
+    pub async fn read_only_transaction(
+        &mut self,
+    ) -> Result<deadpool_postgres::Transaction<'_>, tokio_postgres::Error> {
+        let tx = self.client.build_transaction().read_only(true).start().await?;
+        if let Some(settings) = &self.local_settings {
+            tx.batch_execute(settings).await?;
+        }
+        Ok(tx)
+    }
+
+    /// Run `op` in a transaction of its own when behind a transaction pooler
+    /// and not already pinned.
+    async fn autocommit<R>(
+        &self,
+        op: impl std::future::Future<Output = Result<R, tokio_postgres::Error>>,
+    ) -> Result<R, tokio_postgres::Error> {
+        if !self.transaction_pooling || self.pinned.load(Ordering::Relaxed) {
+            return op.await;
+        }
+        self.begin().await?;
+        match op.await {
+            Ok(value) => {
+                self.client.batch_execute("COMMIT").await?;
+                Ok(value)
+            }
+            Err(e) => {
+                let _ = self.client.batch_execute("ROLLBACK").await;
+                Err(e)
+            }
+        }
+    }
+
+    pub async fn query<T>(
+        &self,
+        statement: &T,
+        params: &[&(dyn tokio_postgres::types::ToSql + Sync)],
+    ) -> Result<Vec<tokio_postgres::Row>, tokio_postgres::Error>
+    where
+        T: ?Sized + tokio_postgres::ToStatement,
+    {
+        self.autocommit(self.client.query(statement, params)).await
+    }
+
+    pub async fn query_one<T>(
+        &self,
+        statement: &T,
+        params: &[&(dyn tokio_postgres::types::ToSql + Sync)],
+    ) -> Result<tokio_postgres::Row, tokio_postgres::Error>
+    where
+        T: ?Sized + tokio_postgres::ToStatement,
+    {
+        self.autocommit(self.client.query_one(statement, params)).await
+    }
+
+    pub async fn query_opt<T>(
+        &self,
+        statement: &T,
+        params: &[&(dyn tokio_postgres::types::ToSql + Sync)],
+    ) -> Result<Option<tokio_postgres::Row>, tokio_postgres::Error>
+    where
+        T: ?Sized + tokio_postgres::ToStatement,
+    {
+        self.autocommit(self.client.query_opt(statement, params)).await
+    }
+
+    pub async fn execute<T>(
+        &self,
+        statement: &T,
+        params: &[&(dyn tokio_postgres::types::ToSql + Sync)],
+    ) -> Result<u64, tokio_postgres::Error>
+    where
+        T: ?Sized + tokio_postgres::ToStatement,
+    {
+        self.autocommit(self.client.execute(statement, params)).await
+    }
+
+    /// Keep everything up to `unpin_backend` on one backend by opening a
+    /// transaction; does nothing outside transaction pooling. Returns whether
+    /// it pinned, so an early error return is rolled back when the pool
+    /// recycles the connection.
+    pub async fn pin_backend(&self) -> Result<bool, tokio_postgres::Error> {
+        if !self.transaction_pooling || self.pinned.load(Ordering::Relaxed) {
+            return Ok(false);
+        }
+        self.begin().await?;
+        self.pinned.store(true, Ordering::Relaxed);
+        Ok(true)
+    }
+
+    /// Commit the transaction opened by `pin_backend`, if any.
+    pub async fn unpin_backend(&self) -> Result<(), tokio_postgres::Error> {
+        if self.pinned.swap(false, Ordering::Relaxed) {
+            self.client.batch_execute("COMMIT").await?;
+        }
+        Ok(())
+    }
+}
+
+impl std::ops::Deref for PooledClient {
+    type Target = Object;
+
+    fn deref(&self) -> &Self::Target {
+        &self.client
+    }
+}
+
+impl std::ops::DerefMut for PooledClient {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.client
+    }
+}
+
+/// Make a query started outside `execute_query` cancellable via `cancel_query`.
+pub async fn register_cancel_token(query_id: &str, token: CancelToken) {
+    CANCEL_TOKENS
+        .lock()
+        .await
+        .insert(query_id.to_string(), (token, Instant::now()));
+}
+
+pub async fn remove_cancel_token(query_id: &str) {
+    CANCEL_TOKENS.lock().await.remove(query_id);
+}
+
+/// Cancel requests go out over a fresh connection to the same address as the
+/// query's own, so this works for Unix socket servers as well as TCP ones.
 pub async fn cancel_query(query_id: &str) -> Result<(), Box<dyn std::error::Error>> {
     let token = {
         let tokens = CANCEL_TOKENS.lock().await;
-        tokens.get(query_id).cloned()
+        tokens.get(query_id).map(|(token, _)| token.clone())
     };
 
     match token {
@@ -170,6 +1091,55 @@ pub async fn cancel_query(query_id: &str) -> Result<(), Box<dyn std::error::Erro
     }
 }
 
+/// Snapshot of every live pool.
+pub async fn list_pools() -> Vec<PoolInfo> {
+    let pools = POOLS.lock().await;
+    pools
+        .iter()
+        .map(|(key, pool)| pool_info(key, pool.status()))
+        .collect()
+}
+
+/// Close and remove every pool belonging to a server. Returns how many were dropped.
+pub async fn drop_pools(server_id: &str) -> usize {
+    let mut pools = POOLS.lock().await;
+    let prefix = format!("{}::", server_id);
+    let keys: Vec<String> = pools
+        .keys()
+        .filter(|key| key.starts_with(&prefix))
+        .cloned()
+        .collect();
+
+    for key in &keys {
+        if let Some(pool) = pools.remove(key) {
+            pool.close();
+        }
+    }
+
+    if !keys.is_empty() {
+        report_state(server_id, ConnectionState::Disconnected);
+    }
+    keys.len()
+}
+
+/// Close every pool on app exit. Dropping the idle clients lets their
+/// connection tasks send a Terminate message instead of the server seeing a
+/// reset; the short pause gives those tasks a chance to run.
+pub async fn close_all_pools() -> usize {
+    let closed = {
+        let mut pools = POOLS.lock().await;
+        let closed = pools.len();
+        for (_, pool) in pools.drain() {
+            pool.close();
+        }
+        closed
+    };
+    if closed > 0 {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    closed
+}
+
 pub async fn cleanup_idle_pools() {
     let mut pools = POOLS.lock().await;
     pools.retain(|_, pool| {
@@ -178,6 +1148,31 @@ pub async fn cleanup_idle_pools() {
     });
 }
 
+/// Change how long cancel tokens are kept before the sweep drops them.
+pub fn set_cancel_token_ttl(ttl: Duration) {
+    CANCEL_TOKEN_TTL_SECS.store(ttl.as_secs(), Ordering::Relaxed);
+}
+
+/// Drop cancel tokens older than the TTL. Finished queries remove their own
+/// token; this catches the ones left by queries that errored out early or
+/// whose window went away. Returns how many were removed.
+pub async fn sweep_cancel_tokens() -> usize {
+    let ttl = Duration::from_secs(CANCEL_TOKEN_TTL_SECS.load(Ordering::Relaxed));
+    let mut tokens = CANCEL_TOKENS.lock().await;
+    let before = tokens.len();
+    tokens.retain(|_, (_, registered)| registered.elapsed() < ttl);
+    before - tokens.len()
+}
+
+pub fn start_cancel_token_sweep() {
+    tokio::spawn(async {
+        loop {
+            tokio::time::sleep(CANCEL_TOKEN_SWEEP_INTERVAL).await;
+            sweep_cancel_tokens().await;
+        }
+    });
+}
+
 pub fn start_cleanup_task() {
     tokio::spawn(async {
         loop {
@@ -186,3 +1181,88 @@ pub fn start_cleanup_task() {
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_server(id: &str, connect_timeout_ms: Option<i64>) -> Server {
+        Server {
+            id: id.to_string(),
+            name: "Test Server".to_string(),
+            host: "localhost".to_string(),
+            port: 5432,
+            database: "testdb".to_string(),
+            username: "user".to_string(),
+            credential_key: "key-1".to_string(),
+            group_name: None,
+            last_connected: None,
+            connect_timeout_ms,
+            pooling_mode: None,
+            favorite: 0,
+            replica_host: None,
+            replica_port: None,
+            keepalive_idle_secs: None,
+            confirm_expensive: 0,
+            expensive_cost_threshold: None,
+            expensive_rows_threshold: None,
+            timezone: None,
+        }
+    }
+
+    #[test]
+    fn test_writing_selects_stay_on_primary() {
+        assert!(is_replica_safe("select * from t where id = 1"));
+        assert!(is_retryable_read("select count(*) from t"));
+        assert!(is_replica_safe("with x as (select 1) select * from x"));
+
+        for head in [
+            "select nextval('t_id_seq')",
+            "select setval('t_id_seq', 10)",
+            "select * into t_copy from t",
+            "select * from t for update",
+            "select * from t for no key update skip locked",
+            "with x as (select txid_current()) select * from x",
+        ] {
+            assert!(!is_replica_safe(head), "{}", head);
+            assert!(!is_retryable_read(head), "{}", head);
+        }
+        assert!(!is_retryable_read("select * into t_copy from t"));
+    }
+
+    #[test]
+    fn test_local_settings_only_behind_transaction_pooler() {
+        let mut server = test_server("test-local-settings", None);
+        server.timezone = Some(" Europe/O'Town ".to_string());
+        assert_eq!(local_settings(&server, Some("auditor")), None);
+
+        server.pooling_mode = Some("transaction".to_string());
+        assert_eq!(
+            local_settings(&server, Some("Auditor")).as_deref(),
+            Some("SET LOCAL ROLE \"Auditor\"; SET LOCAL TimeZone TO 'Europe/O''Town'")
+        );
+        server.timezone = None;
+        assert_eq!(local_settings(&server, None), None);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_pool_picks_up_new_connect_timeout() {
+        // Creating a pool does not connect, so no server is needed
+        let server = test_server("test-pool-timeout", None);
+        let pool = get_or_create_pool(&server, "", "testdb").await.unwrap();
+        let default = Duration::from_millis(DEFAULT_CONNECT_TIMEOUT_MS as u64);
+        assert_eq!(pool.timeouts().wait, Some(default));
+
+        // The cached pool keeps the timeout it was built with
+        let server = test_server("test-pool-timeout", Some(1_500));
+        let pool = get_or_create_pool(&server, "", "testdb").await.unwrap();
+        assert_eq!(pool.timeouts().wait, Some(default));
+
+        assert_eq!(drop_pools(&server.id).await, 1);
+        let pool = get_or_create_pool(&server, "", "testdb").await.unwrap();
+        assert_eq!(pool.timeouts().wait, Some(Duration::from_millis(1_500)));
+        assert_eq!(pool.timeouts().create, Some(Duration::from_millis(1_500)));
+
+        drop_pools(&server.id).await;
+    }
+}
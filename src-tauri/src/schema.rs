@@ -1,32 +1,43 @@
-use crate::db::{self, Schema, Table, Column, Index, View};
+use crate::db::{self, AutocompleteItems, Schema, Table, Column, Index, View, EnumType};
 use crate::postgres;
+use std::collections::BTreeSet;
 use uuid::Uuid;
 use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Refreshes requested within this long of a completed one reuse the cache.
+pub const REFRESH_DEBOUNCE_WINDOW: Duration = Duration::from_secs(2);
+
+/// When each server's last full schema refresh finished.
+static LAST_REFRESHED: once_cell::sync::Lazy<Mutex<HashMap<String, Instant>>> =
+    once_cell::sync::Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Whether a full refresh of `server_id` completed within `window`.
+pub fn refreshed_within(server_id: &str, window: Duration) -> bool {
+    LAST_REFRESHED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(server_id)
+        .is_some_and(|at| at.elapsed() < window)
+}
 
 pub async fn refresh_schema_for_server(
     server: &db::Server,
     password: &str,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let pool = postgres::get_or_create_pool(
-        &server.id,
-        &server.host,
-        server.port as u16,
-        &server.username,
-        password,
-        &server.database,
-    )
-    .await?;
-    let client = pool.get().await?;
-
-    // Clear existing schema data for the server
-    db::clear_server_schema_data(&server.id)?;
-
-    // Collect all data before inserting
+    let pool = postgres::get_or_create_pool(server, password, &server.database).await?;
+    let client = postgres::PooledClient::new(pool.get().await?, server, None);
+
+    // Collect everything first; `refresh_server_schema` swaps the cache in one
+    // transaction, so a failure part way through leaves the old cache intact
     let mut schemas_to_insert = Vec::new();
     let mut tables_to_insert = Vec::new();
     let mut columns_to_insert = Vec::new();
     let mut indexes_to_insert = Vec::new();
     let mut views_to_insert = Vec::new();
+    let mut enums_to_insert = Vec::new();
 
     // Fetch databases (exclude templates and system databases)
     let database_rows = client
@@ -39,16 +50,7 @@ pub async fn refresh_schema_for_server(
     for row in database_rows {
         let database_name: String = row.get(0);
 
-        let db_pool = match postgres::get_or_create_pool(
-            &server.id,
-            &server.host,
-            server.port as u16,
-            &server.username,
-            password,
-            &database_name,
-        )
-        .await
-        {
+        let db_pool = match postgres::get_or_create_pool(server, password, &database_name).await {
             Ok(pool) => pool,
             Err(err) => {
                 eprintln!("Failed to connect to database {}: {}", database_name, err);
@@ -57,7 +59,7 @@ pub async fn refresh_schema_for_server(
         };
 
         let db_client = match db_pool.get().await {
-            Ok(client) => client,
+            Ok(client) => postgres::PooledClient::new(client, server, None),
             Err(err) => {
                 eprintln!("Failed to get client for database {}: {}", database_name, err);
                 continue;
@@ -113,7 +115,9 @@ pub async fn refresh_schema_for_server(
                 // Fetch columns for this table
                 let column_rows = db_client
                     .query(
-                        "SELECT column_name, data_type, is_nullable
+                        "SELECT column_name, data_type, is_nullable, ordinal_position::int4,
+                                (is_identity = 'YES' OR COALESCE(column_default, '') LIKE 'nextval(%'),
+                                is_generated = 'ALWAYS'
                          FROM information_schema.columns
                          WHERE table_schema = $1 AND table_name = $2",
                         &[&schema_name, &table_name],
@@ -131,6 +135,9 @@ pub async fn refresh_schema_for_server(
                         name: column_name,
                         data_type,
                         nullable,
+                        ordinal_position: column_row.get(3),
+                        is_identity: column_row.get::<_, bool>(4) as i32,
+                        is_generated: column_row.get::<_, bool>(5) as i32,
                     });
                 }
 
@@ -174,6 +181,26 @@ pub async fn refresh_schema_for_server(
                     name: view_name,
                 });
             }
+
+            // Fetch enum types and their labels, for dropdown editors
+            let enum_rows = db_client
+                .query(
+                    "SELECT t.typname::text, array_agg(e.enumlabel::text ORDER BY e.enumsortorder)
+                     FROM pg_type t
+                     JOIN pg_namespace n ON n.oid = t.typnamespace
+                     JOIN pg_enum e ON e.enumtypid = t.oid
+                     WHERE n.nspname = $1
+                     GROUP BY t.typname",
+                    &[&schema_name],
+                )
+                .await?;
+            for enum_row in enum_rows {
+                enums_to_insert.push(EnumType {
+                    schema_id: schema_id.clone(),
+                    name: enum_row.get(0),
+                    labels: enum_row.get(1),
+                });
+            }
         }
     }
 
@@ -185,7 +212,196 @@ pub async fn refresh_schema_for_server(
         &columns_to_insert,
         &indexes_to_insert,
         &views_to_insert,
+        &enums_to_insert,
     )?;
 
+    LAST_REFRESHED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .insert(server.id.clone(), Instant::now());
+
     Ok(())
 }
+
+/// Re-read one table's columns and indexes and replace just that table in the
+/// cache. Returns `None` when the schema isn't cached yet or the table no
+/// longer exists (in which case its cached entry is removed).
+pub async fn refresh_table_for_server(
+    server: &db::Server,
+    password: &str,
+    database_name: &str,
+    schema_name: &str,
+    table_name: &str,
+) -> Result<Option<Table>, Box<dyn std::error::Error>> {
+    let Some(schema_id) = db::find_schema_id(&server.id, database_name, schema_name)? else {
+        return Ok(None);
+    };
+
+    let pool = postgres::get_or_create_pool(server, password, database_name).await?;
+    let client = postgres::PooledClient::new(pool.get().await?, server, None);
+
+    let table_row = client
+        .query_opt(
+            "SELECT table_type
+             FROM information_schema.tables
+             WHERE table_schema = $1 AND table_name = $2 AND table_type = 'BASE TABLE'",
+            &[&schema_name, &table_name],
+        )
+        .await?;
+
+    let Some(table_row) = table_row else {
+        db::delete_table(&schema_id, table_name)?;
+        return Ok(None);
+    };
+
+    let table = Table {
+        id: Uuid::new_v4().to_string(),
+        schema_id,
+        name: table_name.to_string(),
+        type_: table_row.get(0),
+    };
+
+    let column_rows = client
+        .query(
+            "SELECT column_name, data_type, is_nullable, ordinal_position::int4,
+                    (is_identity = 'YES' OR COALESCE(column_default, '') LIKE 'nextval(%'),
+                    is_generated = 'ALWAYS'
+             FROM information_schema.columns
+             WHERE table_schema = $1 AND table_name = $2",
+            &[&schema_name, &table_name],
+        )
+        .await?;
+    let columns: Vec<Column> = column_rows
+        .iter()
+        .map(|row| {
+            let is_nullable: String = row.get(2);
+            Column {
+                id: Uuid::new_v4().to_string(),
+                table_id: table.id.clone(),
+                name: row.get(0),
+                data_type: row.get(1),
+                nullable: if is_nullable == "YES" { 1 } else { 0 },
+                ordinal_position: row.get(3),
+                is_identity: row.get::<_, bool>(4) as i32,
+                is_generated: row.get::<_, bool>(5) as i32,
+            }
+        })
+        .collect();
+
+    let index_rows = client
+        .query(
+            "SELECT indexname, indexdef
+             FROM pg_indexes
+             WHERE schemaname = $1 AND tablename = $2",
+            &[&schema_name, &table_name],
+        )
+        .await?;
+    let indexes: Vec<Index> = index_rows
+        .iter()
+        .map(|row| Index {
+            id: Uuid::new_v4().to_string(),
+            table_id: table.id.clone(),
+            name: row.get(0),
+            definition: row.get(1),
+        })
+        .collect();
+
+    db::replace_table(&table, &columns, &indexes)?;
+
+    Ok(Some(table))
+}
+
+/// Collect just the names used for autocomplete (schemas, tables, columns,
+/// indexes) with one aggregate query per kind, skipping the per-table detail
+/// fetch done by `refresh_schema_for_server`.
+pub async fn fetch_autocomplete_items(
+    server: &db::Server,
+    password: &str,
+) -> Result<AutocompleteItems, Box<dyn std::error::Error>> {
+    let pool = postgres::get_or_create_pool(server, password, &server.database).await?;
+    let client = postgres::PooledClient::new(pool.get().await?, server, None);
+
+    let database_rows = client
+        .query(
+            "SELECT datname FROM pg_database WHERE datistemplate = false AND datallowconn = true ORDER BY datname",
+            &[],
+        )
+        .await?;
+
+    let mut schemas = BTreeSet::new();
+    let mut tables = BTreeSet::new();
+    let mut columns = BTreeSet::new();
+    let mut indexes = BTreeSet::new();
+
+    for row in database_rows {
+        let database_name: String = row.get(0);
+
+        let db_pool = match postgres::get_or_create_pool(server, password, &database_name).await {
+            Ok(pool) => pool,
+            Err(err) => {
+                eprintln!("Failed to connect to database {}: {}", database_name, err);
+                continue;
+            }
+        };
+
+        let db_client = match db_pool.get().await {
+            Ok(client) => postgres::PooledClient::new(client, server, None),
+            Err(err) => {
+                eprintln!("Failed to get client for database {}: {}", database_name, err);
+                continue;
+            }
+        };
+
+        let schema_rows = db_client
+            .query(
+                "SELECT nspname
+                 FROM pg_namespace
+                 WHERE nspname NOT IN ('information_schema', 'pg_catalog')
+                   AND nspname !~ '^pg_'",
+                &[],
+            )
+            .await?;
+        schemas.extend(schema_rows.iter().map(|row| row.get::<_, String>(0)));
+
+        let table_rows = db_client
+            .query(
+                "SELECT table_name
+                 FROM information_schema.tables
+                 WHERE table_type = 'BASE TABLE'
+                   AND table_schema NOT IN ('information_schema', 'pg_catalog')
+                   AND table_schema !~ '^pg_'",
+                &[],
+            )
+            .await?;
+        tables.extend(table_rows.iter().map(|row| row.get::<_, String>(0)));
+
+        let column_rows = db_client
+            .query(
+                "SELECT DISTINCT column_name
+                 FROM information_schema.columns
+                 WHERE table_schema NOT IN ('information_schema', 'pg_catalog')
+                   AND table_schema !~ '^pg_'",
+                &[],
+            )
+            .await?;
+        columns.extend(column_rows.iter().map(|row| row.get::<_, String>(0)));
+
+        let index_rows = db_client
+            .query(
+                "SELECT indexname
+                 FROM pg_indexes
+                 WHERE schemaname NOT IN ('information_schema', 'pg_catalog')
+                   AND schemaname !~ '^pg_'",
+                &[],
+            )
+            .await?;
+        indexes.extend(index_rows.iter().map(|row| row.get::<_, String>(0)));
+    }
+
+    Ok(AutocompleteItems {
+        tables: tables.into_iter().collect(),
+        columns: columns.into_iter().collect(),
+        indexes: indexes.into_iter().collect(),
+        schemas: schemas.into_iter().collect(),
+    })
+}
@@ -0,0 +1,154 @@
+//! Random values for filling tables with throwaway test data.
+//!
+//! Developer tooling only: values are plausible for their column type but
+//! carry no meaning, and nothing here is suitable for production data.
+
+use chrono::{DateTime, NaiveDate, TimeDelta};
+use serde_json::Value;
+
+const LOREM_WORDS: &[&str] = &[
+    "lorem", "ipsum", "dolor", "sit", "amet", "consectetur", "adipiscing", "elit", "sed", "do",
+    "eiusmod", "tempor", "incididunt", "ut", "labore", "et", "dolore", "magna", "aliqua", "enim",
+];
+
+/// Dates and timestamps fall in the six years from 2020-01-01.
+const DATE_RANGE_DAYS: u64 = 6 * 365;
+const EPOCH_2020_SECS: i64 = 1_577_836_800;
+
+/// SplitMix64: small, fast and reproducible from a seed, which is all test
+/// data needs.
+pub(crate) struct TestDataRng {
+    state: u64,
+}
+
+impl TestDataRng {
+    pub(crate) fn new(seed: u64) -> Self {
+        TestDataRng { state: seed }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform-enough value in `0..bound`; `bound` must be non-zero.
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+}
+
+/// The `(a)` or `(a,b)` modifiers of a formatted type like `numeric(10,2)`.
+fn type_modifiers(data_type: &str) -> Vec<u32> {
+    data_type
+        .split_once('(')
+        .and_then(|(_, rest)| rest.split_once(')'))
+        .map(|(mods, _)| mods.split(',').filter_map(|m| m.trim().parse().ok()).collect())
+        .unwrap_or_default()
+}
+
+fn lorem(rng: &mut TestDataRng, max_chars: Option<usize>) -> String {
+    let words = 3 + rng.below(6);
+    let text = (0..words)
+        .map(|_| LOREM_WORDS[rng.below(LOREM_WORDS.len() as u64) as usize])
+        .collect::<Vec<_>>()
+        .join(" ");
+    match max_chars {
+        Some(max) => text.chars().take(max).collect(),
+        None => text,
+    }
+}
+
+/// A random value for a column of `data_type` (as `format_type` prints it),
+/// in the JSON form `insert_rows` binds. `None` for types with no generator
+/// (enums, arrays, geometric types, ...).
+pub(crate) fn test_value(rng: &mut TestDataRng, data_type: &str) -> Option<Value> {
+    // Modifiers dropped, e.g. `timestamp(3) with time zone` -> `timestamp with time zone`
+    let base = match data_type.split_once('(') {
+        Some((head, rest)) => format!("{}{}", head.trim_end(), rest.split_once(')').map_or("", |(_, tail)| tail)),
+        None => data_type.to_string(),
+    };
+    let base = base.as_str();
+    let value = match base {
+        "smallint" => Value::from(rng.below(1_000)),
+        "integer" => Value::from(rng.below(1_000_000)),
+        "bigint" => Value::from(rng.below(1_000_000_000)),
+        "real" | "double precision" => Value::from(rng.below(100_000) as f64 / 100.0),
+        "numeric" => {
+            let mods = type_modifiers(data_type);
+            let (precision, scale) = match mods.as_slice() {
+                [precision, scale] => (*precision, *scale),
+                [precision] => (*precision, 0),
+                _ => (8, 2),
+            };
+            let scale = scale.min(2);
+            let whole_digits = precision.saturating_sub(scale).min(6);
+            let units = rng.below(10u64.pow(whole_digits + scale));
+            let divisor = 10u64.pow(scale);
+            // As text so the value arrives at the exact scale
+            if scale == 0 {
+                Value::String(units.to_string())
+            } else {
+                Value::String(format!("{}.{:0width$}", units / divisor, units % divisor, width = scale as usize))
+            }
+        }
+        "boolean" => Value::Bool(rng.below(2) == 1),
+        "text" => Value::String(lorem(rng, None)),
+        "character varying" | "character" => {
+            let max = type_modifiers(data_type).first().map(|n| *n as usize);
+            Value::String(lorem(rng, max.or(if base == "character" { Some(1) } else { None })))
+        }
+        "uuid" => {
+            let mut bytes = [0u8; 16];
+            bytes[..8].copy_from_slice(&rng.next_u64().to_le_bytes());
+            bytes[8..].copy_from_slice(&rng.next_u64().to_le_bytes());
+            Value::String(uuid::Builder::from_random_bytes(bytes).into_uuid().to_string())
+        }
+        "date" => {
+            let date = NaiveDate::from_ymd_opt(2020, 1, 1)? + TimeDelta::days(rng.below(DATE_RANGE_DAYS) as i64);
+            Value::String(date.format("%Y-%m-%d").to_string())
+        }
+        "timestamp without time zone" | "timestamp with time zone" => {
+            let secs = EPOCH_2020_SECS + rng.below(DATE_RANGE_DAYS * 86_400) as i64;
+            let timestamp = DateTime::from_timestamp(secs, 0)?.format("%Y-%m-%d %H:%M:%S");
+            if base == "timestamp with time zone" {
+                Value::String(format!("{}+00", timestamp))
+            } else {
+                Value::String(timestamp.to_string())
+            }
+        }
+        "json" | "jsonb" => serde_json::json!({ "value": rng.below(1_000), "label": lorem(rng, Some(20)) }),
+        _ => return None,
+    };
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_values_are_reproducible_and_fit_the_type() {
+        let mut a = TestDataRng::new(42);
+        let mut b = TestDataRng::new(42);
+        assert_eq!(test_value(&mut a, "uuid"), test_value(&mut b, "uuid"));
+
+        let mut rng = TestDataRng::new(7);
+        for _ in 0..50 {
+            let Some(Value::String(text)) = test_value(&mut rng, "character varying(5)") else {
+                panic!("expected text");
+            };
+            assert!(text.chars().count() <= 5);
+
+            let Some(Value::String(number)) = test_value(&mut rng, "numeric(5,2)") else {
+                panic!("expected numeric text");
+            };
+            let number: f64 = number.parse().unwrap();
+            assert!((0.0..1000.0).contains(&number));
+        }
+        assert!(test_value(&mut rng, "timestamp(3) with time zone").is_some());
+        assert_eq!(test_value(&mut rng, "mood"), None);
+    }
+}
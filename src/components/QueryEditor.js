@@ -17,10 +17,28 @@ import {
 } from '@mui/icons-material';
 import CodeMirror from '@uiw/react-codemirror';
 import { sql as sqlLang } from '@codemirror/lang-sql';
-import { EditorView, keymap, placeholder } from '@codemirror/view';
+import { Decoration, EditorView, keymap, placeholder } from '@codemirror/view';
 import { indentWithTab } from '@codemirror/commands';
 import { autocompletion, completeFromList } from '@codemirror/autocomplete';
-import { Prec } from '@codemirror/state';
+import { Prec, StateField } from '@codemirror/state';
+
+const errorMark = Decoration.mark({ class: 'cm-sql-error' });
+
+// Underline the word at `offset` (or the character there, or the last one when
+// the error is at the end of the input) until the text is edited
+const errorHighlight = (offset) =>
+  StateField.define({
+    create(state) {
+      const text = state.doc.toString();
+      if (offset === null || !text.length) return Decoration.none;
+      const from = Math.min(offset, text.length - 1);
+      let to = from;
+      while (to < text.length && /[\w$]/.test(text[to])) to += 1;
+      return Decoration.set([errorMark.range(from, Math.max(to, from + 1))]);
+    },
+    update: (marks, tr) => (tr.docChanged ? Decoration.none : marks),
+    provide: (field) => EditorView.decorations.from(field),
+  });
 
 const QueryEditor = memo(({
   serverId,
@@ -32,7 +50,8 @@ const QueryEditor = memo(({
   onClear,
   onShowHistory,
   isExecuting = false,
-  isCancelling = false
+  isCancelling = false,
+  errorPosition = null,
 }) => {
   const [sql, setSql] = useState(initialSql);
   const [rows, setRows] = useState(0);
@@ -109,6 +128,8 @@ const QueryEditor = memo(({
     [handleExecute],
   );
 
+  const errorExtension = useMemo(() => errorHighlight(errorPosition), [errorPosition]);
+
   const editorPlaceholder = serverId
     ? "Enter SQL query here...\n\nExample:\nSELECT * FROM table_name LIMIT 100;"
     : 'Select a server to start querying';
@@ -300,6 +321,7 @@ const QueryEditor = memo(({
             placeholder(editorPlaceholder),
             autocompletion({ override: [completionSource] }),
             EditorView.editable.of(Boolean(serverId) && !isExecuting),
+            errorExtension,
             EditorView.theme({
               '&': {
                 height: '100%',
@@ -309,6 +331,7 @@ const QueryEditor = memo(({
               },
               '.cm-scroller': { overflow: 'auto' },
               '.cm-content': { padding: '16px' },
+              '.cm-sql-error': { textDecoration: 'underline wavy #d32f2f' },
             }),
           ]}
           onChange={handleChange}
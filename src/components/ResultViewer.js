@@ -122,6 +122,37 @@ const CellEditor = memo(({ initialValue, placeholder, onCommit, onCancel }) => {
 });
 CellEditor.displayName = "CellEditor";
 
+// Notices and warnings the server sent while the query ran (RAISE NOTICE,
+// deprecation warnings, debug output with a low client_min_messages).
+const NoticeList = ({ notices }) => {
+  if (!notices?.length) return null;
+  return (
+    <Box sx={{ display: "flex", flexDirection: "column", gap: 0.5, p: 1, maxHeight: 160, overflow: "auto" }}>
+      {notices.map((notice, index) => (
+        <Alert
+          key={index}
+          severity={notice.severity === "WARNING" ? "warning" : "info"}
+          sx={{ py: 0 }}
+        >
+          <Typography variant="body2">
+            {notice.severity}: {notice.message}
+          </Typography>
+          {notice.detail && (
+            <Typography variant="caption" component="div">
+              DETAIL: {notice.detail}
+            </Typography>
+          )}
+          {notice.hint && (
+            <Typography variant="caption" component="div">
+              HINT: {notice.hint}
+            </Typography>
+          )}
+        </Alert>
+      ))}
+    </Box>
+  );
+};
+
 const ResultViewer = memo(
   ({
     results = null,
@@ -163,6 +194,8 @@ const ResultViewer = memo(
       return results.message;
     }, [results]);
 
+    const notices = results?.notices;
+
     const shouldShowRowsAffected = useMemo(() => {
       if (rowsAffected === null || rowsAffected === undefined) return false;
       return baseRows.length === 0;
@@ -682,6 +715,7 @@ const ResultViewer = memo(
               </Typography>
             )}
           </Alert>
+          <NoticeList notices={notices} />
         </Box>
       );
     }
@@ -708,6 +742,7 @@ const ResultViewer = memo(
               Completed in {executionTime} ms
             </Typography>
           )}
+          <NoticeList notices={notices} />
         </Box>
       );
     }
@@ -869,6 +904,8 @@ const ResultViewer = memo(
           </Box>
         </Box>
 
+        <NoticeList notices={notices} />
+
         {/* Table header + body in a shared horizontal scroller so columns align */}
         <Box sx={{ flex: 1, overflowX: "auto" }}>
           <Box sx={{ minWidth: tableMinWidth }}>
@@ -30,6 +30,29 @@ import QueryEditor from "./QueryEditor";
 import ResultViewer from "./ResultViewer";
 import QueryHistory from "./QueryHistory";
 
+// Render a command error ({ kind, message, code?, detail?, hint? }) as display text
+export const formatCommandError = (error) => {
+  if (error?.kind === "sql") {
+    return [
+      `${error.code}: ${error.message}`,
+      error.detail ? `Detail: ${error.detail}` : null,
+      error.hint ? `Hint: ${error.hint}` : null,
+    ]
+      .filter(Boolean)
+      .join("\n");
+  }
+  return error?.message || error?.toString?.() || String(error);
+};
+
+// Postgres counts an error position in characters from 1 into the SQL it was
+// sent, which is the editor text trimmed; CodeMirror counts UTF-16 units from
+// 0 into the untrimmed text
+export const editorErrorOffset = (sql, position) => {
+  if (!Number.isInteger(position) || position < 1) return null;
+  const leading = sql.length - sql.trimStart().length;
+  return leading + Array.from(sql.trim()).slice(0, position - 1).join("").length;
+};
+
 export const formatBytes = (bytes) => {
   if (bytes === null || bytes === undefined) return "Unknown";
   if (bytes === 0) return "0 B";
@@ -371,7 +394,7 @@ const RightPanel = memo(({ selectedServer, onSchemaRefresh }) => {
         setLastUpdated(now);
         setError(null);
       } catch (err) {
-        setError(err?.toString?.() || String(err));
+        setError(err?.message || err?.toString?.() || String(err));
       } finally {
         setIsLoading(false);
       }
@@ -641,6 +664,7 @@ const RightPanel = memo(({ selectedServer, onSchemaRefresh }) => {
                 isExecuting: true,
                 isCancelling: false,
                 error: null,
+                errorPosition: null,
                 queryId,
               }
             : tab,
@@ -708,9 +732,9 @@ const RightPanel = memo(({ selectedServer, onSchemaRefresh }) => {
         }
       } catch (error) {
         const executionTime = Date.now() - startTime;
-        const errorMessage = error?.toString?.() || String(error);
+        const errorMessage = formatCommandError(error);
         const isCanceled =
-          errorMessage.includes("57014") ||
+          error?.code === "57014" ||
           errorMessage.toLowerCase().includes("canceling statement due to user request");
 
         // Update tab with error
@@ -724,6 +748,7 @@ const RightPanel = memo(({ selectedServer, onSchemaRefresh }) => {
                   error: isCanceled
                     ? "Query canceled."
                     : `Error executing query:\n${sql}\n\n${errorMessage}`,
+                  errorPosition: isCanceled ? null : editorErrorOffset(sql, error?.position),
                   isExecuting: false,
                   isCancelling: false,
                   queryId: null,
@@ -937,7 +962,7 @@ const RightPanel = memo(({ selectedServer, onSchemaRefresh }) => {
       }
     } catch (error) {
       const executionTime = Date.now() - startTime;
-      const errorMessage = error?.toString?.() || String(error);
+      const errorMessage = formatCommandError(error);
 
       setTabs((prev) =>
         prev.map((tab, index) =>
@@ -1008,7 +1033,7 @@ const RightPanel = memo(({ selectedServer, onSchemaRefresh }) => {
       );
     } catch (error) {
       const executionTime = Date.now() - startTime;
-      const errorMessage = error?.toString?.() || String(error);
+      const errorMessage = formatCommandError(error);
 
       setTabs((prev) =>
         prev.map((tab, index) =>
@@ -1077,7 +1102,7 @@ const RightPanel = memo(({ selectedServer, onSchemaRefresh }) => {
       );
     } catch (error) {
       const executionTime = Date.now() - startTime;
-      const errorMessage = error?.toString?.() || String(error);
+      const errorMessage = formatCommandError(error);
 
       setTabs((prev) =>
         prev.map((tab, index) =>
@@ -1591,6 +1616,7 @@ const RightPanel = memo(({ selectedServer, onSchemaRefresh }) => {
                   onShowHistory={handleShowHistory}
                   isExecuting={currentTab.isExecuting}
                   isCancelling={currentTab.isCancelling}
+                  errorPosition={currentTab.errorPosition ?? null}
                 />
               </Box>
 
@@ -69,4 +69,24 @@ describe("ResultViewer", () => {
     expect(screen.getByText(/3 rows affected/i)).toBeInTheDocument();
     expect(screen.getByText(/completed in 15 ms/i)).toBeInTheDocument();
   });
+
+  it("shows server notices with the result", () => {
+    render(
+      <ResultViewer
+        results={{
+          columns: [],
+          rows: [],
+          notices: [
+            { severity: "NOTICE", code: "00000", message: "table skipped" },
+            { severity: "WARNING", code: "01000", message: "careful", hint: "check it" },
+          ],
+        }}
+        rowsAffected={0}
+      />,
+    );
+
+    expect(screen.getByText("NOTICE: table skipped")).toBeInTheDocument();
+    expect(screen.getByText("WARNING: careful")).toBeInTheDocument();
+    expect(screen.getByText("HINT: check it")).toBeInTheDocument();
+  });
 });
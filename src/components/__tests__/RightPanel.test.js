@@ -1,5 +1,5 @@
 import { describe, it, expect } from "vitest";
-import { formatBytes } from "../RightPanel";
+import { editorErrorOffset, formatBytes } from "../RightPanel";
 
 describe("RightPanel formatBytes", () => {
   it("formats bytes into readable strings", () => {
@@ -9,3 +9,12 @@ describe("RightPanel formatBytes", () => {
     expect(formatBytes(10 * 1024 * 1024)).toBe("10 MB");
   });
 });
+
+describe("RightPanel editorErrorOffset", () => {
+  it("maps a server error position into the editor text", () => {
+    expect(editorErrorOffset("SELECT * FORM t", 10)).toBe(9);
+    expect(editorErrorOffset("\n  SELECT 1 +", 11)).toBe(13);
+    expect(editorErrorOffset("SELECT '😀' FORM t", 12)).toBe(12);
+    expect(editorErrorOffset("SELECT 1", undefined)).toBe(null);
+  });
+});